@@ -0,0 +1,60 @@
+//! Software effects: anything that draws the keys frame by frame and uploads
+//! them as custom frames, as opposed to the effects the keyboard runs itself.
+
+use crate::device::Device;
+use crate::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use crate::transport::TransportError;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub trait Animation: Send {
+    /// Draws the keys at `t` since the start. Returns how long until the
+    /// next frame, or `None` when the animation is over.
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> Option<Duration>;
+}
+
+/// Plays on a shared device, locking it only to upload each frame. After a
+/// frame `wait` gets the time until the next one and returns false to stop.
+pub fn play(
+    device: &Mutex<Device>,
+    animation: &mut dyn Animation,
+    mut wait: impl FnMut(Duration) -> bool,
+) -> Result<(), TransportError> {
+    let mut frame = {
+        let device = device.lock().unwrap();
+        Frame::new(device.frame().rows(), device.frame().cols())
+    };
+    let started = Instant::now();
+    while let Some(next) = animation.render(started.elapsed(), &mut frame) {
+        device.lock().unwrap().set_frame(&frame)?;
+        if !wait(next) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Plays until the animation ends, sleeping between frames.
+pub fn run(device: &Mutex<Device>, animation: &mut dyn Animation) -> Result<(), TransportError> {
+    play(device, animation, |next| {
+        thread::sleep(next);
+        true
+    })
+}
+
+/// Renders up to `count` frames without a keyboard, moving time on by what
+/// each frame asks for. Stops early if the animation ends.
+pub fn render_frames(animation: &mut dyn Animation, count: usize) -> Vec<Frame> {
+    let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+    let mut frames = Vec::with_capacity(count);
+    let mut t = Duration::ZERO;
+    while frames.len() < count {
+        let Some(next) = animation.render(t, &mut frame) else {
+            break;
+        };
+        frames.push(frame.clone());
+        t += next;
+    }
+    frames
+}
@@ -0,0 +1,138 @@
+//! Async front end to a `Transport`, for tokio services.
+//!
+//! USB transfers block, so each exchange runs on tokio's blocking pool. Calls
+//! are serialized on the transport and bounded by a timeout. Dropping a call's
+//! future (or hitting the timeout) before its exchange started cancels it; an
+//! exchange already on the wire always runs to completion.
+
+use crate::commands;
+use crate::descriptor::{DeviceDescriptor, ORNATA_V3};
+use crate::razer_report::{RazerReport, RAZER_CMD_SUCCESSFUL};
+use crate::transport::{Transport, TransportError};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(12);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncError {
+    Transport(TransportError),
+    /// No answer within the client's timeout.
+    Timeout,
+    /// The blocking task panicked or the runtime is shutting down.
+    Aborted,
+}
+
+impl fmt::Display for AsyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsyncError::Transport(err) => err.fmt(f),
+            AsyncError::Timeout => write!(f, "timed out waiting for the device"),
+            AsyncError::Aborted => write!(f, "device task aborted"),
+        }
+    }
+}
+
+impl std::error::Error for AsyncError {}
+
+impl From<TransportError> for AsyncError {
+    fn from(err: TransportError) -> Self {
+        AsyncError::Transport(err)
+    }
+}
+
+/// Sets the flag when the owning future goes away, whatever the reason.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone)]
+pub struct AsyncClient {
+    transport: Arc<Mutex<Box<dyn Transport>>>,
+    timeout: Duration,
+    descriptor: DeviceDescriptor,
+}
+
+impl AsyncClient {
+    pub fn new(transport: impl Transport + 'static) -> Self {
+        Self {
+            transport: Arc::new(Mutex::new(Box::new(transport))),
+            timeout: DEFAULT_TIMEOUT,
+            descriptor: ORNATA_V3,
+        }
+    }
+
+    /// Talks to a keyboard other than the Ornata V3.
+    pub fn with_descriptor(mut self, descriptor: DeviceDescriptor) -> Self {
+        self.descriptor = descriptor;
+        self
+    }
+
+    /// Overrides how long a call may take, including waiting for its turn.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends `request` and returns the raw response, whatever its status.
+    pub async fn exchange(&self, request: RazerReport) -> Result<RazerReport, AsyncError> {
+        let transport = Arc::clone(&self.transport);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _guard = CancelOnDrop(Arc::clone(&cancelled));
+
+        let task = tokio::task::spawn_blocking(move || {
+            let mut transport = transport.lock().unwrap();
+            if cancelled.load(Ordering::SeqCst) {
+                return None;
+            }
+            Some(transport.exchange(&request))
+        });
+        match tokio::time::timeout(self.timeout, task).await {
+            Ok(Ok(Some(result))) => Ok(result?),
+            Ok(Ok(None)) | Ok(Err(_)) => Err(AsyncError::Aborted),
+            Err(_) => Err(AsyncError::Timeout),
+        }
+    }
+
+    /// Sends a command and checks that the keyboard accepted it.
+    pub async fn apply(&self, report: RazerReport) -> Result<(), AsyncError> {
+        self.query(report).await.map(|_| ())
+    }
+
+    /// Sends a request with the device's transaction id and returns the
+    /// successful response carrying its data.
+    pub async fn query(&self, mut request: RazerReport) -> Result<RazerReport, AsyncError> {
+        request.transaction_id = self.descriptor.transaction_id;
+        let response = self.exchange(request).await?;
+        if response.status == RAZER_CMD_SUCCESSFUL {
+            Ok(response)
+        } else {
+            Err(TransportError::Status(response.status).into())
+        }
+    }
+
+    /// Firmware version as `(major, minor)`.
+    pub async fn firmware_version(&self) -> Result<(u8, u8), AsyncError> {
+        let response = self.query(RazerReport::get_firmware()).await?;
+        let def = commands::find("get_firmware").expect("get_firmware is in the command table");
+        Ok((def.byte(&response, "major"), def.byte(&response, "minor")))
+    }
+
+    pub async fn serial(&self) -> Result<String, AsyncError> {
+        let response = self.query(RazerReport::get_serial()).await?;
+        let def = commands::find("get_serial").expect("get_serial is in the command table");
+        Ok(def.text(&response, "serial"))
+    }
+
+    pub async fn brightness(&self) -> Result<u8, AsyncError> {
+        let response = self.query(RazerReport::get_brightness()).await?;
+        let def = commands::find("get_brightness").expect("get_brightness is in the command table");
+        Ok(def.byte(&response, "brightness"))
+    }
+}
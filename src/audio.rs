@@ -0,0 +1,416 @@
+//! A spectrum analyzer on the keys: one bar per matrix column, low notes on
+//! the left, rising from the bottom row.
+//!
+//! Sound comes from an `AudioSource`: a WAV file, raw PCM (e.g. piped from
+//! `ffmpeg` or `parec` on stdin), or with the `live-audio` feature the
+//! default input device.
+
+use crate::animation::Animation;
+use crate::color::{Gradient, Rgb};
+use crate::emojis::EMOJI_WARNING;
+use crate::frame::Frame;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Samples per FFT; about 46 ms at 44.1 kHz.
+pub const FFT_SIZE: usize = 2048;
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+/// Columns span this range on a log scale, capped at the Nyquist frequency.
+const LOWEST_HZ: f32 = 40.0;
+const HIGHEST_HZ: f32 = 16_000.0;
+/// How far below full scale an empty bar is, in dB.
+const FLOOR_DB: f32 = 60.0;
+
+pub trait AudioSource: Send {
+    fn sample_rate(&self) -> u32;
+    /// Fills `buf` with mono samples in -1..=1, blocking until there are
+    /// some. Returns how many were read; 0 means the stream ended.
+    fn read(&mut self, buf: &mut [f32]) -> io::Result<usize>;
+}
+
+impl<S: AudioSource + ?Sized> AudioSource for Box<S> {
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+
+    fn read(&mut self, buf: &mut [f32]) -> io::Result<usize> {
+        (**self).read(buf)
+    }
+}
+
+/// Averages interleaved frames down to mono.
+fn downmix(samples: &[f32], channels: usize, out: &mut [f32]) -> usize {
+    let frames = (samples.len() / channels).min(out.len());
+    for (i, out) in out.iter_mut().take(frames).enumerate() {
+        let frame = &samples[i * channels..(i + 1) * channels];
+        *out = frame.iter().sum::<f32>() / channels as f32;
+    }
+    frames
+}
+
+/// A WAV stream in any format `hound` reads.
+pub struct WavSource {
+    reader: hound::WavReader<Box<dyn Read + Send>>,
+    interleaved: Vec<f32>,
+}
+
+impl WavSource {
+    pub fn new(reader: impl Read + Send + 'static) -> io::Result<Self> {
+        let reader: Box<dyn Read + Send> = Box::new(reader);
+        let reader = hound::WavReader::new(reader)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Self {
+            reader,
+            interleaved: Vec::new(),
+        })
+    }
+}
+
+impl AudioSource for WavSource {
+    fn sample_rate(&self) -> u32 {
+        self.reader.spec().sample_rate
+    }
+
+    fn read(&mut self, buf: &mut [f32]) -> io::Result<usize> {
+        let spec = self.reader.spec();
+        let channels = spec.channels.max(1) as usize;
+        let wanted = buf.len() * channels;
+        let invalid = |err: hound::Error| io::Error::new(io::ErrorKind::InvalidData, err);
+        self.interleaved.clear();
+        match spec.sample_format {
+            hound::SampleFormat::Float => {
+                for sample in self.reader.samples::<f32>().take(wanted) {
+                    self.interleaved.push(sample.map_err(invalid)?);
+                }
+            }
+            hound::SampleFormat::Int => {
+                let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+                for sample in self.reader.samples::<i32>().take(wanted) {
+                    self.interleaved
+                        .push(sample.map_err(invalid)? as f32 / scale);
+                }
+            }
+        }
+        Ok(downmix(&self.interleaved, channels, buf))
+    }
+}
+
+/// Headerless signed 16-bit little-endian PCM, interleaved.
+pub struct PcmSource<R> {
+    reader: R,
+    sample_rate: u32,
+    channels: usize,
+    bytes: Vec<u8>,
+    interleaved: Vec<f32>,
+}
+
+impl<R: Read + Send> PcmSource<R> {
+    pub fn new(reader: R, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            reader,
+            sample_rate,
+            channels: channels.max(1) as usize,
+            bytes: Vec::new(),
+            interleaved: Vec::new(),
+        }
+    }
+}
+
+impl<R: Read + Send> AudioSource for PcmSource<R> {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, buf: &mut [f32]) -> io::Result<usize> {
+        let frame_size = 2 * self.channels;
+        self.bytes.resize(buf.len() * frame_size, 0);
+        // Read whole frames, so a short read never splits a sample.
+        let mut filled = 0;
+        while filled < self.bytes.len() {
+            match self.reader.read(&mut self.bytes[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+            if filled % frame_size == 0 {
+                break;
+            }
+        }
+        self.interleaved.clear();
+        self.interleaved.extend(
+            self.bytes[..filled - filled % frame_size]
+                .chunks_exact(2)
+                .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / 32768.0),
+        );
+        Ok(downmix(&self.interleaved, self.channels, buf))
+    }
+}
+
+#[cfg(feature = "live-audio")]
+pub use live::LiveSource;
+
+#[cfg(feature = "live-audio")]
+mod live {
+    use super::{downmix, AudioSource};
+    use crate::emojis::EMOJI_WARNING;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use std::io;
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::thread;
+
+    /// The system's default input device. The stream lives on its own
+    /// thread because it can't move between threads on every platform.
+    pub struct LiveSource {
+        sample_rate: u32,
+        channels: usize,
+        chunks: Receiver<Vec<f32>>,
+        pending: Vec<f32>,
+    }
+
+    impl LiveSource {
+        pub fn open() -> io::Result<Self> {
+            let (ready_tx, ready_rx) = mpsc::channel();
+            let (chunks_tx, chunks) = mpsc::channel();
+            thread::spawn(move || match start(chunks_tx) {
+                Ok((stream, format)) => {
+                    let _ = ready_tx.send(Ok(format));
+                    // Keep capturing until the process exits.
+                    let _stream = stream;
+                    loop {
+                        thread::park();
+                    }
+                }
+                Err(err) => {
+                    let _ = ready_tx.send(Err(err));
+                }
+            });
+            let (sample_rate, channels) = ready_rx
+                .recv()
+                .map_err(|_| io::Error::other("audio input thread stopped"))??;
+            Ok(Self {
+                sample_rate,
+                channels: channels.max(1) as usize,
+                chunks,
+                pending: Vec::new(),
+            })
+        }
+    }
+
+    /// Starts capturing into `chunks`; returns the stream with its sample
+    /// rate and channel count.
+    fn start(chunks: Sender<Vec<f32>>) -> io::Result<(cpal::Stream, (u32, u16))> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| io::Error::other("no audio input device"))?;
+        let config = device.default_input_config().map_err(io::Error::other)?;
+        let stream_config: cpal::StreamConfig = config.clone().into();
+        let error = |err: cpal::StreamError| {
+            tracing::warn!(icon = EMOJI_WARNING, "Audio input: {err}");
+        };
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &stream_config,
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    let _ = chunks.send(data.to_vec());
+                },
+                error,
+                None,
+            ),
+            cpal::SampleFormat::I16 => device.build_input_stream(
+                &stream_config,
+                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                    let _ = chunks.send(data.iter().map(|&s| s as f32 / 32768.0).collect());
+                },
+                error,
+                None,
+            ),
+            other => {
+                return Err(io::Error::other(format!(
+                    "unsupported input sample format {other}"
+                )))
+            }
+        }
+        .map_err(io::Error::other)?;
+        stream.play().map_err(io::Error::other)?;
+        Ok((stream, (config.sample_rate().0, config.channels())))
+    }
+
+    impl AudioSource for LiveSource {
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn read(&mut self, buf: &mut [f32]) -> io::Result<usize> {
+            let wanted = buf.len() * self.channels;
+            while self.pending.len() < wanted {
+                match self.chunks.recv() {
+                    Ok(chunk) => self.pending.extend(chunk),
+                    Err(_) => break,
+                }
+            }
+            let take = wanted.min(self.pending.len());
+            let take = take - take % self.channels;
+            let frames = downmix(&self.pending[..take], self.channels, buf);
+            self.pending.drain(..take);
+            Ok(frames)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpectrumSettings {
+    /// Multiplies the signal before it is measured; raise it for quiet input.
+    pub gain: f32,
+    /// How fast bars fall, in full heights per second. They rise at once.
+    pub decay: f32,
+    /// Colors from the bottom row to the top one.
+    pub palette: Gradient,
+}
+
+impl Default for SpectrumSettings {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            decay: 2.0,
+            palette: Gradient::default(),
+        }
+    }
+}
+
+/// Plays an `AudioSource` as bars, reading one frame's worth of samples per
+/// frame so files play in real time. Ends with the stream.
+pub struct SpectrumEffect {
+    source: Box<dyn AudioSource>,
+    settings: SpectrumSettings,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// The last `FFT_SIZE` samples.
+    history: VecDeque<f32>,
+    chunk: Vec<f32>,
+    /// Bar heights in 0..=1, one per column.
+    levels: Vec<f32>,
+}
+
+impl SpectrumEffect {
+    pub fn new(source: impl AudioSource + 'static, settings: SpectrumSettings) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+        // Hann window.
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        let samples_per_frame =
+            (source.sample_rate() as f32 * FRAME_INTERVAL.as_secs_f32()).round() as usize;
+        Self {
+            source: Box::new(source),
+            settings,
+            fft,
+            window,
+            history: VecDeque::from(vec![0.0; FFT_SIZE]),
+            chunk: vec![0.0; samples_per_frame.max(1)],
+            levels: Vec::new(),
+        }
+    }
+
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+
+    /// Reads the next chunk and updates the bars. Returns false at the end
+    /// of the stream.
+    pub fn advance(&mut self, columns: usize) -> io::Result<bool> {
+        let read = self.source.read(&mut self.chunk)?;
+        if read == 0 {
+            return Ok(false);
+        }
+        for &sample in &self.chunk[..read] {
+            self.history.pop_front();
+            self.history.push_back(sample);
+        }
+        let measured = self.measure(columns);
+        let elapsed = read as f32 / self.source.sample_rate().max(1) as f32;
+        let fall = self.settings.decay * elapsed;
+        self.levels.resize(columns, 0.0);
+        for (level, &target) in self.levels.iter_mut().zip(&measured) {
+            *level = target.max(*level - fall);
+        }
+        Ok(true)
+    }
+
+    /// Loudness per column in 0..=1 from the current history.
+    fn measure(&self, columns: usize) -> Vec<f32> {
+        let mut buffer: Vec<Complex<f32>> = self
+            .history
+            .iter()
+            .zip(&self.window)
+            .map(|(&sample, &weight)| Complex::new(sample * weight, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+        // A full-scale sine lands at 1: half the energy per side, and the
+        // Hann window halves the amplitude.
+        let scale = 4.0 * self.settings.gain / FFT_SIZE as f32;
+        let magnitudes: Vec<f32> = buffer[..FFT_SIZE / 2]
+            .iter()
+            .map(|bin| bin.norm() * scale)
+            .collect();
+
+        let rate = self.source.sample_rate() as f32;
+        let bin_hz = rate / FFT_SIZE as f32;
+        let highest = HIGHEST_HZ.min(rate / 2.0);
+        let edge = |i: usize| LOWEST_HZ * (highest / LOWEST_HZ).powf(i as f32 / columns as f32);
+        (0..columns)
+            .map(|column| {
+                let (low, high) = (edge(column), edge(column + 1));
+                let first = (low / bin_hz).ceil() as usize;
+                let last = ((high / bin_hz).floor() as usize).min(magnitudes.len() - 1);
+                // Narrow low bands may fall between bins: use the nearest.
+                let peak = if first <= last {
+                    magnitudes[first..=last].iter().copied().fold(0.0, f32::max)
+                } else {
+                    let center = ((low * high).sqrt() / bin_hz).round() as usize;
+                    magnitudes[center.min(magnitudes.len() - 1)]
+                };
+                if peak <= 0.0 {
+                    return 0.0;
+                }
+                ((20.0 * peak.log10() + FLOOR_DB) / FLOOR_DB).clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        frame.fill(Rgb::BLACK);
+        let rows = frame.rows();
+        let top = rows.saturating_sub(1).max(1) as f32;
+        for (col, &level) in self.levels.iter().enumerate().take(frame.cols()) {
+            let lit = (level * rows as f32).round() as usize;
+            for height in 0..lit.min(rows) {
+                let color = self.settings.palette.at(height as f32 / top);
+                frame.set(rows - 1 - height, col, color);
+            }
+        }
+    }
+}
+
+impl Animation for SpectrumEffect {
+    fn render(&mut self, _t: Duration, frame: &mut Frame) -> Option<Duration> {
+        match self.advance(frame.cols()) {
+            Ok(true) => {
+                self.draw(frame);
+                Some(FRAME_INTERVAL)
+            }
+            Ok(false) => None,
+            Err(err) => {
+                tracing::warn!(icon = EMOJI_WARNING, "Audio input failed: {err}");
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,726 @@
+// Razer Ornata V3 RGB Control - command line
+
+use razer_rgb_mac::animation;
+use razer_rgb_mac::audio::{AudioSource, PcmSource, SpectrumEffect, SpectrumSettings, WavSource};
+use razer_rgb_mac::capture::read_capture;
+use razer_rgb_mac::chroma::{self, ChromaServer};
+use razer_rgb_mac::color::{Gradient, Rgb};
+use razer_rgb_mac::config::{config_dir, load_or_create_token};
+use razer_rgb_mac::control::{self, ControlServer};
+use razer_rgb_mac::descriptor::ORNATA_V3;
+use razer_rgb_mac::device::{connect, Device};
+use razer_rgb_mac::doctor::{self, UsbProbe};
+use razer_rgb_mac::effect::{Effect, DEFAULT_WAVE_DIRECTION, DEFAULT_WAVE_SPEED};
+use razer_rgb_mac::emojis::*;
+use razer_rgb_mac::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use razer_rgb_mac::heatmap::{
+    HeatmapEffect, HeatmapSettings, KeySource, Recorder, Recording, COUNTS_FILE, HEATMAP_FILE,
+};
+use razer_rgb_mac::http;
+use razer_rgb_mac::idle::IdleSettings;
+use razer_rgb_mac::layout::Layout;
+use razer_rgb_mac::logging;
+use razer_rgb_mac::metrics::{MetricsConfig, MetricsEffect, ProcSource, METRICS_FILE};
+use razer_rgb_mac::notify::{self, Notification};
+use razer_rgb_mac::openrgb::{self, OpenRgbServer};
+use razer_rgb_mac::playback::{Clip, Playback, PlaybackSettings};
+use razer_rgb_mac::profile::ProfileStore;
+use razer_rgb_mac::razer_report::RazerReport;
+use razer_rgb_mac::schedule::{Schedule, Scheduler, SystemClock, SCHEDULE_FILE};
+use razer_rgb_mac::script::{self, Limits, Script, ScriptEffect, ScriptStore};
+use razer_rgb_mac::sequence::SequenceStore;
+use razer_rgb_mac::transition::{parse_duration, Fade, Target, Transition};
+use razer_rgb_mac::udev;
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+const USAGE: &str = "\
+Usage: razer-rgb <command> [options]
+
+Set RAZER_RGB_EMULATOR=1 to talk to an emulated keyboard instead of USB.
+Set RAZER_RGB_LOG=debug to log every USB exchange, and RAZER_RGB_LOG_STYLE=plain
+for ASCII tags instead of emoji.
+
+Commands:
+  set <effect> [options]     Switch the lighting: off, static <color>,
+                             breathing <color>, spectrum or wave
+      --brightness <0-255>   Also change the brightness
+      --fade <duration>      Fade over e.g. 500ms or 1.5s instead of switching
+      --easing <curve>       linear, ease-in, ease-out or ease-in-out (default)
+  profile <name> [options]   Apply a saved profile
+      --fade, --easing       Override the profile's own transition
+                             An image layer keeps playing until interrupted
+  play <file> [options]      Play a PNG or animated GIF on the keys
+      --speed <x>            Playback speed, 0.01 to 100 (default 1)
+      --once                 Stop at the end instead of looping
+  script [name] [options]    Run scripts/<name>.rhai (or a .rhai file), reloading on
+                             save; without a name, list the scripts
+      --frames <n>           Render n frames without a keyboard and report them
+  sequence [name]            Play sequences/<name>.json, printing each trigger as it
+                             passes; without a name, list the sequences
+  notify <color> [options]   Flash the keyboard, then put back what it showed
+      --pattern <name>       flash (default), pulse or solid
+      --repeat <n>           How many times (default 3)
+      --duration <duration>  Length of one repeat (default 500ms)
+      --http <port>          Go through `serve` on this port if it is running
+  serve [options]            Expose the keyboard to other software
+      --openrgb <port>       OpenRGB SDK server port (default 6742)
+      --chroma <port>        Chroma SDK REST port (default 54235)
+      --http <port>          HTTP/WebSocket control API port (default 8462)
+                             Also follows the schedule in schedule.json
+      --idle-off <duration>  Turn the backlight off after this long without input (Linux)
+      --idle-dim <duration>  Dim it after this long first
+  schedule                   List the next scheduled profile changes
+  metrics [options]          Show CPU and memory load on the keys (Linux)
+      --interval <duration>  How often to sample (default from metrics.json, 1s)
+  heatmap [options]          Light keys by how often they are pressed (Linux)
+      --half-life <duration> How long heat takes to halve (default from heatmap.json, 10s)
+      --all-time             Don't cool down; show every press, scaled to the busiest key
+      --keep                 Add to and save the counts in heatmap-counts.json
+      --replay <file>        Replay presses recorded with --record instead
+      --record <file>        Write the presses to a file for --replay
+  visualize <input> [options] Draw an audio spectrum: a WAV file, `-` for raw
+                             16-bit little-endian PCM on stdin, or `live`
+      --rate <hz>            Sample rate of raw PCM (default 44100)
+      --channels <n>         Channels of raw PCM (default 2)
+      --gain <x>             Amplify quiet input (default 1)
+      --decay <x>            How fast bars fall, in heights per second (default 2)
+      --palette <colors>     Bottom to top, e.g. blue,magenta,white
+  decode <hex>               Describe a 90-byte report given as hex
+      --capture <file>       Describe every exchange in a RAZER_RGB_CAPTURE file
+  doctor                     Check the USB setup and say what to fix
+  udev-rule                  Print the udev rule for non-root access on Linux
+      --install              Write it to /etc/udev/rules.d and reload udev (needs root)
+";
+
+fn main() -> ExitCode {
+    logging::init(None);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("set") => set(&args[1..]),
+        Some("profile") => profile(&args[1..]),
+        Some("play") => play(&args[1..]),
+        Some("script") => script(&args[1..]),
+        Some("sequence") => sequence(&args[1..]),
+        Some("notify") => notify(&args[1..]),
+        Some("serve") => serve(&args[1..]),
+        Some("schedule") => schedule(),
+        Some("metrics") => metrics(&args[1..]),
+        Some("heatmap") => heatmap(&args[1..]),
+        Some("visualize") => visualize(&args[1..]),
+        Some("decode") => decode(&args[1..]),
+        Some("doctor") => doctor(),
+        Some("udev-rule") => udev_rule(&args[1..]),
+        _ => {
+            eprint!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            tracing::error!(icon = EMOJI_CROSS, "{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns the value following `flag`, if present.
+fn option<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+/// The `--fade`/`--easing` options, if a fade was asked for.
+fn transition(args: &[String]) -> Result<Option<Transition>, String> {
+    let Some(duration) = option(args, "--fade") else {
+        return Ok(None);
+    };
+    let easing = match option(args, "--easing") {
+        Some(easing) => easing.parse()?,
+        None => Default::default(),
+    };
+    Ok(Some(Transition::new(parse_duration(duration)?, easing)))
+}
+
+fn open_device() -> Result<Arc<Mutex<Device>>, String> {
+    let device = connect().map_err(|err| err.to_string())?;
+    Ok(Arc::new(Mutex::new(device)))
+}
+
+/// Parses the port following `flag`, falling back to `default`.
+fn port(args: &[String], flag: &str, default: u16) -> Result<u16, String> {
+    match option(args, flag) {
+        Some(port) => port.parse().map_err(|_| format!("invalid port: {port}")),
+        None => Ok(default),
+    }
+}
+
+fn serve(args: &[String]) -> Result<(), String> {
+    let openrgb_port = port(args, "--openrgb", openrgb::DEFAULT_PORT)?;
+    let chroma_port = port(args, "--chroma", chroma::DEFAULT_PORT)?;
+    let http_port = port(args, "--http", control::DEFAULT_PORT)?;
+    let idle = idle_settings(args)?;
+    let token_path = config_dir().join(control::TOKEN_FILE);
+    let token = load_or_create_token(&token_path)
+        .map_err(|err| format!("cannot read {}: {err}", token_path.display()))?;
+    let device = open_device()?;
+
+    let openrgb = OpenRgbServer::bind(("127.0.0.1", openrgb_port), Arc::clone(&device))
+        .map_err(|err| format!("cannot listen on port {openrgb_port}: {err}"))?;
+    tracing::info!(
+        icon = EMOJI_RADAR,
+        "OpenRGB SDK server listening on 127.0.0.1:{openrgb_port}"
+    );
+    let chroma = ChromaServer::bind(("127.0.0.1", chroma_port), Arc::clone(&device))
+        .map_err(|err| format!("cannot listen on port {chroma_port}: {err}"))?;
+    tracing::info!(
+        icon = EMOJI_RADAR,
+        "Chroma SDK REST server listening on 127.0.0.1:{chroma_port}"
+    );
+
+    let control = ControlServer::bind(
+        ("127.0.0.1", http_port),
+        Arc::clone(&device),
+        token,
+        ProfileStore::default(),
+    )
+    .map_err(|err| format!("cannot listen on port {http_port}: {err}"))?;
+    tracing::info!(
+        icon = EMOJI_RADAR,
+        "Control API listening on 127.0.0.1:{http_port} (token in {})",
+        token_path.display()
+    );
+
+    let schedule = load_schedule()?;
+    if !schedule.rules.is_empty() {
+        tracing::info!(
+            icon = EMOJI_STOPWATCH,
+            "Following {} schedule rules",
+            schedule.rules.len()
+        );
+        Scheduler::new(schedule, SystemClock).spawn(Arc::clone(&device), ProfileStore::default());
+    }
+
+    if let Some(settings) = idle {
+        start_idle(settings, Arc::clone(&device))?;
+        tracing::info!(
+            icon = EMOJI_LAMP,
+            "Turning the backlight off after {}s without input",
+            settings.off_after.as_secs()
+        );
+    }
+
+    let servers = [openrgb.spawn(), chroma.spawn(), control.spawn()];
+    for server in servers {
+        match server.join() {
+            Ok(result) => result.map_err(|err| err.to_string())?,
+            Err(_) => return Err("server thread panicked".to_string()),
+        }
+    }
+    Ok(())
+}
+
+fn parse_effect(args: &[&str]) -> Result<Effect, String> {
+    let color = |name: &str| -> Result<Rgb, String> {
+        let text = args.get(1).ok_or_else(|| format!("{name} needs a color"))?;
+        text.parse().map_err(|err| format!("{err}"))
+    };
+    match args.first().copied() {
+        Some("off") => Ok(Effect::Off),
+        Some("static") => Ok(Effect::Static(color("static")?)),
+        Some("breathing") => Ok(Effect::Breathing(color("breathing")?)),
+        Some("spectrum") => Ok(Effect::Spectrum),
+        Some("wave") => Ok(Effect::Wave {
+            direction: DEFAULT_WAVE_DIRECTION,
+            speed: DEFAULT_WAVE_SPEED,
+        }),
+        Some(other) => Err(format!("unknown effect \"{other}\"")),
+        None => Err("expected an effect".to_string()),
+    }
+}
+
+/// Applies `target`, at once unless a fade was asked for.
+fn switch(device: &mut Device, target: &Target, fade: Option<Transition>) -> Result<(), String> {
+    // A fresh connection only knows defaults; the fade starts from the
+    // keyboard's real brightness.
+    device
+        .read_brightness()
+        .map_err(|err| format!("cannot read brightness: {err}"))?;
+    let to = target.resolve(device.state());
+    let transition = fade.unwrap_or_default();
+    Fade::new(&device.state().clone(), &to, &transition)
+        .run(device)
+        .map_err(|err| err.to_string())?;
+    tracing::info!(icon = EMOJI_CHECK, "{} applied", to.effect.name());
+    Ok(())
+}
+
+fn set(args: &[String]) -> Result<(), String> {
+    // Positional arguments come before the first option.
+    let positional: Vec<&str> = args
+        .iter()
+        .map(String::as_str)
+        .take_while(|arg| !arg.starts_with("--"))
+        .collect();
+    let mut target = Target::effect(parse_effect(&positional)?);
+    if let Some(brightness) = option(args, "--brightness") {
+        let brightness = brightness
+            .parse()
+            .map_err(|_| format!("invalid brightness: {brightness}"))?;
+        target.brightness = Some(brightness);
+    }
+    let fade = transition(args)?;
+    let device = open_device()?;
+    let mut device = device.lock().unwrap();
+    switch(&mut device, &target, fade)
+}
+
+fn profile(args: &[String]) -> Result<(), String> {
+    let name = args
+        .first()
+        .filter(|arg| !arg.starts_with("--"))
+        .ok_or("expected a profile name")?;
+    let profile = ProfileStore::default()
+        .load(name)
+        .map_err(|err| format!("cannot load profile \"{name}\": {err}"))?;
+    let fade = transition(args)?.or(profile.transition);
+    let device = open_device()?;
+    let mut layer = None;
+    {
+        let mut device = device.lock().unwrap();
+        if let Some(spec) = &profile.layer {
+            let layout = Layout::for_device(device.descriptor());
+            let animation = spec
+                .animation(&layout)
+                .map_err(|err| format!("cannot load the layer of \"{name}\": {err}"))?;
+            layer = Some(animation);
+        }
+        switch(&mut device, &profile.target(), fade)?;
+    }
+    match layer {
+        Some(mut animation) => {
+            animation::run(&device, animation.as_mut()).map_err(|err| err.to_string())
+        }
+        None => Ok(()),
+    }
+}
+
+fn play(args: &[String]) -> Result<(), String> {
+    let path = args
+        .first()
+        .filter(|arg| !arg.starts_with("--"))
+        .ok_or("expected a PNG or GIF file")?;
+    let settings = PlaybackSettings {
+        speed: number(args, "--speed", 1.0)?,
+        looping: !args.iter().any(|arg| arg == "--once"),
+    };
+    settings.validate()?;
+    let clip = Clip::open(Path::new(path)).map_err(|err| format!("cannot read {path}: {err}"))?;
+    let device = open_device()?;
+    let layout = Layout::for_device(device.lock().unwrap().descriptor());
+    tracing::info!(
+        icon = EMOJI_PAINT,
+        "Playing {path}: {} frames, {:?}",
+        clip.len(),
+        clip.duration()
+    );
+    let mut playback = Playback::new(&clip, &layout, settings);
+    animation::run(&device, &mut playback).map_err(|err| err.to_string())
+}
+
+fn notify(args: &[String]) -> Result<(), String> {
+    let color = args
+        .first()
+        .filter(|arg| !arg.starts_with("--"))
+        .ok_or("expected a color")?;
+    let pattern = match option(args, "--pattern") {
+        Some(pattern) => pattern.parse()?,
+        None => Default::default(),
+    };
+    let mut notification =
+        Notification::new(pattern, color.parse().map_err(|err| format!("{err}"))?);
+    if let Some(repeat) = option(args, "--repeat") {
+        notification.repeat = repeat
+            .parse()
+            .map_err(|_| format!("invalid repeat count: {repeat}"))?;
+    }
+    if let Some(duration) = option(args, "--duration") {
+        notification.duration = parse_duration(duration)?;
+    }
+    notification.validate()?;
+
+    // A running `serve` holds the keyboard and knows what it shows, so the
+    // flash goes through it when there is one.
+    let http_port = port(args, "--http", control::DEFAULT_PORT)?;
+    let token_path = config_dir().join(control::TOKEN_FILE);
+    if let Ok(token) = fs::read_to_string(&token_path) {
+        let auth = format!("Bearer {}", token.trim());
+        match http::request_with_headers(
+            ("127.0.0.1", http_port),
+            "POST",
+            "/notify",
+            &[("Authorization", &auth)],
+            Some(&json!(notification)),
+        ) {
+            Ok((200, _)) => return Ok(()),
+            Ok((status, body)) => {
+                return Err(format!(
+                    "server answered {status}: {}",
+                    String::from_utf8_lossy(&body)
+                ))
+            }
+            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(err) => return Err(format!("cannot reach port {http_port}: {err}")),
+        }
+    }
+    let device = open_device()?;
+    let mut device = device.lock().unwrap();
+    notify::notify(&mut device, &notification).map_err(|err| err.to_string())
+}
+
+fn idle_settings(args: &[String]) -> Result<Option<IdleSettings>, String> {
+    let Some(off_after) = option(args, "--idle-off") else {
+        if option(args, "--idle-dim").is_some() {
+            return Err("--idle-dim needs --idle-off".to_string());
+        }
+        return Ok(None);
+    };
+    let mut settings = IdleSettings::new(parse_duration(off_after)?);
+    if let Some(dim_after) = option(args, "--idle-dim") {
+        settings.dim_after = Some(parse_duration(dim_after)?);
+    }
+    Ok(Some(settings))
+}
+
+/// Dims and turns off the backlight while nobody types.
+#[cfg(target_os = "linux")]
+fn start_idle(settings: IdleSettings, device: Arc<Mutex<Device>>) -> Result<(), String> {
+    use razer_rgb_mac::idle::{IdleDimmer, InputEvents};
+    let source = InputEvents::open().map_err(|err| format!("cannot watch input: {err}"))?;
+    IdleDimmer::new(source, settings).spawn(device);
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn start_idle(_settings: IdleSettings, _device: Arc<Mutex<Device>>) -> Result<(), String> {
+    Err("idle detection is only available on Linux".to_string())
+}
+
+fn load_schedule() -> Result<Schedule, String> {
+    let path = config_dir().join(SCHEDULE_FILE);
+    Schedule::load(&path).map_err(|err| format!("cannot read {}: {err}", path.display()))
+}
+
+fn schedule() -> Result<(), String> {
+    let schedule = load_schedule()?;
+    let scheduler = Scheduler::new(schedule, SystemClock);
+    if let Some(active) = scheduler.schedule().active(scheduler.now()) {
+        println!("{:<9}  {}", "now", active.profile);
+    }
+    for change in scheduler.upcoming(10) {
+        let rule = &scheduler.schedule().rules[change.rule];
+        println!(
+            "{}  {}  ({})",
+            change.at.format("%a %H:%M"),
+            change.profile,
+            rule.when
+        );
+    }
+    Ok(())
+}
+
+fn metrics(args: &[String]) -> Result<(), String> {
+    let path = config_dir().join(METRICS_FILE);
+    let mut config = MetricsConfig::load(&path)
+        .map_err(|err| format!("cannot read {}: {err}", path.display()))?;
+    if let Some(interval) = option(args, "--interval") {
+        config.interval = parse_duration(interval)?;
+        config.validate()?;
+    }
+    let device = open_device()?;
+    tracing::info!(
+        icon = EMOJI_STOPWATCH,
+        "Showing system metrics every {:?}",
+        config.interval
+    );
+    let mut effect = MetricsEffect::new(ProcSource::new(), config);
+    animation::run(&device, &mut effect).map_err(|err| err.to_string())
+}
+
+fn heatmap(args: &[String]) -> Result<(), String> {
+    let path = config_dir().join(HEATMAP_FILE);
+    let mut settings = HeatmapSettings::load(&path)
+        .map_err(|err| format!("cannot read {}: {err}", path.display()))?;
+    if let Some(half_life) = option(args, "--half-life") {
+        settings.half_life = Some(parse_duration(half_life)?);
+    }
+    if args.iter().any(|arg| arg == "--all-time") {
+        settings.half_life = None;
+    }
+    settings.validate()?;
+    let mut source: Box<dyn KeySource> = match option(args, "--replay") {
+        Some(file) => Box::new(
+            Recording::open(Path::new(file)).map_err(|err| format!("cannot read {file}: {err}"))?,
+        ),
+        None => live_keys()?,
+    };
+    if let Some(file) = option(args, "--record") {
+        let out = File::create(file).map_err(|err| format!("cannot create {file}: {err}"))?;
+        source = Box::new(Recorder::new(source, out));
+    }
+    let device = open_device()?;
+    let layout = Layout::for_device(device.lock().unwrap().descriptor());
+    // Presses find their keys by code or by name, and the plain grid has neither.
+    let placeable = layout
+        .keys
+        .iter()
+        .any(|key| key.code.is_some() || key.name.is_some());
+    if !placeable {
+        tracing::warn!(
+            icon = EMOJI_WARNING,
+            "The {} layout has no key names or codes, so presses can't be placed; see {}",
+            layout.name,
+            Layout::path_for(device.lock().unwrap().descriptor()).display()
+        );
+    }
+    let mut effect = HeatmapEffect::new(source, layout, settings);
+    if args.iter().any(|arg| arg == "--keep") {
+        let counts = config_dir().join(COUNTS_FILE);
+        effect
+            .keep_counts(&counts)
+            .map_err(|err| format!("cannot read {}: {err}", counts.display()))?;
+        tracing::info!(
+            icon = EMOJI_FILE,
+            "Keeping counts in {} ({} presses so far)",
+            counts.display(),
+            effect.counts().total()
+        );
+    }
+    tracing::info!(icon = EMOJI_PAINT, "Showing the typing heatmap");
+    animation::run(&device, &mut effect).map_err(|err| err.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn live_keys() -> Result<Box<dyn KeySource>, String> {
+    let source = razer_rgb_mac::heatmap::KeyEvents::open()
+        .map_err(|err| format!("cannot watch input: {err}"))?;
+    Ok(Box::new(source))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn live_keys() -> Result<Box<dyn KeySource>, String> {
+    Err("reading key presses is only available on Linux; use --replay".to_string())
+}
+
+/// Parses the number following `flag`, falling back to `default`.
+fn number<T: std::str::FromStr>(args: &[String], flag: &str, default: T) -> Result<T, String> {
+    match option(args, flag) {
+        Some(text) => text.parse().map_err(|_| format!("invalid {flag}: {text}")),
+        None => Ok(default),
+    }
+}
+
+fn visualize(args: &[String]) -> Result<(), String> {
+    let mut settings = SpectrumSettings {
+        gain: number(args, "--gain", 1.0)?,
+        decay: number(args, "--decay", 2.0)?,
+        ..Default::default()
+    };
+    if let Some(palette) = option(args, "--palette") {
+        let colors = palette
+            .split(',')
+            .map(|color| color.trim().parse().map_err(|err| format!("{err}")))
+            .collect::<Result<Vec<Rgb>, String>>()?;
+        settings.palette = Gradient::try_from(colors)?;
+    }
+    let source: Box<dyn AudioSource> = match args.first().map(String::as_str) {
+        Some("-") => Box::new(PcmSource::new(
+            io::stdin(),
+            number(args, "--rate", 44100)?,
+            number(args, "--channels", 2)?,
+        )),
+        Some("live") => live_audio()?,
+        Some(path) => {
+            let file = File::open(path).map_err(|err| format!("cannot open {path}: {err}"))?;
+            Box::new(WavSource::new(BufReader::new(file)).map_err(|err| format!("{path}: {err}"))?)
+        }
+        None => return Err("expected a WAV file, - or live".to_string()),
+    };
+    let device = open_device()?;
+    tracing::info!(
+        icon = EMOJI_WAVE,
+        "Visualizing {} Hz audio",
+        source.sample_rate()
+    );
+    let mut effect = SpectrumEffect::new(source, settings);
+    animation::run(&device, &mut effect).map_err(|err| err.to_string())
+}
+
+#[cfg(feature = "live-audio")]
+fn live_audio() -> Result<Box<dyn AudioSource>, String> {
+    let source = razer_rgb_mac::audio::LiveSource::open()
+        .map_err(|err| format!("cannot open the audio input: {err}"))?;
+    Ok(Box::new(source))
+}
+
+#[cfg(not(feature = "live-audio"))]
+fn live_audio() -> Result<Box<dyn AudioSource>, String> {
+    Err("live capture needs a build with --features live-audio".to_string())
+}
+
+fn script(args: &[String]) -> Result<(), String> {
+    let Some(name) = args.first().filter(|arg| !arg.starts_with("--")) else {
+        let names = ScriptStore::default()
+            .list()
+            .map_err(|err| err.to_string())?;
+        for name in names {
+            println!("{name}");
+        }
+        return Ok(());
+    };
+    let path = if name.ends_with(".rhai") {
+        PathBuf::from(name)
+    } else {
+        ScriptStore::default()
+            .path(name)
+            .map_err(|err| err.to_string())?
+    };
+    if let Some(count) = option(args, "--frames") {
+        let count: usize = count
+            .parse()
+            .map_err(|_| format!("invalid --frames: {count}"))?;
+        // Without a keyboard, draw on the Ornata V3's layout.
+        let layout = Layout::for_device(&ORNATA_V3);
+        return test_script(&path, &layout, count);
+    }
+    let device = open_device()?;
+    let layout = Layout::for_device(device.lock().unwrap().descriptor());
+    let mut effect = ScriptEffect::open(&path, layout, Limits::default())
+        .map_err(|err| format!("cannot load {}: {err}", path.display()))?;
+    tracing::info!(
+        icon = EMOJI_SPARKLES,
+        "Running {}; edits apply on save",
+        path.display()
+    );
+    animation::run(&device, &mut effect).map_err(|err| err.to_string())
+}
+
+fn sequence(args: &[String]) -> Result<(), String> {
+    let store = SequenceStore::default();
+    let Some(name) = args.first().filter(|arg| !arg.starts_with("--")) else {
+        for name in store.list().map_err(|err| err.to_string())? {
+            println!("{name}");
+        }
+        return Ok(());
+    };
+    let sequence = store
+        .load(name)
+        .map_err(|err| format!("cannot load sequence \"{name}\": {err}"))?;
+    let device = open_device()?;
+    tracing::info!(
+        icon = EMOJI_STOPWATCH,
+        "Playing \"{name}\": {} keyframes over {:?}{}",
+        sequence.keyframes.len(),
+        sequence.length(),
+        if sequence.looping { ", looping" } else { "" }
+    );
+    sequence
+        .play(
+            &device,
+            |next| {
+                thread::sleep(next);
+                true
+            },
+            |trigger| println!("{trigger}"),
+        )
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+/// Renders `count` frames and prints how many keys each lit and how long it took.
+fn test_script(path: &Path, layout: &Layout, count: usize) -> Result<(), String> {
+    let source =
+        fs::read_to_string(path).map_err(|err| format!("cannot read {}: {err}", path.display()))?;
+    let mut script = Script::compile(&source, layout, Limits::default())?;
+    let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+    for i in 0..count {
+        let t = script::FRAME_INTERVAL * i as u32;
+        let started = Instant::now();
+        script
+            .render(t, &mut frame)
+            .map_err(|err| format!("frame {i}: {err}"))?;
+        let lit = frame
+            .pixels()
+            .iter()
+            .filter(|&&color| color != Rgb::BLACK)
+            .count();
+        println!(
+            "{i:>4}  t={:>6.3}s  {lit:>3} keys lit  {:?}",
+            t.as_secs_f32(),
+            started.elapsed()
+        );
+    }
+    Ok(())
+}
+
+fn decode(args: &[String]) -> Result<(), String> {
+    if let Some(path) = option(args, "--capture") {
+        let entries = File::open(path)
+            .and_then(|file| read_capture(BufReader::new(file)))
+            .map_err(|err| format!("cannot read {path}: {err}"))?;
+        for (i, entry) in entries.iter().enumerate() {
+            let request = entry
+                .request()
+                .ok_or_else(|| format!("entry {i}: bad request hex"))?;
+            println!("#{i} -> {request}");
+            match entry.result() {
+                Some(Ok(response)) => println!("#{i} <- {response} after {}us", entry.duration_us),
+                Some(Err(err)) => println!("#{i} <- {err}"),
+                None => println!("#{i} <- bad response hex"),
+            }
+        }
+        return Ok(());
+    }
+    // Dumps are often pasted with spaces between bytes, which splits them into
+    // several arguments.
+    let hex = args.join("");
+    let report = RazerReport::from_hex(&hex).ok_or("expected 90 bytes of hex")?;
+    println!("{report}");
+    Ok(())
+}
+
+fn udev_rule(args: &[String]) -> Result<(), String> {
+    if !args.iter().any(|arg| arg == "--install") {
+        print!("{}", udev::rule());
+        return Ok(());
+    }
+    let path = Path::new(udev::RULE_PATH);
+    udev::install(path).map_err(|err| format!("cannot write {}: {err}", path.display()))?;
+    tracing::info!(icon = EMOJI_CHECK, "Wrote {}", path.display());
+    udev::reload().map_err(|err| format!("cannot reload udev: {err}"))?;
+    tracing::info!(
+        icon = EMOJI_RELOAD,
+        "Rules reloaded; replug the keyboard if it is still denied"
+    );
+    Ok(())
+}
+
+fn doctor() -> Result<(), String> {
+    tracing::info!(icon = EMOJI_MAGNIFYING_GLASS, "Checking the keyboard setup");
+    let report = doctor::run(&mut UsbProbe::new());
+    print!("{report}");
+    if report.healthy() {
+        Ok(())
+    } else {
+        Err("some checks failed".to_string())
+    }
+}
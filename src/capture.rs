@@ -0,0 +1,262 @@
+//! Recording and replaying USB traffic.
+//!
+//! `Recorder` wraps any transport and appends one JSON line per exchange to a
+//! file: when it happened, how long it took, the request and either the
+//! response or the error, all as raw hex. Set `RAZER_RGB_CAPTURE=<file>` to
+//! record everything the GUI or CLI sends.
+//!
+//! `Replay` reads such a file back and answers each request with the recorded
+//! response, so a capture from a bug report can be turned into a test.
+
+use crate::emojis::*;
+use crate::razer_report::RazerReport;
+use crate::transport::{Transport, TransportError};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+pub const CAPTURE_ENV: &str = "RAZER_RGB_CAPTURE";
+
+/// One line of a capture file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureEntry {
+    /// Wall clock time the request was sent, in microseconds since the epoch.
+    pub timestamp_us: u64,
+    /// How long the exchange took.
+    pub duration_us: u64,
+    pub request: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<CapturedError>,
+}
+
+/// A `TransportError` in a form that survives the round trip through JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CapturedError {
+    /// The libusb error name, as in `rusb::Error`'s variants.
+    Usb(String),
+    ShortResponse(usize),
+    Status(u8),
+}
+
+const USB_ERRORS: [(rusb::Error, &str); 14] = [
+    (rusb::Error::Io, "Io"),
+    (rusb::Error::InvalidParam, "InvalidParam"),
+    (rusb::Error::Access, "Access"),
+    (rusb::Error::NoDevice, "NoDevice"),
+    (rusb::Error::NotFound, "NotFound"),
+    (rusb::Error::Busy, "Busy"),
+    (rusb::Error::Timeout, "Timeout"),
+    (rusb::Error::Overflow, "Overflow"),
+    (rusb::Error::Pipe, "Pipe"),
+    (rusb::Error::Interrupted, "Interrupted"),
+    (rusb::Error::NoMem, "NoMem"),
+    (rusb::Error::NotSupported, "NotSupported"),
+    (rusb::Error::BadDescriptor, "BadDescriptor"),
+    (rusb::Error::Other, "Other"),
+];
+
+impl From<TransportError> for CapturedError {
+    fn from(err: TransportError) -> Self {
+        match err {
+            TransportError::Usb(err) => {
+                let name = USB_ERRORS
+                    .iter()
+                    .find(|(known, _)| *known == err)
+                    .map_or("Other", |(_, name)| name);
+                CapturedError::Usb(name.to_string())
+            }
+            TransportError::ShortResponse(len) => CapturedError::ShortResponse(len),
+            TransportError::Status(status) => CapturedError::Status(status),
+        }
+    }
+}
+
+impl From<&CapturedError> for TransportError {
+    fn from(err: &CapturedError) -> Self {
+        match err {
+            CapturedError::Usb(name) => TransportError::Usb(
+                USB_ERRORS
+                    .iter()
+                    .find(|(_, known)| known == name)
+                    .map_or(rusb::Error::Other, |(err, _)| *err),
+            ),
+            CapturedError::ShortResponse(len) => TransportError::ShortResponse(*len),
+            CapturedError::Status(status) => TransportError::Status(*status),
+        }
+    }
+}
+
+impl CaptureEntry {
+    pub fn request(&self) -> Option<RazerReport> {
+        RazerReport::from_hex(&self.request)
+    }
+
+    /// What the transport returned, or `None` if the line is corrupt.
+    pub fn result(&self) -> Option<Result<RazerReport, TransportError>> {
+        match (&self.response, &self.error) {
+            (Some(response), _) => RazerReport::from_hex(response).map(Ok),
+            (None, Some(error)) => Some(Err(error.into())),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Passes exchanges through to `inner` and logs each one.
+pub struct Recorder<T: Transport> {
+    inner: T,
+    out: Box<dyn Write + Send>,
+}
+
+impl<T: Transport> Recorder<T> {
+    pub fn new(inner: T, out: impl Write + Send + 'static) -> Self {
+        Self {
+            inner,
+            out: Box::new(out),
+        }
+    }
+
+    /// Appends to `path`, creating it if needed.
+    pub fn create(inner: T, path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(inner, file))
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    fn exchange(&mut self, request: &RazerReport) -> Result<RazerReport, TransportError> {
+        let timestamp_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_micros() as u64);
+        let started = Instant::now();
+        let result = self.inner.exchange(request);
+
+        let entry = CaptureEntry {
+            timestamp_us,
+            duration_us: started.elapsed().as_micros() as u64,
+            request: request.to_hex(),
+            response: result.as_ref().ok().map(RazerReport::to_hex),
+            error: result.err().map(CapturedError::from),
+        };
+        // A capture that can't be written must not break the keyboard.
+        if let Ok(line) = serde_json::to_string(&entry) {
+            let _ = writeln!(self.out, "{line}").and_then(|()| self.out.flush());
+        }
+        result
+    }
+}
+
+/// Wraps `transport` in a `Recorder` when `RAZER_RGB_CAPTURE` names a file.
+pub fn from_env(transport: Box<dyn Transport>) -> Box<dyn Transport> {
+    let Some(path) = std::env::var_os(CAPTURE_ENV).filter(|path| !path.is_empty()) else {
+        return transport;
+    };
+    let path = Path::new(&path);
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => {
+            tracing::info!(
+                icon = EMOJI_FILE,
+                "Recording USB traffic to {}",
+                path.display()
+            );
+            Box::new(Recorder::new(transport, file))
+        }
+        Err(err) => {
+            tracing::warn!(
+                icon = EMOJI_WARNING,
+                "Cannot record to {}: {err}",
+                path.display()
+            );
+            transport
+        }
+    }
+}
+
+#[derive(Default)]
+struct ReplayState {
+    entries: VecDeque<CaptureEntry>,
+    mismatches: Vec<(usize, RazerReport)>,
+    played: usize,
+}
+
+/// Answers requests from a capture, in order.
+///
+/// A request that differs from the recorded one still gets the recorded
+/// answer, but is remembered in `mismatches()` so a test can fail on it.
+/// Once the capture runs out every exchange fails with `NoDevice`, as if the
+/// keyboard had been unplugged. Clones share state.
+#[derive(Clone, Default)]
+pub struct Replay {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl Replay {
+    pub fn new(entries: impl IntoIterator<Item = CaptureEntry>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ReplayState {
+                entries: entries.into_iter().collect(),
+                ..ReplayState::default()
+            })),
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(read_capture(BufReader::new(File::open(path)?))?))
+    }
+
+    /// Entries not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Requests that didn't match the capture, with their position in it.
+    pub fn mismatches(&self) -> Vec<(usize, RazerReport)> {
+        self.state.lock().unwrap().mismatches.clone()
+    }
+}
+
+impl Transport for Replay {
+    fn exchange(&mut self, request: &RazerReport) -> Result<RazerReport, TransportError> {
+        let mut state = self.state.lock().unwrap();
+        let Some(entry) = state.entries.pop_front() else {
+            return Err(TransportError::Usb(rusb::Error::NoDevice));
+        };
+        let index = state.played;
+        state.played += 1;
+        if entry.request().map(|recorded| recorded.to_bytes()) != Some(request.to_bytes()) {
+            state.mismatches.push((index, *request));
+        }
+        entry
+            .result()
+            .unwrap_or(Err(TransportError::Usb(rusb::Error::Other)))
+    }
+}
+
+/// Parses a capture file. Blank lines are skipped.
+pub fn read_capture(reader: impl BufRead) -> io::Result<Vec<CaptureEntry>> {
+    let mut entries = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {err}", number + 1),
+            )
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
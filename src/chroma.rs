@@ -0,0 +1,339 @@
+//! The keyboard part of the Razer Chroma SDK REST API, served on localhost so
+//! Chroma-aware games and apps can drive the keyboard without Synapse.
+//!
+//! A client registers with `POST /razer/chromasdk` and gets back a session
+//! URI. Under it, `PUT /keyboard` applies an effect directly, `POST /keyboard`
+//! stores one for later `PUT /effect`, and `PUT /heartbeat` keeps the session
+//! alive. When the last session ends or times out, the lighting that was
+//! active before the first session is put back.
+
+use crate::color::Rgb;
+use crate::device::{Device, LightingState};
+use crate::effect::Effect;
+use crate::emojis::EMOJI_WARNING;
+use crate::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use crate::http::{self, Request, Response};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_PORT: u16 = 54235;
+pub const SESSION_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub const RZRESULT_SUCCESS: i64 = 0;
+pub const RZRESULT_NOT_SUPPORTED: i64 = 50;
+pub const RZRESULT_INVALID_PARAMETER: i64 = 87;
+pub const RZRESULT_NOT_FOUND: i64 = 1168;
+
+/// Set on a `CHROMA_CUSTOM_KEY` key entry to make it override the grid color.
+const CUSTOM_KEY_FLAG: u64 = 0x0100_0000;
+
+/// A keyboard effect as described by a Chroma request body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyboardEffect {
+    None,
+    Static(Rgb),
+    Custom(Frame),
+}
+
+impl KeyboardEffect {
+    /// Parses `{"effect": "CHROMA_...", "param": ...}`, returning the Chroma
+    /// result code on failure.
+    pub fn from_json(body: &Value) -> Result<Self, i64> {
+        let param = &body["param"];
+        match body["effect"].as_str() {
+            Some("CHROMA_NONE") => Ok(KeyboardEffect::None),
+            Some("CHROMA_STATIC") => {
+                let color = param["color"].as_u64().ok_or(RZRESULT_INVALID_PARAMETER)?;
+                Ok(KeyboardEffect::Static(bgr(color)))
+            }
+            Some("CHROMA_CUSTOM") => Ok(KeyboardEffect::Custom(grid(param, |_, _| None)?)),
+            Some("CHROMA_CUSTOM_KEY") => {
+                let keys = &param["key"];
+                let frame = grid(&param["color"], |row, col| {
+                    keys[row][col]
+                        .as_u64()
+                        .filter(|key| key & CUSTOM_KEY_FLAG != 0)
+                        .map(bgr)
+                })?;
+                Ok(KeyboardEffect::Custom(frame))
+            }
+            Some(_) => Err(RZRESULT_NOT_SUPPORTED),
+            None => Err(RZRESULT_INVALID_PARAMETER),
+        }
+    }
+
+    pub fn apply(&self, device: &mut Device) -> Result<(), crate::transport::TransportError> {
+        match self {
+            KeyboardEffect::None => device.set_effect(Effect::Off),
+            KeyboardEffect::Static(color) => device.set_effect(Effect::Static(*color)),
+            KeyboardEffect::Custom(frame) => device.set_frame(frame),
+        }
+    }
+}
+
+/// Chroma colors are `0x00BBGGRR`.
+pub fn bgr(color: u64) -> Rgb {
+    Rgb::new(color as u8, (color >> 8) as u8, (color >> 16) as u8)
+}
+
+/// Reads a 6x22 array of colors. `overlay` can replace single keys.
+fn grid(value: &Value, overlay: impl Fn(usize, usize) -> Option<Rgb>) -> Result<Frame, i64> {
+    let rows = value.as_array().ok_or(RZRESULT_INVALID_PARAMETER)?;
+    if rows.len() != MATRIX_ROWS {
+        return Err(RZRESULT_INVALID_PARAMETER);
+    }
+    let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+    for (row, cols) in rows.iter().enumerate() {
+        let cols = cols.as_array().ok_or(RZRESULT_INVALID_PARAMETER)?;
+        if cols.len() != MATRIX_COLS {
+            return Err(RZRESULT_INVALID_PARAMETER);
+        }
+        for (col, color) in cols.iter().enumerate() {
+            let color = color.as_u64().ok_or(RZRESULT_INVALID_PARAMETER)?;
+            frame.set(row, col, overlay(row, col).unwrap_or_else(|| bgr(color)));
+        }
+    }
+    Ok(frame)
+}
+
+struct Session {
+    last_seen: Instant,
+    tick: u64,
+    effects: HashMap<String, KeyboardEffect>,
+}
+
+struct Sessions {
+    next_id: u32,
+    next_effect: u64,
+    active: HashMap<u32, Session>,
+    /// What the keyboard showed before the first session started.
+    saved: Option<LightingState>,
+}
+
+pub struct ChromaServer {
+    listener: TcpListener,
+    device: Arc<Mutex<Device>>,
+    sessions: Arc<Mutex<Sessions>>,
+    timeout: Duration,
+}
+
+impl ChromaServer {
+    pub fn bind(addr: impl ToSocketAddrs, device: Arc<Mutex<Device>>) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            device,
+            sessions: Arc::new(Mutex::new(Sessions {
+                next_id: 1,
+                next_effect: 1,
+                active: HashMap::new(),
+                saved: None,
+            })),
+            timeout: SESSION_TIMEOUT,
+        })
+    }
+
+    /// Overrides how long a session may go without a request.
+    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts clients forever, one thread each, while a reaper thread ends
+    /// sessions whose heartbeat stopped.
+    pub fn run(self) -> io::Result<()> {
+        let addr = self.listener.local_addr()?;
+        let api = Api {
+            base: format!("http://{addr}"),
+            device: self.device,
+            sessions: self.sessions,
+            timeout: self.timeout,
+        };
+
+        let reaper = api.clone();
+        thread::spawn(move || loop {
+            thread::sleep(reaper.timeout.min(Duration::from_secs(1)) / 2);
+            reaper.expire_sessions();
+        });
+
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::warn!(
+                        icon = EMOJI_WARNING,
+                        "Chroma server could not accept a client: {err}"
+                    );
+                    continue;
+                }
+            };
+            let api = api.clone();
+            thread::spawn(move || http::serve_connection(stream, |request| api.handle(request)));
+        }
+        Ok(())
+    }
+
+    pub fn spawn(self) -> JoinHandle<io::Result<()>> {
+        thread::spawn(move || self.run())
+    }
+}
+
+#[derive(Clone)]
+struct Api {
+    base: String,
+    device: Arc<Mutex<Device>>,
+    sessions: Arc<Mutex<Sessions>>,
+    timeout: Duration,
+}
+
+fn result(code: i64) -> Response {
+    let status = match code {
+        RZRESULT_SUCCESS => 200,
+        RZRESULT_NOT_FOUND => 404,
+        _ => 400,
+    };
+    Response::json(status, &json!({ "result": code }))
+}
+
+fn parse_effect(request: &Request) -> Result<KeyboardEffect, i64> {
+    let body = request.json().ok_or(RZRESULT_INVALID_PARAMETER)?;
+    KeyboardEffect::from_json(&body)
+}
+
+impl Api {
+    fn handle(&self, request: &Request) -> Response {
+        self.expire_sessions();
+        let method = request.method.as_str();
+        match request.segments().as_slice() {
+            ["razer", "chromasdk"] if method == "GET" => Response::json(
+                200,
+                &json!({ "core": "3.1", "device": "3.1", "version": "3.1.0" }),
+            ),
+            ["razer", "chromasdk"] if method == "POST" => self.init(),
+            ["chromasdk", id, rest @ ..] => match id.parse() {
+                Ok(id) => self.session_request(id, method, rest, request),
+                Err(_) => result(RZRESULT_NOT_FOUND),
+            },
+            _ => result(RZRESULT_NOT_FOUND),
+        }
+    }
+
+    fn init(&self) -> Response {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.active.is_empty() {
+            sessions.saved = Some(self.device.lock().unwrap().state().clone());
+        }
+        let id = sessions.next_id;
+        sessions.next_id += 1;
+        sessions.active.insert(
+            id,
+            Session {
+                last_seen: Instant::now(),
+                tick: 0,
+                effects: HashMap::new(),
+            },
+        );
+        Response::json(
+            200,
+            &json!({ "sessionid": id, "uri": format!("{}/chromasdk/{id}", self.base) }),
+        )
+    }
+
+    fn session_request(&self, id: u32, method: &str, rest: &[&str], request: &Request) -> Response {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(session) = sessions.active.get_mut(&id) else {
+            return result(RZRESULT_NOT_FOUND);
+        };
+        session.last_seen = Instant::now();
+
+        match (method, rest) {
+            ("DELETE", []) => {
+                sessions.active.remove(&id);
+                self.restore_if_idle(&mut sessions);
+                result(RZRESULT_SUCCESS)
+            }
+            ("PUT", ["heartbeat"]) => {
+                session.tick += 1;
+                Response::json(200, &json!({ "tick": session.tick }))
+            }
+            ("PUT", ["keyboard"]) => match parse_effect(request) {
+                Ok(effect) => self.apply(&effect),
+                Err(code) => result(code),
+            },
+            ("POST", ["keyboard"]) => {
+                let effect = match parse_effect(request) {
+                    Ok(effect) => effect,
+                    Err(code) => return result(code),
+                };
+                let effect_id = format!("{:08x}-0000-4000-8000-{:012x}", id, sessions.next_effect);
+                sessions.next_effect += 1;
+                if let Some(session) = sessions.active.get_mut(&id) {
+                    session.effects.insert(effect_id.clone(), effect);
+                }
+                Response::json(200, &json!({ "id": effect_id, "result": RZRESULT_SUCCESS }))
+            }
+            ("PUT", ["effect"]) => {
+                let Some(body) = request.json() else {
+                    return result(RZRESULT_INVALID_PARAMETER);
+                };
+                match body["id"]
+                    .as_str()
+                    .and_then(|effect_id| session.effects.get(effect_id))
+                {
+                    Some(effect) => self.apply(effect),
+                    None => result(RZRESULT_NOT_FOUND),
+                }
+            }
+            ("DELETE", ["effect"]) => {
+                let Some(body) = request.json() else {
+                    return result(RZRESULT_INVALID_PARAMETER);
+                };
+                let ids: Vec<&str> = match body["ids"].as_array() {
+                    Some(ids) => ids.iter().filter_map(Value::as_str).collect(),
+                    None => body["id"].as_str().into_iter().collect(),
+                };
+                for effect_id in ids {
+                    session.effects.remove(effect_id);
+                }
+                result(RZRESULT_SUCCESS)
+            }
+            _ => result(RZRESULT_NOT_SUPPORTED),
+        }
+    }
+
+    fn apply(&self, effect: &KeyboardEffect) -> Response {
+        match effect.apply(&mut self.device.lock().unwrap()) {
+            Ok(()) => result(RZRESULT_SUCCESS),
+            Err(err) => Response::json(500, &json!({ "result": -1, "error": err.to_string() })),
+        }
+    }
+
+    fn expire_sessions(&self) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.active.len();
+        let timeout = self.timeout;
+        sessions
+            .active
+            .retain(|_, session| session.last_seen.elapsed() < timeout);
+        if sessions.active.len() != before {
+            self.restore_if_idle(&mut sessions);
+        }
+    }
+
+    fn restore_if_idle(&self, sessions: &mut Sessions) {
+        if !sessions.active.is_empty() {
+            return;
+        }
+        if let Some(saved) = sessions.saved.take() {
+            let _ = self.device.lock().unwrap().restore(&saved);
+        }
+    }
+}
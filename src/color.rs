@@ -0,0 +1,468 @@
+//! Colors and how to make the keyboard show them.
+//!
+//! `Rgb` parses `#RGB`/`#RRGGBB` hex and CSS color names, converts to and
+//! from HSV and HSL, and serializes as `[r, g, b]` so profiles and API bodies
+//! written before it existed still load. JSON may also give a color as a hex
+//! or name string.
+//!
+//! The LEDs don't render colors like a screen does. `ColorCorrection` holds a
+//! per-keyboard gamma and white balance that `Device` applies when it builds
+//! static, breathing and frame reports, leaving its cached state in the
+//! colors that were asked for. Corrections live in `<config dir>/color.json`,
+//! keyed by product id; without an entry nothing is changed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+pub const CORRECTION_FILE: &str = "color.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(into = "[u8; 3]", try_from = "ColorSpec")]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0, 0, 0);
+    pub const WHITE: Rgb = Rgb::new(255, 255, 255);
+    pub const RED: Rgb = Rgb::new(255, 0, 0);
+    pub const GREEN: Rgb = Rgb::new(0, 255, 0);
+    pub const BLUE: Rgb = Rgb::new(0, 0, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// `hue` in degrees (any value, wrapped), `saturation` and `value` in 0..=1.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let value = value.clamp(0.0, 1.0);
+        let chroma = value * saturation;
+        Self::from_hue(hue, chroma, value - chroma)
+    }
+
+    /// `(hue in degrees 0..360, saturation 0..=1, value 0..=1)`.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue_max_min();
+        let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+        (hue, saturation, max)
+    }
+
+    /// `hue` in degrees (any value, wrapped), `saturation` and `lightness` in 0..=1.
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let lightness = lightness.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        Self::from_hue(hue, chroma, lightness - chroma / 2.0)
+    }
+
+    /// `(hue in degrees 0..360, saturation 0..=1, lightness 0..=1)`.
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue_max_min();
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        (hue, saturation, lightness)
+    }
+
+    fn from_hue(hue: f32, chroma: f32, offset: f32) -> Self {
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let channel = |c: f32| ((c + offset) * 255.0).round().clamp(0.0, 255.0) as u8;
+        Self::new(channel(r), channel(g), channel(b))
+    }
+
+    fn hue_max_min(self) -> (f32, f32, f32) {
+        let [r, g, b] = <[u8; 3]>::from(self).map(|c| c as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        (hue, max, min)
+    }
+
+    /// Mixes towards `other`; `t` is clamped to 0..=1.
+    pub fn lerp(self, other: Rgb, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Self::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+
+    /// The CSS name of exactly this color, if it has one.
+    pub fn name(self) -> Option<&'static str> {
+        NAMED_COLORS
+            .iter()
+            .find(|(_, color)| *color == self)
+            .map(|(name, _)| *name)
+    }
+}
+
+impl From<[u8; 3]> for Rgb {
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Self::new(r, g, b)
+    }
+}
+
+impl From<Rgb> for [u8; 3] {
+    fn from(color: Rgb) -> Self {
+        [color.r, color.g, color.b]
+    }
+}
+
+/// `#RRGGBB`.
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError(String);
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "\"{}\" is not a color (expected #RGB, #RRGGBB or a CSS color name)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// `#RGB`, `#RRGGBB` (the `#` is optional) or a CSS color name, in any case.
+impl FromStr for Rgb {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim();
+        let error = || ParseColorError(s.to_string());
+        let lower = text.to_ascii_lowercase();
+        if let Some((_, color)) = NAMED_COLORS.iter().find(|(name, _)| *name == lower) {
+            return Ok(*color);
+        }
+        let hex = text.strip_prefix('#').unwrap_or(text);
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(error());
+        }
+        let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap();
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        match hex.len() {
+            3 => Ok(Self::new(digit(0) * 17, digit(1) * 17, digit(2) * 17)),
+            6 => Ok(Self::new(byte(0), byte(2), byte(4))),
+            _ => Err(error()),
+        }
+    }
+}
+
+/// What JSON may hold where a color goes.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorSpec {
+    Channels([u8; 3]),
+    Text(String),
+}
+
+impl TryFrom<ColorSpec> for Rgb {
+    type Error = ParseColorError;
+
+    fn try_from(spec: ColorSpec) -> Result<Self, Self::Error> {
+        match spec {
+            ColorSpec::Channels(channels) => Ok(channels.into()),
+            ColorSpec::Text(text) => text.parse(),
+        }
+    }
+}
+
+/// Colors spread evenly over 0..=1, written as a list: `["green", "red"]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Rgb>", into = "Vec<Rgb>")]
+pub struct Gradient(Vec<Rgb>);
+
+impl Gradient {
+    /// Needs at least one color.
+    pub fn new(stops: Vec<Rgb>) -> Option<Self> {
+        (!stops.is_empty()).then_some(Self(stops))
+    }
+
+    pub fn stops(&self) -> &[Rgb] {
+        &self.0
+    }
+
+    pub fn at(&self, t: f32) -> Rgb {
+        let last = self.0.len() - 1;
+        let position = t.clamp(0.0, 1.0) * last as f32;
+        let i = (position.floor() as usize).min(last);
+        match self.0.get(i + 1) {
+            Some(&next) => self.0[i].lerp(next, position - i as f32),
+            None => self.0[last],
+        }
+    }
+}
+
+/// Green through yellow to red, for loads and levels.
+impl Default for Gradient {
+    fn default() -> Self {
+        Self(vec![Rgb::GREEN, Rgb::new(255, 255, 0), Rgb::RED])
+    }
+}
+
+impl TryFrom<Vec<Rgb>> for Gradient {
+    type Error = String;
+
+    fn try_from(stops: Vec<Rgb>) -> Result<Self, Self::Error> {
+        Self::new(stops).ok_or_else(|| "a gradient needs at least one color".to_string())
+    }
+}
+
+impl From<Gradient> for Vec<Rgb> {
+    fn from(gradient: Gradient) -> Self {
+        gradient.0
+    }
+}
+
+/// Gamma and white balance for one keyboard. `apply` turns the color we want
+/// to see into the color to send.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorCorrection {
+    /// Exponent on each channel scaled to 0..=1. Above 1 darkens mid tones.
+    pub gamma: f32,
+    /// Per-channel gain applied after gamma, 0..=1.
+    pub white_balance: [f32; 3],
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl ColorCorrection {
+    pub const IDENTITY: ColorCorrection = ColorCorrection {
+        gamma: 1.0,
+        white_balance: [1.0, 1.0, 1.0],
+    };
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    pub fn apply(&self, color: Rgb) -> Rgb {
+        if self.is_identity() {
+            return color;
+        }
+        let channel = |value: u8, gain: f32| {
+            let linear = (value as f32 / 255.0).powf(self.gamma);
+            (linear * gain.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        Rgb::new(
+            channel(color.r, self.white_balance[0]),
+            channel(color.g, self.white_balance[1]),
+            channel(color.b, self.white_balance[2]),
+        )
+    }
+}
+
+/// Corrections keyed by product id as four hex digits (`"02A1"`). A missing
+/// file means no corrections.
+pub fn load_corrections(path: &Path) -> io::Result<HashMap<u16, ColorCorrection>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(err),
+    };
+    let entries: HashMap<String, ColorCorrection> =
+        serde_json::from_str(&text).map_err(io::Error::other)?;
+    entries
+        .into_iter()
+        .map(|(key, correction)| {
+            u16::from_str_radix(key.trim_start_matches("0x"), 16)
+                .map(|product_id| (product_id, correction))
+                .map_err(|_| io::Error::other(format!("\"{key}\" is not a product id")))
+        })
+        .collect()
+}
+
+/// The CSS Color Module Level 4 named colors.
+pub static NAMED_COLORS: &[(&str, Rgb)] = &[
+    ("aliceblue", Rgb::new(240, 248, 255)),
+    ("antiquewhite", Rgb::new(250, 235, 215)),
+    ("aqua", Rgb::new(0, 255, 255)),
+    ("aquamarine", Rgb::new(127, 255, 212)),
+    ("azure", Rgb::new(240, 255, 255)),
+    ("beige", Rgb::new(245, 245, 220)),
+    ("bisque", Rgb::new(255, 228, 196)),
+    ("black", Rgb::new(0, 0, 0)),
+    ("blanchedalmond", Rgb::new(255, 235, 205)),
+    ("blue", Rgb::new(0, 0, 255)),
+    ("blueviolet", Rgb::new(138, 43, 226)),
+    ("brown", Rgb::new(165, 42, 42)),
+    ("burlywood", Rgb::new(222, 184, 135)),
+    ("cadetblue", Rgb::new(95, 158, 160)),
+    ("chartreuse", Rgb::new(127, 255, 0)),
+    ("chocolate", Rgb::new(210, 105, 30)),
+    ("coral", Rgb::new(255, 127, 80)),
+    ("cornflowerblue", Rgb::new(100, 149, 237)),
+    ("cornsilk", Rgb::new(255, 248, 220)),
+    ("crimson", Rgb::new(220, 20, 60)),
+    ("cyan", Rgb::new(0, 255, 255)),
+    ("darkblue", Rgb::new(0, 0, 139)),
+    ("darkcyan", Rgb::new(0, 139, 139)),
+    ("darkgoldenrod", Rgb::new(184, 134, 11)),
+    ("darkgray", Rgb::new(169, 169, 169)),
+    ("darkgreen", Rgb::new(0, 100, 0)),
+    ("darkgrey", Rgb::new(169, 169, 169)),
+    ("darkkhaki", Rgb::new(189, 183, 107)),
+    ("darkmagenta", Rgb::new(139, 0, 139)),
+    ("darkolivegreen", Rgb::new(85, 107, 47)),
+    ("darkorange", Rgb::new(255, 140, 0)),
+    ("darkorchid", Rgb::new(153, 50, 204)),
+    ("darkred", Rgb::new(139, 0, 0)),
+    ("darksalmon", Rgb::new(233, 150, 122)),
+    ("darkseagreen", Rgb::new(143, 188, 143)),
+    ("darkslateblue", Rgb::new(72, 61, 139)),
+    ("darkslategray", Rgb::new(47, 79, 79)),
+    ("darkslategrey", Rgb::new(47, 79, 79)),
+    ("darkturquoise", Rgb::new(0, 206, 209)),
+    ("darkviolet", Rgb::new(148, 0, 211)),
+    ("deeppink", Rgb::new(255, 20, 147)),
+    ("deepskyblue", Rgb::new(0, 191, 255)),
+    ("dimgray", Rgb::new(105, 105, 105)),
+    ("dimgrey", Rgb::new(105, 105, 105)),
+    ("dodgerblue", Rgb::new(30, 144, 255)),
+    ("firebrick", Rgb::new(178, 34, 34)),
+    ("floralwhite", Rgb::new(255, 250, 240)),
+    ("forestgreen", Rgb::new(34, 139, 34)),
+    ("fuchsia", Rgb::new(255, 0, 255)),
+    ("gainsboro", Rgb::new(220, 220, 220)),
+    ("ghostwhite", Rgb::new(248, 248, 255)),
+    ("gold", Rgb::new(255, 215, 0)),
+    ("goldenrod", Rgb::new(218, 165, 32)),
+    ("gray", Rgb::new(128, 128, 128)),
+    ("green", Rgb::new(0, 128, 0)),
+    ("greenyellow", Rgb::new(173, 255, 47)),
+    ("grey", Rgb::new(128, 128, 128)),
+    ("honeydew", Rgb::new(240, 255, 240)),
+    ("hotpink", Rgb::new(255, 105, 180)),
+    ("indianred", Rgb::new(205, 92, 92)),
+    ("indigo", Rgb::new(75, 0, 130)),
+    ("ivory", Rgb::new(255, 255, 240)),
+    ("khaki", Rgb::new(240, 230, 140)),
+    ("lavender", Rgb::new(230, 230, 250)),
+    ("lavenderblush", Rgb::new(255, 240, 245)),
+    ("lawngreen", Rgb::new(124, 252, 0)),
+    ("lemonchiffon", Rgb::new(255, 250, 205)),
+    ("lightblue", Rgb::new(173, 216, 230)),
+    ("lightcoral", Rgb::new(240, 128, 128)),
+    ("lightcyan", Rgb::new(224, 255, 255)),
+    ("lightgoldenrodyellow", Rgb::new(250, 250, 210)),
+    ("lightgray", Rgb::new(211, 211, 211)),
+    ("lightgreen", Rgb::new(144, 238, 144)),
+    ("lightgrey", Rgb::new(211, 211, 211)),
+    ("lightpink", Rgb::new(255, 182, 193)),
+    ("lightsalmon", Rgb::new(255, 160, 122)),
+    ("lightseagreen", Rgb::new(32, 178, 170)),
+    ("lightskyblue", Rgb::new(135, 206, 250)),
+    ("lightslategray", Rgb::new(119, 136, 153)),
+    ("lightslategrey", Rgb::new(119, 136, 153)),
+    ("lightsteelblue", Rgb::new(176, 196, 222)),
+    ("lightyellow", Rgb::new(255, 255, 224)),
+    ("lime", Rgb::new(0, 255, 0)),
+    ("limegreen", Rgb::new(50, 205, 50)),
+    ("linen", Rgb::new(250, 240, 230)),
+    ("magenta", Rgb::new(255, 0, 255)),
+    ("maroon", Rgb::new(128, 0, 0)),
+    ("mediumaquamarine", Rgb::new(102, 205, 170)),
+    ("mediumblue", Rgb::new(0, 0, 205)),
+    ("mediumorchid", Rgb::new(186, 85, 211)),
+    ("mediumpurple", Rgb::new(147, 112, 219)),
+    ("mediumseagreen", Rgb::new(60, 179, 113)),
+    ("mediumslateblue", Rgb::new(123, 104, 238)),
+    ("mediumspringgreen", Rgb::new(0, 250, 154)),
+    ("mediumturquoise", Rgb::new(72, 209, 204)),
+    ("mediumvioletred", Rgb::new(199, 21, 133)),
+    ("midnightblue", Rgb::new(25, 25, 112)),
+    ("mintcream", Rgb::new(245, 255, 250)),
+    ("mistyrose", Rgb::new(255, 228, 225)),
+    ("moccasin", Rgb::new(255, 228, 181)),
+    ("navajowhite", Rgb::new(255, 222, 173)),
+    ("navy", Rgb::new(0, 0, 128)),
+    ("oldlace", Rgb::new(253, 245, 230)),
+    ("olive", Rgb::new(128, 128, 0)),
+    ("olivedrab", Rgb::new(107, 142, 35)),
+    ("orange", Rgb::new(255, 165, 0)),
+    ("orangered", Rgb::new(255, 69, 0)),
+    ("orchid", Rgb::new(218, 112, 214)),
+    ("palegoldenrod", Rgb::new(238, 232, 170)),
+    ("palegreen", Rgb::new(152, 251, 152)),
+    ("paleturquoise", Rgb::new(175, 238, 238)),
+    ("palevioletred", Rgb::new(219, 112, 147)),
+    ("papayawhip", Rgb::new(255, 239, 213)),
+    ("peachpuff", Rgb::new(255, 218, 185)),
+    ("peru", Rgb::new(205, 133, 63)),
+    ("pink", Rgb::new(255, 192, 203)),
+    ("plum", Rgb::new(221, 160, 221)),
+    ("powderblue", Rgb::new(176, 224, 230)),
+    ("purple", Rgb::new(128, 0, 128)),
+    ("rebeccapurple", Rgb::new(102, 51, 153)),
+    ("red", Rgb::new(255, 0, 0)),
+    ("rosybrown", Rgb::new(188, 143, 143)),
+    ("royalblue", Rgb::new(65, 105, 225)),
+    ("saddlebrown", Rgb::new(139, 69, 19)),
+    ("salmon", Rgb::new(250, 128, 114)),
+    ("sandybrown", Rgb::new(244, 164, 96)),
+    ("seagreen", Rgb::new(46, 139, 87)),
+    ("seashell", Rgb::new(255, 245, 238)),
+    ("sienna", Rgb::new(160, 82, 45)),
+    ("silver", Rgb::new(192, 192, 192)),
+    ("skyblue", Rgb::new(135, 206, 235)),
+    ("slateblue", Rgb::new(106, 90, 205)),
+    ("slategray", Rgb::new(112, 128, 144)),
+    ("slategrey", Rgb::new(112, 128, 144)),
+    ("snow", Rgb::new(255, 250, 250)),
+    ("springgreen", Rgb::new(0, 255, 127)),
+    ("steelblue", Rgb::new(70, 130, 180)),
+    ("tan", Rgb::new(210, 180, 140)),
+    ("teal", Rgb::new(0, 128, 128)),
+    ("thistle", Rgb::new(216, 191, 216)),
+    ("tomato", Rgb::new(255, 99, 71)),
+    ("turquoise", Rgb::new(64, 224, 208)),
+    ("violet", Rgb::new(238, 130, 238)),
+    ("wheat", Rgb::new(245, 222, 179)),
+    ("white", Rgb::new(255, 255, 255)),
+    ("whitesmoke", Rgb::new(245, 245, 245)),
+    ("yellow", Rgb::new(255, 255, 0)),
+    ("yellowgreen", Rgb::new(154, 205, 50)),
+];
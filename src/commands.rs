@@ -0,0 +1,257 @@
+//! Every command we know, declared once.
+//!
+//! Each entry of the `commands!` table gives the command's name, class, id,
+//! data size, the argument bytes that never change, the builder parameters
+//! and where they go, and the fields of the answer. From that the macro
+//! generates the `RazerReport` constructor of the same name and the
+//! `COMMANDS` table, which the decoder, the response readers and the golden
+//! tests in `tests/commands_test.rs` walk. Adding a command is one entry here
+//! plus one line in `tests/golden/commands.txt`.
+//!
+//! Entries marked `manual` have a hand-written constructor in
+//! `razer_report.rs` (e.g. `custom_frame_row`, which takes a slice of colors)
+//! and only contribute metadata.
+
+use crate::razer_report::*;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Byte,
+    /// NUL-padded text of at most this many bytes.
+    Text(usize),
+}
+
+use FieldKind::{Byte, Text};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Byte(u8),
+    Text(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Byte(byte) => write!(f, "0x{byte:02X}"),
+            Value::Text(text) => write!(f, "\"{text}\""),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CommandDef {
+    /// Name of the `RazerReport` constructor.
+    pub name: &'static str,
+    /// What the firmware calls it, for the decoder.
+    pub description: &'static str,
+    pub class: u8,
+    pub id: u8,
+    pub data_size: u8,
+    /// `(offset, value)` argument bytes set for every use of the command.
+    pub fixed: &'static [(usize, u8)],
+    /// `(name, offset)` of each builder parameter, in parameter order.
+    pub params: &'static [(&'static str, usize)],
+    /// `(name, offset, kind)` of each field the keyboard answers with.
+    pub returns: &'static [(&'static str, usize, FieldKind)],
+}
+
+impl CommandDef {
+    /// Builds the report from parameter values given in `params` order.
+    pub fn build(&self, values: &[u8]) -> RazerReport {
+        assert_eq!(
+            values.len(),
+            self.params.len(),
+            "{} takes {} parameters",
+            self.name,
+            self.params.len()
+        );
+        let mut report = RazerReport::new();
+        report.command_class = self.class;
+        report.command_id = self.id;
+        report.data_size = self.data_size;
+        for &(offset, value) in self.fixed {
+            report.arguments[offset] = value;
+        }
+        for (&(_, offset), &value) in self.params.iter().zip(values) {
+            report.arguments[offset] = value;
+        }
+        report.calculate_crc();
+        report
+    }
+
+    /// Whether `report` is this command: same class and id, same fixed bytes.
+    pub fn matches(&self, report: &RazerReport) -> bool {
+        report.command_class == self.class
+            && report.command_id == self.id
+            && self
+                .fixed
+                .iter()
+                .all(|&(offset, value)| report.arguments[offset] == value)
+    }
+
+    /// The builder parameters as they appear in `report`.
+    pub fn read_params(&self, report: &RazerReport) -> Vec<(&'static str, u8)> {
+        self.params
+            .iter()
+            .map(|&(name, offset)| (name, report.arguments[offset]))
+            .collect()
+    }
+
+    /// Every field of an answer to this command.
+    pub fn read_returns(&self, response: &RazerReport) -> Vec<(&'static str, Value)> {
+        self.returns
+            .iter()
+            .map(|&(name, offset, kind)| (name, read_field(response, offset, kind)))
+            .collect()
+    }
+
+    /// One field of an answer. Panics if the table doesn't define it.
+    pub fn field(&self, response: &RazerReport, name: &str) -> Value {
+        let &(_, offset, kind) = self
+            .returns
+            .iter()
+            .find(|(field, _, _)| *field == name)
+            .unwrap_or_else(|| panic!("{} has no field {name}", self.name));
+        read_field(response, offset, kind)
+    }
+
+    pub fn byte(&self, response: &RazerReport, name: &str) -> u8 {
+        match self.field(response, name) {
+            Value::Byte(byte) => byte,
+            Value::Text(_) => panic!("{}.{name} is text", self.name),
+        }
+    }
+
+    pub fn text(&self, response: &RazerReport, name: &str) -> String {
+        match self.field(response, name) {
+            Value::Text(text) => text,
+            Value::Byte(_) => panic!("{}.{name} is a byte", self.name),
+        }
+    }
+}
+
+fn read_field(response: &RazerReport, offset: usize, kind: FieldKind) -> Value {
+    match kind {
+        Byte => Value::Byte(response.arguments[offset]),
+        Text(len) => {
+            let bytes = &response.arguments[offset..offset + len];
+            let len = bytes.iter().position(|&b| b == 0).unwrap_or(len);
+            Value::Text(String::from_utf8_lossy(&bytes[..len]).into_owned())
+        }
+    }
+}
+
+/// The definition behind constructor `name`.
+pub fn find(name: &str) -> Option<&'static CommandDef> {
+    COMMANDS.iter().find(|def| def.name == name)
+}
+
+/// The definition `report` was built from. When several match (the effect
+/// commands share a class and id) the one pinning the most bytes wins.
+pub fn lookup(report: &RazerReport) -> Option<&'static CommandDef> {
+    COMMANDS
+        .iter()
+        .filter(|def| def.matches(report))
+        .max_by_key(|def| def.fixed.len())
+}
+
+macro_rules! builder {
+    (
+        fn $(#[doc = $doc:literal])* $name:ident($($param:ident @ $poffset:literal),*),
+        $class:literal, $id:literal, $size:literal, [$($offset:literal => $value:expr),*]
+    ) => {
+        $(#[doc = $doc])*
+        pub fn $name($($param: u8),*) -> Self {
+            let mut report = Self::new();
+            report.command_class = $class;
+            report.command_id = $id;
+            report.data_size = $size;
+            $(report.arguments[$offset] = $value;)*
+            $(report.arguments[$poffset] = $param;)*
+            report.calculate_crc();
+            report
+        }
+    };
+    (manual $($rest:tt)*) => {};
+}
+
+macro_rules! commands {
+    ($(
+        $(#[doc = $doc:literal])*
+        $kind:ident $name:ident($($param:ident @ $poffset:literal),*)
+            = $description:literal, $class:literal / $id:literal, size $size:literal
+            $(, fixed [$($offset:literal => $value:expr),* $(,)?])?
+            $(, returns [$($field:ident @ $foffset:literal : $fkind:expr),* $(,)?])?;
+    )*) => {
+        pub static COMMANDS: &[CommandDef] = &[$(
+            CommandDef {
+                name: stringify!($name),
+                description: $description,
+                class: $class,
+                id: $id,
+                data_size: $size,
+                fixed: &[$($(($offset, $value)),*)?],
+                params: &[$((stringify!($param), $poffset)),*],
+                returns: &[$($((stringify!($field), $foffset, $fkind)),*)?],
+            },
+        )*];
+
+        impl RazerReport {
+            $(builder!(
+                $kind $(#[doc = $doc])* $name($($param @ $poffset),*),
+                $class, $id, $size, [$($($offset => $value),*)?]
+            );)*
+        }
+    };
+}
+
+commands! {
+    /// Turns the backlight off.
+    fn none() = "set extended effect", 0x0F / 0x02, size 0x06,
+        fixed [0 => VARSTORE, 1 => BACKLIGHT_LED, 2 => EXT_EFFECT_NONE];
+
+    fn static_rgb(red @ 6, green @ 7, blue @ 8) = "set extended effect", 0x0F / 0x02, size 0x09,
+        fixed [0 => VARSTORE, 1 => BACKLIGHT_LED, 2 => EXT_EFFECT_STATIC, 5 => 0x01];
+
+    /// Single-color breathing.
+    fn breathing(red @ 6, green @ 7, blue @ 8) = "set extended effect", 0x0F / 0x02, size 0x09,
+        fixed [
+            0 => VARSTORE,
+            1 => BACKLIGHT_LED,
+            2 => EXT_EFFECT_BREATHING,
+            3 => 0x01,
+            5 => 0x01,
+        ];
+
+    fn spectrum() = "set extended effect", 0x0F / 0x02, size 0x06,
+        fixed [0 => VARSTORE, 1 => BACKLIGHT_LED, 2 => EXT_EFFECT_SPECTRUM];
+
+    fn wave(direction @ 3, speed @ 4) = "set extended effect", 0x0F / 0x02, size 0x06,
+        fixed [0 => VARSTORE, 1 => BACKLIGHT_LED, 2 => EXT_EFFECT_WAVE];
+
+    /// Shows the frame uploaded with `custom_frame_row`.
+    fn custom_frame() = "set extended effect", 0x0F / 0x02, size 0x0C,
+        fixed [0 => NOSTORE, 1 => BACKLIGHT_LED, 2 => EXT_EFFECT_CUSTOM_FRAME];
+
+    manual custom_frame_row(row @ 2, start_col @ 3, stop_col @ 4)
+        = "set custom frame row", 0x0F / 0x03, size 0x47;
+
+    fn brightness(brightness @ 2) = "set brightness", 0x0F / 0x04, size 0x03,
+        fixed [0 => VARSTORE, 1 => BACKLIGHT_LED];
+
+    fn get_brightness() = "get brightness", 0x0F / 0x84, size 0x03,
+        fixed [0 => VARSTORE, 1 => BACKLIGHT_LED],
+        returns [brightness @ 2: Byte];
+
+    fn get_firmware() = "get firmware version", 0x00 / 0x81, size 0x02,
+        returns [major @ 0: Byte, minor @ 1: Byte];
+
+    fn get_serial() = "get serial", 0x00 / 0x82, size 0x16,
+        returns [serial @ 0: Text(22)];
+
+    fn set_device_mode(mode @ 0, param @ 1) = "set device mode", 0x00 / 0x04, size 0x02;
+
+    fn get_device_mode() = "get device mode", 0x00 / 0x84, size 0x02,
+        returns [mode @ 0: Byte, param @ 1: Byte];
+}
@@ -0,0 +1,120 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Where profiles, the API token and other settings live.
+///
+/// `RAZER_RGB_CONFIG_DIR` overrides the platform default, which is
+/// `~/Library/Application Support/razer-rgb` on macOS and
+/// `$XDG_CONFIG_HOME/razer-rgb` (or `~/.config/razer-rgb`) elsewhere.
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("RAZER_RGB_CONFIG_DIR") {
+        return PathBuf::from(dir);
+    }
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    if cfg!(target_os = "macos") {
+        return home.join("Library/Application Support/razer-rgb");
+    }
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir).join("razer-rgb"),
+        None => home.join(".config/razer-rgb"),
+    }
+}
+
+/// Reads the token stored at `path`, creating a fresh random one (readable
+/// only by the current user) if the file doesn't exist yet.
+pub fn load_or_create_token(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Ok(token) if !token.trim().is_empty() => return Ok(token.trim().to_string()),
+        // An empty file gets replaced, so it must go before creating anew.
+        Ok(_) => fs::remove_file(path)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(io::Error::other)?;
+    let token: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    // Never readable by anyone else, not even for a moment.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+/// A directory of files named `<name>.<extension>`, one per stored item.
+/// Names can't be empty, start with a dot or contain path separators, so
+/// they never point outside the directory.
+#[derive(Debug, Clone)]
+pub struct NamedFileStore {
+    dir: PathBuf,
+    extension: &'static str,
+    /// What the files hold, for error messages.
+    kind: &'static str,
+}
+
+impl NamedFileStore {
+    pub fn new(dir: impl Into<PathBuf>, extension: &'static str, kind: &'static str) -> Self {
+        Self {
+            dir: dir.into(),
+            extension,
+            kind,
+        }
+    }
+
+    pub fn path(&self, name: &str) -> io::Result<PathBuf> {
+        let valid = !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\']);
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid {} name \"{name}\"", self.kind),
+            ));
+        }
+        Ok(self.dir.join(format!("{name}.{}", self.extension)))
+    }
+
+    /// Names of all stored files, sorted.
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == self.extension) {
+                if let Some(stem) = path.file_stem() {
+                    names.push(stem.to_string_lossy().into_owned());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn read(&self, name: &str) -> io::Result<String> {
+        fs::read_to_string(self.path(name)?)
+    }
+
+    /// Writes `contents` under `name`, creating the directory if needed.
+    pub fn write(&self, name: &str, contents: &str) -> io::Result<()> {
+        let path = self.path(name)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, contents)
+    }
+
+    pub fn delete(&self, name: &str) -> io::Result<()> {
+        fs::remove_file(self.path(name)?)
+    }
+}
@@ -0,0 +1,141 @@
+//! The input side of the GUI's raw command console: turning the typed fields
+//! into a report, a history of what was sent, and hex dumps of the answers.
+
+use crate::razer_report::RazerReport;
+use std::collections::VecDeque;
+
+/// How many commands the history keeps.
+pub const HISTORY_LEN: usize = 50;
+
+/// A command as typed into the console.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawCommand {
+    pub command_class: u8,
+    pub command_id: u8,
+    pub data_size: u8,
+    pub transaction_id: u8,
+    pub arguments: Vec<u8>,
+}
+
+/// Parses one byte written in hex, with or without the `0x` prefix, since
+/// that's how every protocol note writes them.
+fn parse_byte(text: &str, field: &str) -> Result<u8, String> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    u8::from_str_radix(digits, 16).map_err(|_| format!("{field}: '{text}' is not a hex byte"))
+}
+
+/// Parses bytes separated by spaces or commas, e.g. `01 05 02` or `0x01,0x05`.
+pub fn parse_bytes(text: &str) -> Result<Vec<u8>, String> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .enumerate()
+        .map(|(i, part)| parse_byte(part, &format!("argument {i}")))
+        .collect()
+}
+
+impl RawCommand {
+    /// Builds a command from the console fields. An empty data size means the
+    /// number of argument bytes, and an empty transaction id `default_id`, the
+    /// one the connected keyboard expects.
+    pub fn parse(
+        class: &str,
+        id: &str,
+        data_size: &str,
+        transaction_id: &str,
+        arguments: &str,
+        default_id: u8,
+    ) -> Result<Self, String> {
+        let arguments = parse_bytes(arguments)?;
+        if arguments.len() > 80 {
+            return Err(format!(
+                "{} argument bytes, at most 80 fit",
+                arguments.len()
+            ));
+        }
+        let data_size = if data_size.trim().is_empty() {
+            arguments.len() as u8
+        } else {
+            parse_byte(data_size, "data size")?
+        };
+        let transaction_id = if transaction_id.trim().is_empty() {
+            default_id
+        } else {
+            parse_byte(transaction_id, "transaction id")?
+        };
+        Ok(Self {
+            command_class: parse_byte(class, "class")?,
+            command_id: parse_byte(id, "id")?,
+            data_size,
+            transaction_id,
+            arguments,
+        })
+    }
+
+    /// The report to send, with the CRC filled in.
+    pub fn report(&self) -> RazerReport {
+        let mut report = RazerReport::new();
+        report.transaction_id = self.transaction_id;
+        report.command_class = self.command_class;
+        report.command_id = self.command_id;
+        report.data_size = self.data_size;
+        report.arguments[..self.arguments.len()].copy_from_slice(&self.arguments);
+        report.calculate_crc();
+        report
+    }
+
+    /// One-line summary for the history list.
+    pub fn label(&self) -> String {
+        let args: Vec<String> = self.arguments.iter().map(|b| format!("{b:02X}")).collect();
+        format!(
+            "{:02X}/{:02X} size {:02X} [{}]",
+            self.command_class,
+            self.command_id,
+            self.data_size,
+            args.join(" ")
+        )
+    }
+}
+
+/// Sent commands, newest first. Sending a command again moves it to the top
+/// instead of adding a duplicate.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    commands: VecDeque<RawCommand>,
+}
+
+impl History {
+    pub fn push(&mut self, command: RawCommand) {
+        self.commands.retain(|sent| *sent != command);
+        self.commands.push_front(command);
+        self.commands.truncate(HISTORY_LEN);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RawCommand> {
+        self.commands.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+/// Classic 16-bytes-per-line dump with offsets.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02X}")).collect();
+            format!("{:04X}  {}", line * 16, hex.join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
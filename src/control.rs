@@ -0,0 +1,292 @@
+//! Local HTTP/WebSocket API for dashboards and scripts.
+//!
+//! Every request must carry the token from the token file, either as
+//! `Authorization: Bearer <token>` or as `?token=<token>` (browsers can't set
+//! headers on WebSockets).
+//!
+//! - `GET /state`: current lighting state
+//! - `POST /effect`: `{"effect": "static", "color": [255, 0, 0]}`
+//! - `POST /brightness`: `{"brightness": 128}`
+//! - `POST /frame`: `{"frame": [[[r, g, b], ...], ...]}`, 6 rows of 22 colors
+//! - `GET /profiles`, `POST /profile`: `{"profile": "evening"}`
+//! - `POST /notify`: `{"notify": "flash", "color": "red", "repeat": 3,
+//!   "duration_ms": 500}`, answered once the previous state is back
+//! - `GET /ws`: WebSocket. Binary messages are raw RGB frames (rows x cols x 3
+//!   bytes), text messages are any of the POST bodies above. Every state
+//!   change is sent back as a JSON text message.
+
+use crate::color::Rgb;
+use crate::device::Device;
+use crate::effect::Effect;
+use crate::emojis::EMOJI_WARNING;
+use crate::frame::Frame;
+use crate::http::{Request, Response};
+use crate::notify::{self, Notification};
+use crate::profile::ProfileStore;
+use serde_json::{json, Value};
+use std::io::{self, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+pub const DEFAULT_PORT: u16 = 8462;
+pub const TOKEN_FILE: &str = "api-token";
+
+/// How often a WebSocket connection checks for state changes to push out.
+const WEBSOCKET_POLL: Duration = Duration::from_millis(20);
+
+pub struct ControlServer {
+    listener: TcpListener,
+    api: Api,
+}
+
+impl ControlServer {
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        device: Arc<Mutex<Device>>,
+        token: String,
+        profiles: ProfileStore,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            api: Api {
+                device,
+                token,
+                profiles,
+            },
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn run(self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::warn!(
+                        icon = EMOJI_WARNING,
+                        "Control server could not accept a client: {err}"
+                    );
+                    continue;
+                }
+            };
+            let api = self.api.clone();
+            thread::spawn(move || api.serve(stream));
+        }
+        Ok(())
+    }
+
+    pub fn spawn(self) -> JoinHandle<io::Result<()>> {
+        thread::spawn(move || self.run())
+    }
+}
+
+#[derive(Clone)]
+struct Api {
+    device: Arc<Mutex<Device>>,
+    token: String,
+    profiles: ProfileStore,
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, value: Value) -> io::Result<()> {
+    socket
+        .send(Message::text(value.to_string()))
+        .map_err(io::Error::other)
+}
+
+fn error(status: u16, message: impl ToString) -> Response {
+    Response::json(status, &json!({ "error": message.to_string() }))
+}
+
+impl Api {
+    fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        while let Some(request) = Request::read(&mut reader)? {
+            if request.method == "OPTIONS" {
+                Response::empty(204).write_to(&mut writer)?;
+            } else if !self.authorized(&request) {
+                error(401, "missing or wrong token").write_to(&mut writer)?;
+            } else if request.segments() == ["ws"] {
+                // A client may send its first message right behind the
+                // handshake, in which case the reader already holds it.
+                return self.upgrade(&request, writer, reader.buffer().to_vec());
+            } else {
+                self.handle(&request).write_to(&mut writer)?;
+            }
+            if !request.keep_alive() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let bearer = request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "));
+        bearer
+            .or_else(|| request.query("token"))
+            .is_some_and(|token| same_token(token, &self.token))
+    }
+
+    fn handle(&self, request: &Request) -> Response {
+        match (request.method.as_str(), request.segments().as_slice()) {
+            ("GET", ["state"]) => self.state(),
+            ("GET", ["profiles"]) => match self.profiles.list() {
+                Ok(names) => Response::json(200, &json!({ "profiles": names })),
+                Err(err) => error(500, err),
+            },
+            ("POST", ["effect" | "brightness" | "frame" | "profile" | "notify"]) => {
+                match request.json() {
+                    Some(body) => match self.command(&body) {
+                        Ok(()) => self.state(),
+                        Err(response) => response,
+                    },
+                    None => error(400, "body must be JSON"),
+                }
+            }
+            _ => error(404, "no such endpoint"),
+        }
+    }
+
+    fn state(&self) -> Response {
+        let state = self.device.lock().unwrap().state().clone();
+        Response::json(200, &json!(state))
+    }
+
+    /// Applies one command body. The key that is present decides what it is.
+    fn command(&self, body: &Value) -> Result<(), Response> {
+        // Profiles with a transition lock per step, so the lock is taken
+        // only where needed.
+        let device = || self.device.lock().unwrap();
+        let result = if body.get("effect").is_some() {
+            let effect: Effect =
+                serde_json::from_value(body.clone()).map_err(|err| error(400, err))?;
+            device().set_effect(effect)
+        } else if let Some(brightness) = body.get("brightness") {
+            let brightness = brightness
+                .as_u64()
+                .filter(|&value| value <= 0xFF)
+                .ok_or_else(|| error(400, "brightness must be 0-255"))?;
+            device().set_brightness(brightness as u8)
+        } else if let Some(frame) = body.get("frame") {
+            let frame: Frame =
+                serde_json::from_value(frame.clone()).map_err(|err| error(400, err))?;
+            device().set_frame(&frame)
+        } else if let Some(name) = body.get("profile").and_then(Value::as_str) {
+            let profile = self.profiles.load(name).map_err(|err| error(404, err))?;
+            profile.play(&self.device)
+        } else if body.get("notify").is_some() {
+            let notification: Notification =
+                serde_json::from_value(body.clone()).map_err(|err| error(400, err))?;
+            notification.validate().map_err(|err| error(400, err))?;
+            notify::notify(&mut device(), &notification)
+        } else {
+            return Err(error(
+                400,
+                "expected effect, brightness, frame, profile or notify",
+            ));
+        };
+        result.map_err(|err| error(500, err))
+    }
+
+    fn upgrade(&self, request: &Request, mut stream: TcpStream, read: Vec<u8>) -> io::Result<()> {
+        let has_token = |name: &str, token: &str| {
+            request.header(name).is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|part| part.trim().eq_ignore_ascii_case(token))
+            })
+        };
+        let key = request.header("sec-websocket-key").filter(|_| {
+            request.method == "GET"
+                && has_token("upgrade", "websocket")
+                && has_token("connection", "upgrade")
+        });
+        let Some(key) = key else {
+            return error(400, "expected a WebSocket upgrade").write_to(&mut stream);
+        };
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\r\n",
+            tungstenite::handshake::derive_accept_key(key.as_bytes())
+        )?;
+        stream.set_read_timeout(Some(WEBSOCKET_POLL))?;
+
+        let (updates, state) = {
+            let mut device = self.device.lock().unwrap();
+            (device.subscribe(), device.state().clone())
+        };
+        let mut socket = WebSocket::from_partially_read(stream, read, Role::Server, None);
+        send(&mut socket, json!(state))?;
+
+        loop {
+            let reply = match socket.read() {
+                Ok(Message::Binary(bytes)) => self.raw_frame(&bytes).err(),
+                Ok(Message::Text(text)) => match serde_json::from_str(text.as_str()) {
+                    Ok(body) => self.command(&body).err(),
+                    Err(err) => Some(error(400, err)),
+                },
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => None,
+                Err(tungstenite::Error::Io(err))
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    None
+                }
+                Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Ok(())
+                }
+                Err(err) => return Err(io::Error::other(err)),
+            };
+            if let Some(response) = reply {
+                let body = serde_json::from_slice(&response.body).unwrap_or(Value::Null);
+                send(&mut socket, body)?;
+            }
+            // Only the latest state matters to a dashboard.
+            if let Some(state) = updates.try_iter().last() {
+                send(&mut socket, json!(state))?;
+            }
+        }
+    }
+
+    /// Applies a binary WebSocket message holding packed RGB rows.
+    fn raw_frame(&self, bytes: &[u8]) -> Result<(), Response> {
+        let mut device = self.device.lock().unwrap();
+        let mut frame = device.frame().clone();
+        if bytes.len() != frame.pixels().len() * 3 {
+            return Err(error(
+                400,
+                format!("frame must be {} bytes", frame.pixels().len() * 3),
+            ));
+        }
+        for (pixel, rgb) in frame.pixels_mut().iter_mut().zip(bytes.chunks_exact(3)) {
+            *pixel = Rgb::new(rgb[0], rgb[1], rgb[2]);
+        }
+        device.set_frame(&frame).map_err(|err| error(500, err))
+    }
+}
+
+/// Compares in time that depends only on the lengths, so guesses can't be
+/// refined from how quickly they are turned down.
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
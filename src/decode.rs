@@ -0,0 +1,188 @@
+//! Human-readable descriptions of reports, for hex dumps and captures.
+//!
+//! `decode` names the status and command and spells out the arguments the way
+//! the firmware reads them; `RazerReport`'s `Display` impl prints the result
+//! on one line.
+
+use crate::commands::{lookup, CommandDef, COMMANDS};
+use crate::razer_report::*;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub status: &'static str,
+    pub transaction_id: u8,
+    pub command_class: u8,
+    pub command_id: u8,
+    pub data_size: u8,
+    /// Command name, or `None` for a class/id pair we don't know.
+    pub command: Option<&'static str>,
+    /// The `commands` table entry the report matches.
+    pub definition: Option<&'static CommandDef>,
+    /// The arguments in words, when the command is known.
+    pub details: Option<String>,
+    pub crc_valid: bool,
+}
+
+pub fn status_name(status: u8) -> &'static str {
+    match status {
+        RAZER_CMD_NEW => "new",
+        RAZER_CMD_BUSY => "busy",
+        RAZER_CMD_SUCCESSFUL => "successful",
+        RAZER_CMD_FAILURE => "failure",
+        RAZER_CMD_TIMEOUT => "timeout",
+        RAZER_CMD_NOT_SUPPORTED => "not supported",
+        _ => "unknown status",
+    }
+}
+
+pub fn command_name(class: u8, id: u8) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .find(|def| def.class == class && def.id == id)
+        .map(|def| def.description)
+}
+
+fn led_name(led: u8) -> String {
+    match led {
+        0x00 => "zero LED".to_string(),
+        0x01 => "scroll wheel".to_string(),
+        0x04 => "logo".to_string(),
+        BACKLIGHT_LED => "backlight".to_string(),
+        _ => format!("LED 0x{led:02X}"),
+    }
+}
+
+fn storage_name(storage: u8) -> String {
+    match storage {
+        NOSTORE => "nostore".to_string(),
+        VARSTORE => "varstore".to_string(),
+        _ => format!("storage 0x{storage:02X}"),
+    }
+}
+
+fn color(bytes: &[u8]) -> String {
+    format!("#{:02X}{:02X}{:02X}", bytes[0], bytes[1], bytes[2])
+}
+
+fn effect_details(args: &[u8; 80]) -> String {
+    let effect = match args[2] {
+        EXT_EFFECT_NONE => "none".to_string(),
+        EXT_EFFECT_STATIC => format!("static {}", color(&args[6..9])),
+        EXT_EFFECT_BREATHING => match args[3] {
+            0x01 => format!("breathing single {}", color(&args[6..9])),
+            0x02 => format!(
+                "breathing dual {} {}",
+                color(&args[6..9]),
+                color(&args[9..12])
+            ),
+            0x03 => "breathing random".to_string(),
+            kind => format!("breathing type 0x{kind:02X}"),
+        },
+        EXT_EFFECT_SPECTRUM => "spectrum".to_string(),
+        EXT_EFFECT_WAVE => format!("wave direction {} speed 0x{:02X}", args[3], args[4]),
+        EXT_EFFECT_CUSTOM_FRAME => "custom frame".to_string(),
+        other => format!("effect 0x{other:02X}"),
+    };
+    format!(
+        "{effect} on {}, {}",
+        led_name(args[1]),
+        storage_name(args[0])
+    )
+}
+
+fn frame_row_details(args: &[u8; 80]) -> String {
+    let (row, start, stop) = (args[2], args[3], args[4]);
+    if stop < start {
+        return format!("row {row}, columns {start}-{stop} (empty)");
+    }
+    let count = (stop - start) as usize + 1;
+    let colors: Vec<String> = args[5..].chunks_exact(3).take(count).map(color).collect();
+    format!("row {row}, columns {start}-{stop}: {}", colors.join(" "))
+}
+
+/// What the arguments say. Data the keyboard fills in is only shown once it
+/// has answered successfully.
+fn details(report: &RazerReport) -> Option<String> {
+    let args = &report.arguments;
+    let answered = report.status == RAZER_CMD_SUCCESSFUL;
+    Some(match (report.command_class, report.command_id) {
+        (0x00, 0x81) if answered => format!("v{}.{}", args[0], args[1]),
+        (0x00, 0x82) if answered => lookup(report)?.field(report, "serial").to_string(),
+        (0x0F, 0x02) => effect_details(args),
+        (0x0F, 0x03) => frame_row_details(args),
+        (0x0F, 0x04) => format!(
+            "{} ({}%) on {}, {}",
+            args[2],
+            args[2] as u32 * 100 / 255,
+            led_name(args[1]),
+            storage_name(args[0])
+        ),
+        (0x0F, 0x84) if answered => format!("{} on {}", args[2], led_name(args[1])),
+        (0x0F, 0x84) => format!("{}, {}", led_name(args[1]), storage_name(args[0])),
+        _ => return generic_details(report),
+    })
+}
+
+/// Lists parameters, or for an answer the returned fields, by their names in
+/// the command table.
+fn generic_details(report: &RazerReport) -> Option<String> {
+    let def = lookup(report)?;
+    let fields: Vec<String> = if report.status == RAZER_CMD_SUCCESSFUL && !def.returns.is_empty() {
+        def.read_returns(report)
+            .iter()
+            .map(|(name, value)| format!("{name} {value}"))
+            .collect()
+    } else {
+        def.read_params(report)
+            .iter()
+            .map(|(name, value)| format!("{name} 0x{value:02X}"))
+            .collect()
+    };
+    (!fields.is_empty()).then(|| fields.join(", "))
+}
+
+pub fn decode(report: &RazerReport) -> Decoded {
+    let command = command_name(report.command_class, report.command_id);
+    Decoded {
+        status: status_name(report.status),
+        transaction_id: report.transaction_id,
+        command_class: report.command_class,
+        command_id: report.command_id,
+        data_size: report.data_size,
+        command,
+        definition: lookup(report),
+        details: command.and_then(|_| details(report)),
+        crc_valid: report.crc_valid(),
+    }
+}
+
+impl fmt::Display for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.command {
+            Some(name) => write!(f, "{name}")?,
+            None => write!(
+                f,
+                "unknown command 0x{:02X}/0x{:02X}",
+                self.command_class, self.command_id
+            )?,
+        }
+        if let Some(details) = &self.details {
+            write!(f, ": {details}")?;
+        }
+        write!(
+            f,
+            " (status {}, transaction 0x{:02X}, data size {}, CRC {})",
+            self.status,
+            self.transaction_id,
+            self.data_size,
+            if self.crc_valid { "ok" } else { "BAD" }
+        )
+    }
+}
+
+impl fmt::Display for RazerReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        decode(self).fmt(f)
+    }
+}
@@ -0,0 +1,67 @@
+//! What differs between keyboards that speak the same protocol.
+//!
+//! The values follow OpenRazer's kernel driver. Only the Ornata V3 has been
+//! tried on real hardware; the others are there so they work as soon as
+//! someone plugs one in.
+
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceDescriptor {
+    pub name: &'static str,
+    pub product_id: u16,
+    /// Byte 1 of every report. Devices only act on reports carrying the id
+    /// they expect.
+    pub transaction_id: u8,
+    /// `wIndex` of the feature report control transfers, which is the number
+    /// of the interface they go to.
+    pub report_index: u16,
+    /// Wait between writing a report and reading the answer.
+    pub command_delay: Duration,
+}
+
+pub const ORNATA_V3: DeviceDescriptor = DeviceDescriptor {
+    name: "Razer Ornata V3",
+    product_id: 0x02A1,
+    transaction_id: 0x1F,
+    report_index: 0x02,
+    command_delay: Duration::from_micros(600),
+};
+
+pub static DEVICES: &[DeviceDescriptor] = &[
+    ORNATA_V3,
+    DeviceDescriptor {
+        name: "Razer Ornata V3 X",
+        product_id: 0x0294,
+        ..ORNATA_V3
+    },
+    DeviceDescriptor {
+        name: "Razer Ornata V2",
+        product_id: 0x025D,
+        transaction_id: 0x3F,
+        ..ORNATA_V3
+    },
+    DeviceDescriptor {
+        name: "Razer Ornata Chroma",
+        product_id: 0x021E,
+        transaction_id: 0x3F,
+        ..ORNATA_V3
+    },
+    DeviceDescriptor {
+        name: "Razer BlackWidow V3",
+        product_id: 0x024E,
+        ..ORNATA_V3
+    },
+    DeviceDescriptor {
+        name: "Razer BlackWidow Chroma V2",
+        product_id: 0x0221,
+        transaction_id: 0x3F,
+        ..ORNATA_V3
+    },
+];
+
+pub fn by_product_id(product_id: u16) -> Option<&'static DeviceDescriptor> {
+    DEVICES
+        .iter()
+        .find(|descriptor| descriptor.product_id == product_id)
+}
@@ -0,0 +1,351 @@
+use crate::capture;
+use crate::color::{self, ColorCorrection};
+use crate::commands;
+use crate::config::config_dir;
+use crate::decode::status_name;
+use crate::descriptor::{self, DeviceDescriptor};
+use crate::effect::Effect;
+use crate::emojis::*;
+use crate::emulator::{self, Emulator};
+use crate::frame::Frame;
+use crate::razer_report::RazerReport;
+use crate::transport::{Transport, TransportError, UsbTransport};
+use rusb::{Context, DeviceHandle, UsbContext};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+pub const RAZER_VENDOR_ID: u16 = 0x1532;
+pub const ORNATA_V3_PRODUCT_ID: u16 = descriptor::ORNATA_V3.product_id;
+
+/// Set to `0` to leave kernel drivers alone, or `1` to detach them on
+/// platforms other than Linux.
+pub const DETACH_ENV: &str = "RAZER_RGB_DETACH";
+
+/// Why no keyboard could be opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenError {
+    /// libusb itself failed to start.
+    Context(rusb::Error),
+    /// No supported keyboard is plugged in.
+    NotFound,
+    /// The keyboard is there but we may not open it.
+    Permission(&'static DeviceDescriptor),
+    /// Another driver or program holds the interface.
+    Busy(&'static DeviceDescriptor),
+    Usb(&'static DeviceDescriptor, rusb::Error),
+}
+
+impl OpenError {
+    /// Sorts a libusb error from opening or claiming `descriptor`'s device.
+    pub fn classify(descriptor: &'static DeviceDescriptor, err: rusb::Error) -> Self {
+        match err {
+            rusb::Error::Access => OpenError::Permission(descriptor),
+            rusb::Error::Busy => OpenError::Busy(descriptor),
+            rusb::Error::NoDevice | rusb::Error::NotFound => OpenError::NotFound,
+            err => OpenError::Usb(descriptor, err),
+        }
+    }
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenError::Context(err) => write!(f, "cannot start libusb: {err}"),
+            OpenError::NotFound => write!(f, "no supported Razer keyboard found"),
+            OpenError::Permission(descriptor) if cfg!(target_os = "linux") => write!(
+                f,
+                "permission denied opening the {}; install the udev rule with \
+                 `sudo razer-rgb udev-rule --install` and replug the keyboard",
+                descriptor.name
+            ),
+            OpenError::Permission(descriptor) => {
+                write!(f, "permission denied opening the {}", descriptor.name)
+            }
+            OpenError::Busy(descriptor) => write!(
+                f,
+                "the {} is busy: another program or driver holds it \
+                 (close Razer Synapse or OpenRazer, or set {DETACH_ENV}=1)",
+                descriptor.name
+            ),
+            OpenError::Usb(descriptor, err) => {
+                write!(f, "cannot open the {}: {err}", descriptor.name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OpenError {}
+
+/// Whether to detach kernel drivers from the report interface. On by default
+/// on Linux, where `usbhid` otherwise keeps us out.
+pub fn detach_requested() -> bool {
+    match std::env::var(DETACH_ENV) {
+        Ok(value) => value != "0",
+        Err(_) => cfg!(target_os = "linux"),
+    }
+}
+
+/// Opens the first supported keyboard, together with its descriptor.
+pub fn find_device() -> Result<(DeviceHandle<Context>, &'static DeviceDescriptor), OpenError> {
+    let context = Context::new().map_err(OpenError::Context)?;
+    let devices = context.devices().map_err(OpenError::Context)?;
+    for device in devices.iter() {
+        let Ok(device_desc) = device.device_descriptor() else {
+            continue;
+        };
+        if device_desc.vendor_id() != RAZER_VENDOR_ID {
+            continue;
+        }
+        if let Some(descriptor) = descriptor::by_product_id(device_desc.product_id()) {
+            tracing::info!(
+                icon = EMOJI_TARGET,
+                device = descriptor.name,
+                "Found {} (Vendor ID: 0x{:04X}, Product ID: 0x{:04X})",
+                descriptor.name,
+                device_desc.vendor_id(),
+                device_desc.product_id()
+            );
+            let handle = device
+                .open()
+                .map_err(|err| OpenError::classify(descriptor, err))?;
+            return Ok((handle, descriptor));
+        }
+    }
+    Err(OpenError::NotFound)
+}
+
+/// Opens the keyboard over USB, or the emulator when `RAZER_RGB_EMULATOR` is set.
+/// Traffic is recorded when `RAZER_RGB_CAPTURE` is set.
+pub fn connect() -> Result<Device, OpenError> {
+    let (transport, descriptor): (Box<dyn Transport>, _) = if emulator::requested() {
+        tracing::info!(icon = EMOJI_ROBOT, "Using the emulated keyboard");
+        (Box::new(Emulator::new()), &descriptor::ORNATA_V3)
+    } else {
+        let (handle, descriptor) = find_device()?;
+        let mut transport = UsbTransport::with_descriptor(handle, descriptor);
+        if detach_requested() {
+            transport
+                .claim()
+                .map_err(|err| OpenError::classify(descriptor, err))?;
+        }
+        (Box::new(transport), descriptor)
+    };
+    let mut device = Device::with_descriptor(capture::from_env(transport), *descriptor);
+    let path = config_dir().join(color::CORRECTION_FILE);
+    match color::load_corrections(&path) {
+        Ok(corrections) => {
+            if let Some(correction) = corrections.get(&descriptor.product_id) {
+                tracing::info!(
+                    icon = EMOJI_PAINT,
+                    device = descriptor.name,
+                    "Correcting colors with gamma {} and white balance {:?}",
+                    correction.gamma,
+                    correction.white_balance
+                );
+                device.set_correction(*correction);
+            }
+        }
+        Err(err) => tracing::warn!(icon = EMOJI_WARNING, "Ignoring {}: {err}", path.display()),
+    }
+    Ok(device)
+}
+
+/// Everything we know about what the keys are showing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightingState {
+    pub effect: Effect,
+    pub frame: Frame,
+    pub brightness: u8,
+}
+
+impl Default for LightingState {
+    fn default() -> Self {
+        Self {
+            effect: Effect::default(),
+            frame: Frame::default(),
+            brightness: 0xFF,
+        }
+    }
+}
+
+/// A keyboard behind some transport, together with the lighting state we
+/// last applied to it. The firmware can't report most of this back, so the
+/// cached copy is what servers and the GUI show.
+pub struct Device {
+    transport: Box<dyn Transport>,
+    descriptor: DeviceDescriptor,
+    state: LightingState,
+    correction: ColorCorrection,
+    watchers: Vec<Sender<LightingState>>,
+}
+
+impl Device {
+    /// A device with the Ornata V3's descriptor.
+    pub fn new(transport: impl Transport + 'static) -> Self {
+        Self::with_descriptor(transport, descriptor::ORNATA_V3)
+    }
+
+    pub fn with_descriptor(
+        transport: impl Transport + 'static,
+        descriptor: DeviceDescriptor,
+    ) -> Self {
+        Self {
+            transport: Box::new(transport),
+            descriptor,
+            state: LightingState::default(),
+            correction: ColorCorrection::IDENTITY,
+            watchers: Vec::new(),
+        }
+    }
+
+    /// Returns a channel that receives the new state after every change.
+    pub fn subscribe(&mut self) -> Receiver<LightingState> {
+        let (sender, receiver) = mpsc::channel();
+        self.watchers.push(sender);
+        receiver
+    }
+
+    fn notify(&mut self) {
+        let state = &self.state;
+        self.watchers
+            .retain(|watcher| watcher.send(state.clone()).is_ok());
+    }
+
+    pub fn descriptor(&self) -> &DeviceDescriptor {
+        &self.descriptor
+    }
+
+    pub fn correction(&self) -> &ColorCorrection {
+        &self.correction
+    }
+
+    /// Applies from the next report on; the cached state keeps the colors as
+    /// asked for.
+    pub fn set_correction(&mut self, correction: ColorCorrection) {
+        self.correction = correction;
+    }
+
+    pub fn state(&self) -> &LightingState {
+        &self.state
+    }
+
+    pub fn effect(&self) -> Effect {
+        self.state.effect
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.state.frame
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.state.brightness
+    }
+
+    /// Sends a single report with this device's transaction id and checks
+    /// its status.
+    pub fn send(&mut self, report: &RazerReport) -> Result<RazerReport, TransportError> {
+        let mut report = *report;
+        // The CRC starts at byte 2, so it stays valid.
+        report.transaction_id = self.descriptor.transaction_id;
+        let started = Instant::now();
+        let result = self.transport.send(&report);
+        self.log_exchange(&report, &result, started.elapsed());
+        result
+    }
+
+    /// Sends a report as is and returns the answer whatever its status. The
+    /// cached state is left alone, since we can't know what the report did.
+    pub fn exchange(&mut self, report: &RazerReport) -> Result<RazerReport, TransportError> {
+        let started = Instant::now();
+        let result = self.transport.exchange(report);
+        self.log_exchange(report, &result, started.elapsed());
+        result
+    }
+
+    /// Every exchange at debug level, failures as warnings.
+    fn log_exchange(
+        &self,
+        request: &RazerReport,
+        result: &Result<RazerReport, TransportError>,
+        latency: Duration,
+    ) {
+        let command = commands::lookup(request).map_or("unknown", |def| def.name);
+        let latency_us = latency.as_micros() as u64;
+        match result {
+            Ok(response) => tracing::debug!(
+                icon = EMOJI_RADAR,
+                device = self.descriptor.name,
+                command,
+                status = status_name(response.status),
+                latency_us,
+                "Exchanged {command}"
+            ),
+            Err(err) => tracing::warn!(
+                icon = EMOJI_CROSS,
+                device = self.descriptor.name,
+                command,
+                status = %err,
+                latency_us,
+                "{command} failed: {err}"
+            ),
+        }
+    }
+
+    /// Switches to `effect`. For `Effect::Custom` the cached frame is uploaded
+    /// again so the keys show what we think they show.
+    pub fn set_effect(&mut self, effect: Effect) -> Result<(), TransportError> {
+        if effect == Effect::Custom {
+            let frame = self.state.frame.clone();
+            return self.set_frame(&frame);
+        }
+        self.send(&effect.corrected_report(&self.correction))?;
+        self.state.effect = effect;
+        self.notify();
+        Ok(())
+    }
+
+    /// Uploads `frame` row by row and switches to the custom frame effect.
+    pub fn set_frame(&mut self, frame: &Frame) -> Result<(), TransportError> {
+        for report in frame.corrected_reports(&self.correction) {
+            self.send(&report)?;
+        }
+        self.send(&RazerReport::custom_frame())?;
+        self.state.frame = frame.clone();
+        self.state.effect = Effect::Custom;
+        self.notify();
+        Ok(())
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) -> Result<(), TransportError> {
+        self.send(&RazerReport::brightness(brightness))?;
+        self.state.brightness = brightness;
+        self.notify();
+        Ok(())
+    }
+
+    /// Asks the keyboard for its brightness and caches the answer. Useful
+    /// right after connecting, when the cache only holds defaults.
+    pub fn read_brightness(&mut self) -> Result<u8, TransportError> {
+        let response = self.send(&RazerReport::get_brightness())?;
+        let brightness = commands::find("get_brightness")
+            .expect("get_brightness is in the command table")
+            .byte(&response, "brightness");
+        self.state.brightness = brightness;
+        Ok(brightness)
+    }
+
+    /// Puts the keyboard back into a state captured earlier with `state()`.
+    pub fn restore(&mut self, state: &LightingState) -> Result<(), TransportError> {
+        if state.brightness != self.state.brightness {
+            self.set_brightness(state.brightness)?;
+        }
+        if state.effect == Effect::Custom {
+            self.set_frame(&state.frame)
+        } else {
+            self.state.frame = state.frame.clone();
+            self.set_effect(state.effect)
+        }
+    }
+}
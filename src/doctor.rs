@@ -0,0 +1,300 @@
+//! Setup checks for when the keyboard "isn't found".
+//!
+//! `run` walks every Razer device on the bus, tries to open the supported
+//! ones, asks them for their firmware version and serial, and turns each step
+//! into a line of a checklist with a fix for whatever failed. USB access goes
+//! through `Probe`, so tests can stage any failure.
+
+use crate::commands;
+use crate::descriptor::{self, DeviceDescriptor};
+use crate::device::{detach_requested, Device, OpenError, RAZER_VENDOR_ID};
+use crate::emojis::*;
+use crate::logging::icon;
+use crate::razer_report::RazerReport;
+use crate::transport::{Transport, UsbTransport};
+use rusb::{Context, UsbContext};
+use std::fmt;
+
+/// A USB device as enumeration sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbDeviceInfo {
+    pub bus: u8,
+    pub address: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Interface numbers of the active configuration.
+    pub interfaces: Vec<u8>,
+}
+
+/// The USB operations the checks need.
+pub trait Probe {
+    /// Every device on the bus.
+    fn devices(&mut self) -> Result<Vec<UsbDeviceInfo>, rusb::Error>;
+    /// Opens `device` the way the app would, claim included.
+    fn open(
+        &mut self,
+        device: &UsbDeviceInfo,
+        descriptor: &'static DeviceDescriptor,
+    ) -> Result<Box<dyn Transport>, rusb::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub status: Status,
+    pub label: String,
+    /// What to do about a warning or failure.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(label: impl Into<String>) -> Self {
+        Self {
+            status: Status::Pass,
+            label: label.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(label: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Warn,
+            label: label.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(label: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Fail,
+            label: label.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// No check failed. Warnings are fine.
+    pub fn healthy(&self) -> bool {
+        self.checks.iter().all(|check| check.status != Status::Fail)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let mark = match check.status {
+                Status::Pass => icon(EMOJI_CHECK),
+                Status::Warn => icon(EMOJI_WARNING),
+                Status::Fail => icon(EMOJI_CROSS),
+            };
+            writeln!(f, "{mark} {}", check.label)?;
+            if let Some(fix) = &check.fix {
+                writeln!(f, "   {} {fix}", icon(EMOJI_WRENCH))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn libusb_fix() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "install libusb with `brew install libusb`"
+    } else {
+        "install libusb (e.g. `sudo apt install libusb-1.0-0`)"
+    }
+}
+
+pub fn run(probe: &mut dyn Probe) -> Report {
+    let mut report = Report::default();
+    let devices = match probe.devices() {
+        Ok(devices) => devices,
+        Err(err) => {
+            report.checks.push(Check::fail(
+                format!("libusb can't list devices: {err}"),
+                libusb_fix(),
+            ));
+            return report;
+        }
+    };
+    report.checks.push(Check::pass(format!(
+        "libusb works ({} USB devices)",
+        devices.len()
+    )));
+
+    let razer: Vec<&UsbDeviceInfo> = devices
+        .iter()
+        .filter(|device| device.vendor_id == RAZER_VENDOR_ID)
+        .collect();
+    if razer.is_empty() {
+        report.checks.push(Check::fail(
+            "No Razer devices on the USB bus",
+            "plug the keyboard in directly (not through a KVM), and pass it through if this is a VM",
+        ));
+        return report;
+    }
+
+    for device in razer {
+        let id = format!(
+            "1532:{:04X} on bus {} address {}",
+            device.product_id, device.bus, device.address
+        );
+        let Some(descriptor) = descriptor::by_product_id(device.product_id) else {
+            report.checks.push(Check::warn(
+                format!("Razer device {id} is not a supported keyboard"),
+                "add it to src/descriptor.rs if it is a keyboard using the same protocol",
+            ));
+            continue;
+        };
+        check_keyboard(probe, device, descriptor, &id, &mut report);
+    }
+    report
+}
+
+fn check_keyboard(
+    probe: &mut dyn Probe,
+    device: &UsbDeviceInfo,
+    descriptor: &'static DeviceDescriptor,
+    id: &str,
+    report: &mut Report,
+) {
+    let interfaces: Vec<String> = device.interfaces.iter().map(u8::to_string).collect();
+    report.checks.push(Check::pass(format!(
+        "{} ({id}), interfaces {}",
+        descriptor.name,
+        interfaces.join(", ")
+    )));
+
+    let interface = descriptor.report_index as u8;
+    if !device.interfaces.contains(&interface) {
+        report.checks.push(Check::fail(
+            format!(
+                "{} has no interface {interface} for lighting reports",
+                descriptor.name
+            ),
+            "the report index in src/descriptor.rs is wrong for this model",
+        ));
+        return;
+    }
+
+    let transport = match probe.open(device, descriptor) {
+        Ok(transport) => transport,
+        Err(err) => {
+            let error = OpenError::classify(descriptor, err);
+            let fix = match error {
+                OpenError::Permission(_) if cfg!(target_os = "linux") => {
+                    "run `sudo razer-rgb udev-rule --install`, then replug the keyboard"
+                }
+                OpenError::Permission(_) => "run with sudo",
+                OpenError::Busy(_) => {
+                    "quit Razer Synapse, OpenRazer or another razer-rgb, or set RAZER_RGB_DETACH=1"
+                }
+                _ => "replug the keyboard and try again",
+            };
+            report
+                .checks
+                .push(Check::fail(format!("Can't open: {error}"), fix));
+            return;
+        }
+    };
+    report
+        .checks
+        .push(Check::pass(format!("Opened interface {interface}")));
+
+    let mut keyboard = Device::with_descriptor(transport, *descriptor);
+    match keyboard.send(&RazerReport::get_firmware()) {
+        Ok(response) => {
+            let def = commands::find("get_firmware").expect("get_firmware is in the command table");
+            report.checks.push(Check::pass(format!(
+                "Firmware v{}.{}",
+                def.byte(&response, "major"),
+                def.byte(&response, "minor")
+            )));
+        }
+        Err(err) => {
+            report.checks.push(Check::fail(
+                format!("Keyboard doesn't answer queries: {err}"),
+                "the transaction id or report index in src/descriptor.rs is wrong for this model",
+            ));
+            return;
+        }
+    }
+    match keyboard.send(&RazerReport::get_serial()) {
+        Ok(response) => {
+            let def = commands::find("get_serial").expect("get_serial is in the command table");
+            report.checks.push(Check::pass(format!(
+                "Serial {}",
+                def.text(&response, "serial")
+            )));
+        }
+        Err(err) => report.checks.push(Check::warn(
+            format!("Serial query failed: {err}"),
+            "harmless for lighting, but worth a bug report",
+        )),
+    }
+}
+
+/// The real bus.
+#[derive(Default)]
+pub struct UsbProbe {
+    devices: Vec<(UsbDeviceInfo, rusb::Device<Context>)>,
+}
+
+impl UsbProbe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Probe for UsbProbe {
+    fn devices(&mut self) -> Result<Vec<UsbDeviceInfo>, rusb::Error> {
+        let context = Context::new()?;
+        self.devices.clear();
+        for device in context.devices()?.iter() {
+            let Ok(desc) = device.device_descriptor() else {
+                continue;
+            };
+            let interfaces = device
+                .active_config_descriptor()
+                .map(|config| config.interfaces().map(|i| i.number()).collect())
+                .unwrap_or_default();
+            let info = UsbDeviceInfo {
+                bus: device.bus_number(),
+                address: device.address(),
+                vendor_id: desc.vendor_id(),
+                product_id: desc.product_id(),
+                interfaces,
+            };
+            self.devices.push((info, device));
+        }
+        Ok(self.devices.iter().map(|(info, _)| info.clone()).collect())
+    }
+
+    fn open(
+        &mut self,
+        device: &UsbDeviceInfo,
+        descriptor: &'static DeviceDescriptor,
+    ) -> Result<Box<dyn Transport>, rusb::Error> {
+        let (_, usb) = self
+            .devices
+            .iter()
+            .find(|(info, _)| info == device)
+            .ok_or(rusb::Error::NoDevice)?;
+        let mut transport = UsbTransport::with_descriptor(usb.open()?, descriptor);
+        if detach_requested() {
+            transport.claim()?;
+        }
+        Ok(Box::new(transport))
+    }
+}
@@ -0,0 +1,114 @@
+use crate::color::{ColorCorrection, Rgb};
+use crate::razer_report::RazerReport;
+use serde::{Deserialize, Serialize};
+
+/// A lighting mode the keyboard can run on its own, plus `Custom`, which
+/// shows whatever was last uploaded to the frame buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "EffectSpec", try_from = "EffectSpec")]
+pub enum Effect {
+    Off,
+    Static(Rgb),
+    Breathing(Rgb),
+    #[default]
+    Spectrum,
+    Wave {
+        direction: u8,
+        speed: u8,
+    },
+    Custom,
+}
+
+impl Effect {
+    pub fn report(&self) -> RazerReport {
+        self.corrected_report(&ColorCorrection::IDENTITY)
+    }
+
+    /// The report with `correction` applied to the effect's color.
+    pub fn corrected_report(&self, correction: &ColorCorrection) -> RazerReport {
+        match *self {
+            Effect::Off => RazerReport::none(),
+            Effect::Static(color) => {
+                let Rgb { r, g, b } = correction.apply(color);
+                RazerReport::static_rgb(r, g, b)
+            }
+            Effect::Breathing(color) => {
+                let Rgb { r, g, b } = correction.apply(color);
+                RazerReport::breathing(r, g, b)
+            }
+            Effect::Spectrum => RazerReport::spectrum(),
+            Effect::Wave { direction, speed } => RazerReport::wave(direction, speed),
+            Effect::Custom => RazerReport::custom_frame(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Effect::Off => "off",
+            Effect::Static(_) => "static",
+            Effect::Breathing(_) => "breathing",
+            Effect::Spectrum => "spectrum",
+            Effect::Wave { .. } => "wave",
+            Effect::Custom => "custom",
+        }
+    }
+}
+
+pub const DEFAULT_WAVE_DIRECTION: u8 = 0x01;
+pub const DEFAULT_WAVE_SPEED: u8 = 0x28;
+
+/// Flat JSON form of an effect: `{"effect": "static", "color": [255, 0, 0]}`.
+/// The color may also be `"#FF0000"` or `"red"`.
+#[derive(Serialize, Deserialize)]
+struct EffectSpec {
+    effect: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<Rgb>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    direction: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    speed: Option<u8>,
+}
+
+impl From<Effect> for EffectSpec {
+    fn from(effect: Effect) -> Self {
+        let mut spec = EffectSpec {
+            effect: effect.name().to_string(),
+            color: None,
+            direction: None,
+            speed: None,
+        };
+        match effect {
+            Effect::Static(color) | Effect::Breathing(color) => spec.color = Some(color),
+            Effect::Wave { direction, speed } => {
+                spec.direction = Some(direction);
+                spec.speed = Some(speed);
+            }
+            Effect::Off | Effect::Spectrum | Effect::Custom => {}
+        }
+        spec
+    }
+}
+
+impl TryFrom<EffectSpec> for Effect {
+    type Error = String;
+
+    fn try_from(spec: EffectSpec) -> Result<Self, Self::Error> {
+        let color = || {
+            spec.color
+                .ok_or_else(|| format!("effect \"{}\" needs a color", spec.effect))
+        };
+        match spec.effect.as_str() {
+            "off" => Ok(Effect::Off),
+            "static" => Ok(Effect::Static(color()?)),
+            "breathing" => Ok(Effect::Breathing(color()?)),
+            "spectrum" => Ok(Effect::Spectrum),
+            "wave" => Ok(Effect::Wave {
+                direction: spec.direction.unwrap_or(DEFAULT_WAVE_DIRECTION),
+                speed: spec.speed.unwrap_or(DEFAULT_WAVE_SPEED),
+            }),
+            "custom" => Ok(Effect::Custom),
+            other => Err(format!("unknown effect \"{other}\"")),
+        }
+    }
+}
@@ -56,3 +56,53 @@ pub const EMOJI_GAMEPAD: &str = "🎮";
 pub const EMOJI_PUFF: &str = "💨";
 pub const EMOJI_STARS: &str = "✨";
 pub const EMOJI_RUST: &str = "🦀";
+
+/// Every emoji above with its ASCII stand-in, for logs read without an emoji
+/// font.
+pub static ASCII_TAGS: &[(&str, &str)] = &[
+    (EMOJI_CHECK, "[ok]"),
+    (EMOJI_CROSS, "[error]"),
+    (EMOJI_WARNING, "[warn]"),
+    (EMOJI_INFO, "[info]"),
+    (EMOJI_SUCCESS, "[done]"),
+    (EMOJI_WRONG_WAY, "[denied]"),
+    (EMOJI_RELOAD, "[reload]"),
+    (EMOJI_HOURGLASS, "[wait]"),
+    (EMOJI_STOPWATCH, "[time]"),
+    (EMOJI_SPARKLES, "[new]"),
+    (EMOJI_WRENCH, "[fix]"),
+    (EMOJI_BOLT, "[fast]"),
+    (EMOJI_TARGET, "[found]"),
+    (EMOJI_RADAR, "[net]"),
+    (EMOJI_EYE, "[watch]"),
+    (EMOJI_MAGNIFYING_GLASS, "[check]"),
+    (EMOJI_PAINT, "[color]"),
+    (EMOJI_LAMP, "[light]"),
+    (EMOJI_RED_CIRCLE, "[red]"),
+    (EMOJI_GREEN_CIRCLE, "[green]"),
+    (EMOJI_YELLOW_CIRCLE, "[yellow]"),
+    (EMOJI_BLUE_CIRCLE, "[blue]"),
+    (EMOJI_BELL, "[notify]"),
+    (EMOJI_MUTE, "[mute]"),
+    (EMOJI_BRAIN, "[think]"),
+    (EMOJI_ROBOT, "[emulator]"),
+    (EMOJI_FOLDER, "[dir]"),
+    (EMOJI_FILE, "[file]"),
+    (EMOJI_DATABASE, "[data]"),
+    (EMOJI_CHAT, "[msg]"),
+    (EMOJI_MAIL, "[mail]"),
+    (EMOJI_LOCK, "[lock]"),
+    (EMOJI_UNLOCK, "[unlock]"),
+    (EMOJI_RAINBOW, "[rainbow]"),
+    (EMOJI_WAVE, "[wave]"),
+    (EMOJI_GAMEPAD, "[keyboard]"),
+    (EMOJI_PUFF, "[puff]"),
+    (EMOJI_RUST, "[rust]"),
+];
+
+pub fn ascii_tag(emoji: &str) -> &'static str {
+    ASCII_TAGS
+        .iter()
+        .find(|(known, _)| *known == emoji)
+        .map_or("[*]", |(_, tag)| tag)
+}
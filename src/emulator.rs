@@ -0,0 +1,175 @@
+//! A software Ornata V3 for end-to-end tests.
+//!
+//! `Emulator` is a `Transport` that parses every report the way the firmware
+//! does, keeps the resulting lighting state, and answers with the status,
+//! transaction id and CRC a real keyboard would send. Set
+//! `RAZER_RGB_EMULATOR=1` to run the GUI or CLI against it instead of USB.
+
+use crate::color::Rgb;
+use crate::effect::Effect;
+use crate::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use crate::razer_report::*;
+use crate::transport::{Transport, TransportError};
+use std::sync::{Arc, Mutex};
+
+pub const EMULATOR_ENV: &str = "RAZER_RGB_EMULATOR";
+
+/// What the emulated keyboard currently holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmulatorState {
+    pub effect: Effect,
+    pub brightness: u8,
+    /// The custom frame buffer; shown while `effect` is `Effect::Custom`.
+    pub frame: Frame,
+    pub serial: String,
+    pub firmware: (u8, u8),
+    pub device_mode: (u8, u8),
+    /// Number of reports received, including rejected ones.
+    pub reports: usize,
+}
+
+impl Default for EmulatorState {
+    fn default() -> Self {
+        Self {
+            effect: Effect::Spectrum,
+            brightness: 0xFF,
+            frame: Frame::new(MATRIX_ROWS, MATRIX_COLS),
+            serial: "PM2143H00000001".to_string(),
+            firmware: (1, 2),
+            device_mode: (0x00, 0x00),
+            reports: 0,
+        }
+    }
+}
+
+/// Clones share the same keyboard, so a test can look at the state while a
+/// `Device` owns the transport.
+#[derive(Clone, Default)]
+pub struct Emulator {
+    state: Arc<Mutex<EmulatorState>>,
+}
+
+impl Emulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_state(state: EmulatorState) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    pub fn state(&self) -> EmulatorState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Handles one request and builds the response report.
+    pub fn respond(&self, request: &RazerReport) -> RazerReport {
+        let mut state = self.state.lock().unwrap();
+        state.reports += 1;
+
+        let mut response = *request;
+        response.status = if !request.crc_valid() || request.data_size as usize > 80 {
+            RAZER_CMD_FAILURE
+        } else {
+            state.handle(request, &mut response.arguments)
+        };
+        response.calculate_crc();
+        response
+    }
+}
+
+impl EmulatorState {
+    /// Applies a command, writing any returned data into `out`. Returns the
+    /// status byte.
+    fn handle(&mut self, request: &RazerReport, out: &mut [u8; 80]) -> u8 {
+        let args = &request.arguments;
+        match (request.command_class, request.command_id) {
+            (0x00, 0x04) => {
+                self.device_mode = (args[0], args[1]);
+                RAZER_CMD_SUCCESSFUL
+            }
+            (0x00, 0x81) => {
+                out[0] = self.firmware.0;
+                out[1] = self.firmware.1;
+                RAZER_CMD_SUCCESSFUL
+            }
+            (0x00, 0x82) => {
+                out[..22].fill(0);
+                let serial = self.serial.as_bytes();
+                let len = serial.len().min(22);
+                out[..len].copy_from_slice(&serial[..len]);
+                RAZER_CMD_SUCCESSFUL
+            }
+            (0x00, 0x84) => {
+                out[0] = self.device_mode.0;
+                out[1] = self.device_mode.1;
+                RAZER_CMD_SUCCESSFUL
+            }
+            (0x0F, 0x02) if args[1] == BACKLIGHT_LED => match parse_effect(args) {
+                Some(effect) => {
+                    self.effect = effect;
+                    RAZER_CMD_SUCCESSFUL
+                }
+                None => RAZER_CMD_NOT_SUPPORTED,
+            },
+            (0x0F, 0x03) => self.set_frame_row(args),
+            (0x0F, 0x04) if args[1] == BACKLIGHT_LED => {
+                self.brightness = args[2];
+                RAZER_CMD_SUCCESSFUL
+            }
+            (0x0F, 0x84) if args[1] == BACKLIGHT_LED => {
+                out[2] = self.brightness;
+                RAZER_CMD_SUCCESSFUL
+            }
+            (0x0F, 0x02 | 0x04 | 0x84) => RAZER_CMD_FAILURE,
+            _ => RAZER_CMD_NOT_SUPPORTED,
+        }
+    }
+
+    fn set_frame_row(&mut self, args: &[u8; 80]) -> u8 {
+        let (row, start, stop) = (args[2] as usize, args[3] as usize, args[4] as usize);
+        if row >= self.frame.rows() || start > stop || stop >= self.frame.cols() {
+            return RAZER_CMD_FAILURE;
+        }
+        for col in start..=stop {
+            let offset = 5 + (col - start) * 3;
+            self.frame.set(
+                row,
+                col,
+                Rgb::new(args[offset], args[offset + 1], args[offset + 2]),
+            );
+        }
+        RAZER_CMD_SUCCESSFUL
+    }
+}
+
+/// Reads the arguments of a set-extended-effect command.
+fn parse_effect(args: &[u8; 80]) -> Option<Effect> {
+    let color = Rgb::new(args[6], args[7], args[8]);
+    match args[2] {
+        EXT_EFFECT_NONE => Some(Effect::Off),
+        EXT_EFFECT_STATIC => Some(Effect::Static(color)),
+        // Only single-color breathing is modelled.
+        EXT_EFFECT_BREATHING if args[3] == 0x01 => Some(Effect::Breathing(color)),
+        EXT_EFFECT_SPECTRUM => Some(Effect::Spectrum),
+        EXT_EFFECT_WAVE => Some(Effect::Wave {
+            direction: args[3],
+            speed: args[4],
+        }),
+        EXT_EFFECT_CUSTOM_FRAME => Some(Effect::Custom),
+        _ => None,
+    }
+}
+
+impl Transport for Emulator {
+    fn exchange(&mut self, request: &RazerReport) -> Result<RazerReport, TransportError> {
+        Ok(self.respond(request))
+    }
+}
+
+/// Whether `RAZER_RGB_EMULATOR` asks for the emulator instead of USB.
+pub fn requested() -> bool {
+    std::env::var_os(EMULATOR_ENV).is_some_and(|value| !value.is_empty() && value != "0")
+}
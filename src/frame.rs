@@ -0,0 +1,131 @@
+use crate::color::{ColorCorrection, Rgb};
+use crate::razer_report::RazerReport;
+use serde::{Deserialize, Serialize};
+
+/// Matrix size of the Ornata V3 custom frame buffer.
+pub const MATRIX_ROWS: usize = 6;
+pub const MATRIX_COLS: usize = 22;
+
+/// A per-key color buffer, row-major. Serialized as a list of rows; only
+/// MATRIX_ROWS rows of MATRIX_COLS colors deserialize.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "Vec<Vec<Rgb>>", try_from = "Vec<Vec<Rgb>>")]
+pub struct Frame {
+    rows: usize,
+    cols: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new(MATRIX_ROWS, MATRIX_COLS)
+    }
+}
+
+impl Frame {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            pixels: vec![Rgb::BLACK; rows * cols],
+        }
+    }
+
+    pub fn filled(rows: usize, cols: usize, color: Rgb) -> Self {
+        let mut frame = Self::new(rows, cols);
+        frame.fill(color);
+        frame
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Rgb {
+        self.pixels[row * self.cols + col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, color: Rgb) {
+        self.pixels[row * self.cols + col] = color;
+    }
+
+    pub fn fill(&mut self, color: Rgb) {
+        self.pixels.fill(color);
+    }
+
+    pub fn row(&self, row: usize) -> &[Rgb] {
+        &self.pixels[row * self.cols..(row + 1) * self.cols]
+    }
+
+    pub fn pixels(&self) -> &[Rgb] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Rgb] {
+        &mut self.pixels
+    }
+
+    /// Mixes every key towards the same key of `other`, which must have the
+    /// same size.
+    pub fn lerp(&self, other: &Frame, t: f32) -> Frame {
+        assert_eq!(
+            (self.rows, self.cols),
+            (other.rows, other.cols),
+            "frames differ in size"
+        );
+        Frame {
+            rows: self.rows,
+            cols: self.cols,
+            pixels: self
+                .pixels
+                .iter()
+                .zip(&other.pixels)
+                .map(|(a, b)| a.lerp(*b, t))
+                .collect(),
+        }
+    }
+
+    /// One upload report per row. Send these, then `RazerReport::custom_frame`
+    /// to put the buffer on the keys.
+    pub fn reports(&self) -> Vec<RazerReport> {
+        self.corrected_reports(&ColorCorrection::IDENTITY)
+    }
+
+    /// `reports` with `correction` applied to every key.
+    pub fn corrected_reports(&self, correction: &ColorCorrection) -> Vec<RazerReport> {
+        (0..self.rows)
+            .map(|row| {
+                let colors: Vec<Rgb> = self.row(row).iter().map(|&c| correction.apply(c)).collect();
+                RazerReport::custom_frame_row(row as u8, 0, &colors)
+            })
+            .collect()
+    }
+}
+
+impl From<Frame> for Vec<Vec<Rgb>> {
+    fn from(frame: Frame) -> Self {
+        frame.pixels.chunks(frame.cols).map(<[_]>::to_vec).collect()
+    }
+}
+
+impl TryFrom<Vec<Vec<Rgb>>> for Frame {
+    type Error = String;
+
+    fn try_from(rows: Vec<Vec<Rgb>>) -> Result<Self, Self::Error> {
+        // Anything else would be partly uploaded, or not fit a report row.
+        if rows.len() != MATRIX_ROWS || rows.iter().any(|row| row.len() != MATRIX_COLS) {
+            return Err(format!(
+                "a frame must be {MATRIX_ROWS} rows of {MATRIX_COLS} colors"
+            ));
+        }
+        Ok(Self {
+            rows: MATRIX_ROWS,
+            cols: MATRIX_COLS,
+            pixels: rows.concat(),
+        })
+    }
+}
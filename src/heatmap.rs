@@ -0,0 +1,401 @@
+//! Colors keys by how often they are pressed. Every press heats its key up
+//! and the heat halves every `half_life`; without a half-life the keys show
+//! all presses so far, scaled to the busiest key.
+//!
+//! Presses come from a `KeySource` as Linux key codes and find their key
+//! through the layout (`Layout::key_for_code`). On Linux `KeyEvents` reads
+//! every keyboard, which needs root or the `input` group. A `Recording`
+//! replays presses from a file with one JSON line per press,
+//! `{"at_ms": 120, "code": 30}`, which `Recorder` writes.
+//!
+//! Settings live in `<config dir>/heatmap.json`:
+//!
+//! ```json
+//! { "half_life_ms": 10000, "saturation": 10, "gradient": ["#000028", "blue", "red", "yellow"] }
+//! ```
+//!
+//! Counts per key code can be kept across sessions in `heatmap-counts.json`
+//! (`HeatmapEffect::keep_counts`). They are saved every minute and when the
+//! presses run out, so stopping the effect loses at most a minute of them.
+
+use crate::animation::Animation;
+use crate::color::{Gradient, Rgb};
+use crate::emojis::EMOJI_WARNING;
+use crate::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use crate::layout::Layout;
+use crate::transition::{millis, optional_millis};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const HEATMAP_FILE: &str = "heatmap.json";
+pub const COUNTS_FILE: &str = "heatmap-counts.json";
+
+/// Time between two frames.
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How often kept counts are written back.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// One key press, `at` from the start of the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyPress {
+    #[serde(rename = "at_ms", with = "millis")]
+    pub at: Duration,
+    /// Linux key code.
+    pub code: u16,
+}
+
+pub trait KeySource: Send {
+    /// Adds the codes of keys pressed up to `t` from the start that weren't
+    /// read before. Returns false once no more presses will come.
+    fn read(&mut self, t: Duration, codes: &mut Vec<u16>) -> io::Result<bool>;
+}
+
+impl<S: KeySource + ?Sized> KeySource for Box<S> {
+    fn read(&mut self, t: Duration, codes: &mut Vec<u16>) -> io::Result<bool> {
+        (**self).read(t, codes)
+    }
+}
+
+/// Presses read from a file, given out at the times they were recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    presses: Vec<KeyPress>,
+    next: usize,
+}
+
+impl Recording {
+    pub fn new(mut presses: Vec<KeyPress>) -> Self {
+        presses.sort_by_key(|press| press.at);
+        Self { presses, next: 0 }
+    }
+
+    /// One JSON press per line; blank lines are skipped.
+    pub fn parse(reader: impl BufRead) -> io::Result<Self> {
+        let mut presses = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let press = serde_json::from_str(&line).map_err(|err| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {err}", i + 1))
+            })?;
+            presses.push(press);
+        }
+        Ok(Self::new(presses))
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::parse(BufReader::new(File::open(path)?))
+    }
+
+    pub fn presses(&self) -> &[KeyPress] {
+        &self.presses
+    }
+}
+
+impl KeySource for Recording {
+    fn read(&mut self, t: Duration, codes: &mut Vec<u16>) -> io::Result<bool> {
+        while let Some(press) = self.presses.get(self.next).filter(|press| press.at <= t) {
+            codes.push(press.code);
+            self.next += 1;
+        }
+        Ok(self.next < self.presses.len())
+    }
+}
+
+/// Passes presses through from `source` and writes each to `out` as a
+/// recording line.
+pub struct Recorder<S, W> {
+    source: S,
+    out: W,
+}
+
+impl<S: KeySource, W: Write + Send> Recorder<S, W> {
+    pub fn new(source: S, out: W) -> Self {
+        Self { source, out }
+    }
+}
+
+impl<S: KeySource, W: Write + Send> KeySource for Recorder<S, W> {
+    fn read(&mut self, t: Duration, codes: &mut Vec<u16>) -> io::Result<bool> {
+        let start = codes.len();
+        let more = self.source.read(t, codes)?;
+        for &code in &codes[start..] {
+            let line = serde_json::to_string(&KeyPress { at: t, code })?;
+            writeln!(self.out, "{line}")?;
+        }
+        self.out.flush()?;
+        Ok(more)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::KeyEvents;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::KeySource;
+    use crate::idle::{watch_input, EV_KEY};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Key presses from every input device. Auto-repeat doesn't count.
+    pub struct KeyEvents {
+        pressed: Arc<Mutex<Vec<u16>>>,
+    }
+
+    impl KeyEvents {
+        pub fn open() -> io::Result<Self> {
+            let pressed = Arc::new(Mutex::new(Vec::new()));
+            let queue = Arc::clone(&pressed);
+            watch_input(move |kind, code, value| {
+                if kind == EV_KEY && value == 1 {
+                    queue.lock().unwrap().push(code);
+                }
+            })?;
+            Ok(Self { pressed })
+        }
+    }
+
+    impl KeySource for KeyEvents {
+        fn read(&mut self, _t: Duration, codes: &mut Vec<u16>) -> io::Result<bool> {
+            codes.append(&mut self.pressed.lock().unwrap());
+            Ok(true)
+        }
+    }
+}
+
+/// Presses per key code.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KeyCounts(BTreeMap<u16, u64>);
+
+impl KeyCounts {
+    /// Missing file means no presses yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        serde_json::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, text)
+    }
+
+    pub fn add(&mut self, code: u16) {
+        *self.0.entry(code).or_default() += 1;
+    }
+
+    pub fn get(&self, code: u16) -> u64 {
+        self.0.get(&code).copied().unwrap_or_default()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.values().sum()
+    }
+
+    /// Codes and counts, most pressed first.
+    pub fn ranked(&self) -> Vec<(u16, u64)> {
+        let mut ranked: Vec<_> = self.0.iter().map(|(&code, &count)| (code, count)).collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeatmapSettings {
+    /// How long heat takes to halve; `null` keeps every press.
+    #[serde(rename = "half_life_ms", with = "optional_millis")]
+    pub half_life: Option<Duration>,
+    /// Heat at which a key reaches the end of the gradient, in presses.
+    /// Only used with a half-life.
+    pub saturation: f32,
+    /// Cold to hot.
+    pub gradient: Gradient,
+}
+
+impl Default for HeatmapSettings {
+    fn default() -> Self {
+        Self {
+            half_life: Some(Duration::from_secs(10)),
+            saturation: 10.0,
+            gradient: Gradient::new(vec![
+                Rgb::new(0, 0, 40),
+                Rgb::BLUE,
+                Rgb::RED,
+                Rgb::new(255, 255, 0),
+            ])
+            .expect("the default heatmap gradient has stops"),
+        }
+    }
+}
+
+impl HeatmapSettings {
+    /// Missing file means the defaults.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        let settings: Self = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        settings
+            .validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.half_life.is_some_and(|half_life| half_life.is_zero()) {
+            return Err("the half-life must be above 0".to_string());
+        }
+        if self.saturation.is_nan() || self.saturation <= 0.0 {
+            return Err("the saturation must be above 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub struct HeatmapEffect<S> {
+    source: S,
+    layout: Layout,
+    settings: HeatmapSettings,
+    counts: KeyCounts,
+    /// Heat of each matrix cell, row-major.
+    heat: Vec<f32>,
+    /// Time of the last frame.
+    last: Duration,
+    /// Codes the layout has no key for, logged once each.
+    unmapped: HashSet<u16>,
+    /// Where to keep counts, and when they were last saved.
+    counts_file: Option<(PathBuf, Duration)>,
+}
+
+impl<S: KeySource> HeatmapEffect<S> {
+    pub fn new(source: S, layout: Layout, settings: HeatmapSettings) -> Self {
+        Self {
+            source,
+            layout,
+            settings,
+            counts: KeyCounts::default(),
+            heat: vec![0.0; MATRIX_ROWS * MATRIX_COLS],
+            last: Duration::ZERO,
+            unmapped: HashSet::new(),
+            counts_file: None,
+        }
+    }
+
+    /// Adds the counts saved in `path` and saves there from now on. Without
+    /// a half-life the saved presses show right away.
+    pub fn keep_counts(&mut self, path: &Path) -> io::Result<()> {
+        let saved = KeyCounts::load(path)?;
+        for (code, count) in saved.ranked() {
+            *self.counts.0.entry(code).or_default() += count;
+            if self.settings.half_life.is_none() {
+                self.heat_up(code, count as f32);
+            }
+        }
+        self.counts_file = Some((path.to_path_buf(), self.last));
+        Ok(())
+    }
+
+    pub fn counts(&self) -> &KeyCounts {
+        &self.counts
+    }
+
+    /// Heat of the key at `row`, `col`.
+    pub fn heat(&self, row: usize, col: usize) -> f32 {
+        self.heat[row * MATRIX_COLS + col]
+    }
+
+    pub fn press(&mut self, code: u16) {
+        self.counts.add(code);
+        self.heat_up(code, 1.0);
+    }
+
+    fn heat_up(&mut self, code: u16, amount: f32) {
+        let Some(key) = self.layout.key_for_code(code) else {
+            if self.unmapped.insert(code) {
+                tracing::debug!("No key in the {} layout for code {code}", self.layout.name);
+            }
+            return;
+        };
+        if key.row < MATRIX_ROWS && key.col < MATRIX_COLS {
+            self.heat[key.row * MATRIX_COLS + key.col] += amount;
+        }
+    }
+
+    /// Lets `elapsed` worth of heat fade.
+    pub fn cool(&mut self, elapsed: Duration) {
+        let Some(half_life) = self.settings.half_life else {
+            return;
+        };
+        let factor = 0.5f32.powf(elapsed.as_secs_f32() / half_life.as_secs_f32());
+        for heat in &mut self.heat {
+            *heat *= factor;
+        }
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        // Without decay, the busiest key is the hottest color.
+        let full = match self.settings.half_life {
+            Some(_) => self.settings.saturation,
+            None => self.heat.iter().copied().fold(1.0, f32::max),
+        };
+        for row in 0..frame.rows().min(MATRIX_ROWS) {
+            for col in 0..frame.cols().min(MATRIX_COLS) {
+                let level = (self.heat(row, col) / full).min(1.0);
+                frame.set(row, col, self.settings.gradient.at(level));
+            }
+        }
+    }
+}
+
+impl<S: KeySource> Animation for HeatmapEffect<S> {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> Option<Duration> {
+        self.cool(t.saturating_sub(self.last));
+        self.last = t;
+        let mut codes = Vec::new();
+        let more = match self.source.read(t, &mut codes) {
+            Ok(more) => more,
+            Err(err) => {
+                tracing::warn!(icon = EMOJI_WARNING, "Reading key presses failed: {err}");
+                false
+            }
+        };
+        for code in codes {
+            self.press(code);
+        }
+        if let Some((path, saved_at)) = &mut self.counts_file {
+            if !more || t.saturating_sub(*saved_at) >= SAVE_INTERVAL {
+                if let Err(err) = self.counts.save(path) {
+                    tracing::warn!(
+                        icon = EMOJI_WARNING,
+                        "Cannot save {}: {err}",
+                        path.display()
+                    );
+                }
+                *saved_at = t;
+            }
+        }
+        self.draw(frame);
+        more.then_some(FRAME_INTERVAL)
+    }
+}
@@ -0,0 +1,275 @@
+//! Dims the backlight when nobody is typing and turns it off a while later;
+//! the next key press or mouse move brings back what was showing.
+//!
+//! How long the user has been idle comes from an `IdleSource`. On Linux,
+//! `InputEvents` watches keys, buttons and pointer motion on every readable
+//! `/dev/input/event*` device, which needs root or the `input` group.
+//!
+//! If something else changes the lighting while the keys are dimmed (the
+//! schedule, the API), waking up only puts the brightness back and keeps the
+//! new effect.
+
+use crate::device::{Device, LightingState};
+use crate::effect::Effect;
+use crate::emojis::{EMOJI_LAMP, EMOJI_WARNING};
+use crate::transition::{Easing, Fade, Transition};
+use crate::transport::TransportError;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often a running dimmer asks its source.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub trait IdleSource: Send {
+    /// Time since the last user input.
+    fn idle_for(&mut self) -> io::Result<Duration>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleSettings {
+    /// When to fade down to `dim_brightness`; `None` skips dimming.
+    pub dim_after: Option<Duration>,
+    pub dim_brightness: u8,
+    pub off_after: Duration,
+    /// How long dimming and turning off take.
+    pub fade: Duration,
+}
+
+impl IdleSettings {
+    pub fn new(off_after: Duration) -> Self {
+        Self {
+            dim_after: None,
+            dim_brightness: 0x40,
+            off_after,
+            fade: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Active,
+    Dimmed,
+    Off,
+}
+
+pub struct IdleDimmer {
+    source: Box<dyn IdleSource>,
+    settings: IdleSettings,
+    phase: Phase,
+    /// What to go back to on activity.
+    saved: Option<LightingState>,
+    /// What we left the keyboard showing, to notice changes by others.
+    left: Option<LightingState>,
+}
+
+impl IdleDimmer {
+    pub fn new(source: impl IdleSource + 'static, settings: IdleSettings) -> Self {
+        Self {
+            source: Box::new(source),
+            settings,
+            phase: Phase::Active,
+            saved: None,
+            left: None,
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    fn wanted(&self, idle: Duration) -> Phase {
+        if idle >= self.settings.off_after {
+            Phase::Off
+        } else if self.settings.dim_after.is_some_and(|dim| idle >= dim) {
+            Phase::Dimmed
+        } else {
+            Phase::Active
+        }
+    }
+
+    /// Asks the source and dims, turns off or wakes the keyboard as needed.
+    /// Returns the phase it moved to, if it changed.
+    pub fn poll(&mut self, device: &Mutex<Device>) -> io::Result<Option<Phase>> {
+        let idle = self.source.idle_for()?;
+        let wanted = self.wanted(idle);
+        if wanted == self.phase {
+            return Ok(None);
+        }
+        // Coming back from off to dimmed means there was activity in between.
+        let next = match (self.phase, wanted) {
+            (Phase::Off, Phase::Dimmed) => Phase::Active,
+            _ => wanted,
+        };
+        let result = match next {
+            Phase::Active => self.wake(device),
+            Phase::Dimmed => self.dim(device),
+            Phase::Off => self.turn_off(device),
+        };
+        result.map_err(io::Error::other)?;
+        self.phase = next;
+        Ok(Some(next))
+    }
+
+    /// Fades on the shared device, which stays free for others between steps.
+    fn fade_brightness(
+        &self,
+        device: &Mutex<Device>,
+        brightness: u8,
+    ) -> Result<(), TransportError> {
+        let from = device.lock().unwrap().state().clone();
+        let to = LightingState {
+            brightness,
+            ..from.clone()
+        };
+        let transition = Transition::new(self.settings.fade, Easing::EaseInOut);
+        Fade::new(&from, &to, &transition)
+            .play(device, |duration| {
+                thread::sleep(duration);
+                true
+            })
+            .map(|_| ())
+    }
+
+    fn dim(&mut self, device: &Mutex<Device>) -> Result<(), TransportError> {
+        let saved = self
+            .saved
+            .get_or_insert_with(|| device.lock().unwrap().state().clone());
+        let brightness = self.settings.dim_brightness.min(saved.brightness);
+        self.fade_brightness(device, brightness)?;
+        self.left = Some(device.lock().unwrap().state().clone());
+        Ok(())
+    }
+
+    fn turn_off(&mut self, device: &Mutex<Device>) -> Result<(), TransportError> {
+        self.saved
+            .get_or_insert_with(|| device.lock().unwrap().state().clone());
+        self.fade_brightness(device, 0)?;
+        let mut device = device.lock().unwrap();
+        device.set_effect(Effect::Off)?;
+        self.left = Some(device.state().clone());
+        Ok(())
+    }
+
+    fn wake(&mut self, device: &Mutex<Device>) -> Result<(), TransportError> {
+        let (Some(saved), left) = (self.saved.take(), self.left.take()) else {
+            return Ok(());
+        };
+        let mut device = device.lock().unwrap();
+        if left.as_ref() == Some(device.state()) {
+            device.restore(&saved)
+        } else {
+            device.set_brightness(saved.brightness)
+        }
+    }
+
+    /// Polls on a thread until the source fails.
+    pub fn spawn(mut self, device: Arc<Mutex<Device>>) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            match self.poll(&device) {
+                Ok(Some(phase)) => tracing::info!(icon = EMOJI_LAMP, "Idle: {phase:?}"),
+                Ok(None) => {}
+                Err(err) => {
+                    tracing::warn!(icon = EMOJI_WARNING, "Idle dimming stopped: {err}");
+                    return;
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::InputEvents;
+#[cfg(target_os = "linux")]
+pub(crate) use linux::{watch_input, EV_KEY};
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::IdleSource;
+    use std::fs::{self, File};
+    use std::io::{self, Read};
+    use std::mem::size_of;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    pub const EV_KEY: u16 = 0x01;
+    const EV_REL: u16 = 0x02;
+    const EV_ABS: u16 = 0x03;
+
+    /// `struct input_event`: a `timeval` (two longs), type, code and value.
+    const TIME_SIZE: usize = 2 * size_of::<usize>();
+    const EVENT_SIZE: usize = TIME_SIZE + 8;
+
+    /// Reads every readable input device on its own thread and calls
+    /// `on_event` with the type, code and value of each event. Devices
+    /// plugged in later are not picked up.
+    pub fn watch_input(on_event: impl Fn(u16, u16, i32) + Send + Sync + 'static) -> io::Result<()> {
+        let on_event = Arc::new(on_event);
+        let mut opened = 0;
+        for entry in fs::read_dir("/dev/input")? {
+            let path = entry?.path();
+            let is_event = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("event"));
+            if !is_event {
+                continue;
+            }
+            let Ok(file) = File::open(&path) else {
+                continue;
+            };
+            let on_event = Arc::clone(&on_event);
+            thread::spawn(move || watch(file, on_event.as_ref()));
+            opened += 1;
+        }
+        if opened == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "no readable /dev/input/event* device (run as root or join the input group)",
+            ));
+        }
+        Ok(())
+    }
+
+    fn watch(mut file: File, on_event: &dyn Fn(u16, u16, i32)) {
+        let mut event = [0u8; EVENT_SIZE];
+        while file.read_exact(&mut event).is_ok() {
+            let field = |at: usize| [event[TIME_SIZE + at], event[TIME_SIZE + at + 1]];
+            let value = [4, 5, 6, 7].map(|i| event[TIME_SIZE + i]);
+            on_event(
+                u16::from_ne_bytes(field(0)),
+                u16::from_ne_bytes(field(2)),
+                i32::from_ne_bytes(value),
+            );
+        }
+    }
+
+    /// Remembers the time of the last key, button or motion event on any
+    /// input device.
+    pub struct InputEvents {
+        last: Arc<Mutex<Instant>>,
+    }
+
+    impl InputEvents {
+        pub fn open() -> io::Result<Self> {
+            let last = Arc::new(Mutex::new(Instant::now()));
+            let seen = Arc::clone(&last);
+            watch_input(move |kind, _, _| {
+                if matches!(kind, EV_KEY | EV_REL | EV_ABS) {
+                    *seen.lock().unwrap() = Instant::now();
+                }
+            })?;
+            Ok(Self { last })
+        }
+    }
+
+    impl IdleSource for InputEvents {
+        fn idle_for(&mut self) -> io::Result<Duration> {
+            Ok(self.last.lock().unwrap().elapsed())
+        }
+    }
+}
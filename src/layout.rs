@@ -0,0 +1,286 @@
+//! Where the keys of the lighting matrix physically sit, so images and
+//! effects can be drawn by position instead of by matrix cell.
+//!
+//! Layouts are JSON files in `<config dir>/layouts`, named after the product
+//! id as four hex digits (`02A1.json`):
+//!
+//! ```json
+//! {
+//!   "name": "Ornata V3 ANSI",
+//!   "keys": [
+//!     { "row": 1, "col": 1, "x": 0, "y": 1.25, "name": "`" },
+//!     { "row": 3, "col": 1, "x": 0, "y": 3.25, "width": 1.75, "name": "caps lock", "code": 58 }
+//!   ]
+//! }
+//! ```
+//!
+//! Positions are in key units (one letter key is 1 wide and 1 high) from the
+//! top left corner. Without a file every matrix cell is taken to be a 1u key
+//! at its row and column, which is close for the letter rows and off for
+//! wide keys and gaps.
+//!
+//! Input events name keys by their Linux key code. A key matches a code by
+//! its `code`, or else by its name when that is the code's usual name
+//! (`code_name`), so layouts with names mostly don't need codes.
+
+use crate::config::config_dir;
+use crate::descriptor::DeviceDescriptor;
+use crate::emojis::{EMOJI_FILE, EMOJI_WARNING};
+use crate::frame::{MATRIX_COLS, MATRIX_ROWS};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const LAYOUT_DIR: &str = "layouts";
+
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Key {
+    /// The matrix cell lighting this key.
+    pub row: usize,
+    pub col: usize,
+    /// Left and top edges, in key units.
+    pub x: f32,
+    pub y: f32,
+    #[serde(default = "one")]
+    pub width: f32,
+    #[serde(default = "one")]
+    pub height: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Linux input key code (`KEY_*` in `input-event-codes.h`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<u16>,
+}
+
+impl Key {
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    pub name: String,
+    pub keys: Vec<Key>,
+}
+
+impl Layout {
+    /// Every cell of a `rows` x `cols` matrix as a 1u key.
+    pub fn grid(rows: usize, cols: usize) -> Self {
+        let keys = (0..rows)
+            .flat_map(|row| {
+                (0..cols).map(move |col| Key {
+                    row,
+                    col,
+                    x: col as f32,
+                    y: row as f32,
+                    width: 1.0,
+                    height: 1.0,
+                    name: None,
+                    code: None,
+                })
+            })
+            .collect();
+        Self {
+            name: "grid".to_string(),
+            keys,
+        }
+    }
+
+    /// Width and height of the box around all keys.
+    pub fn size(&self) -> (f32, f32) {
+        self.keys.iter().fold((0.0, 0.0), |(width, height), key| {
+            (
+                f32::max(width, key.x + key.width),
+                f32::max(height, key.y + key.height),
+            )
+        })
+    }
+
+    /// Looks a key up by name, ignoring case.
+    pub fn key(&self, name: &str) -> Option<&Key> {
+        self.keys.iter().find(|key| {
+            key.name
+                .as_deref()
+                .is_some_and(|key| key.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// The key sending Linux key code `code`.
+    pub fn key_for_code(&self, code: u16) -> Option<&Key> {
+        self.keys
+            .iter()
+            .find(|key| key.code == Some(code))
+            .or_else(|| code_name(code).and_then(|name| self.key(name)))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for key in &self.keys {
+            if key.row >= MATRIX_ROWS || key.col >= MATRIX_COLS {
+                return Err(format!(
+                    "key at row {} column {} is outside the {MATRIX_ROWS}x{MATRIX_COLS} matrix",
+                    key.row, key.col
+                ));
+            }
+            if key.x < 0.0 || key.y < 0.0 || key.width <= 0.0 || key.height <= 0.0 {
+                return Err(format!(
+                    "key at row {} column {} has a bad position or size",
+                    key.row, key.col
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let layout: Self = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        layout
+            .validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(layout)
+    }
+
+    pub fn path_for(descriptor: &DeviceDescriptor) -> PathBuf {
+        config_dir()
+            .join(LAYOUT_DIR)
+            .join(format!("{:04X}.json", descriptor.product_id))
+    }
+
+    /// The keyboard's layout file, or the plain grid if there is none or it
+    /// can't be read.
+    pub fn for_device(descriptor: &DeviceDescriptor) -> Self {
+        let path = Self::path_for(descriptor);
+        match Self::load(&path) {
+            Ok(layout) => {
+                tracing::info!(icon = EMOJI_FILE, "Using the {} layout", layout.name);
+                layout
+            }
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    tracing::warn!(icon = EMOJI_WARNING, "Ignoring {}: {err}", path.display());
+                }
+                Self::grid(MATRIX_ROWS, MATRIX_COLS)
+            }
+        }
+    }
+}
+
+/// Usual names of the Linux key codes of a full-size keyboard, as layouts
+/// spell them.
+const CODE_NAMES: [(u16, &str); 100] = [
+    (1, "esc"),
+    (2, "1"),
+    (3, "2"),
+    (4, "3"),
+    (5, "4"),
+    (6, "5"),
+    (7, "6"),
+    (8, "7"),
+    (9, "8"),
+    (10, "9"),
+    (11, "0"),
+    (12, "-"),
+    (13, "="),
+    (14, "backspace"),
+    (15, "tab"),
+    (16, "q"),
+    (17, "w"),
+    (18, "e"),
+    (19, "r"),
+    (20, "t"),
+    (21, "y"),
+    (22, "u"),
+    (23, "i"),
+    (24, "o"),
+    (25, "p"),
+    (26, "["),
+    (27, "]"),
+    (28, "enter"),
+    (29, "left ctrl"),
+    (30, "a"),
+    (31, "s"),
+    (32, "d"),
+    (33, "f"),
+    (34, "g"),
+    (35, "h"),
+    (36, "j"),
+    (37, "k"),
+    (38, "l"),
+    (39, ";"),
+    (40, "'"),
+    (41, "`"),
+    (42, "left shift"),
+    (43, "\\"),
+    (44, "z"),
+    (45, "x"),
+    (46, "c"),
+    (47, "v"),
+    (48, "b"),
+    (49, "n"),
+    (50, "m"),
+    (51, ","),
+    (52, "."),
+    (53, "/"),
+    (54, "right shift"),
+    (55, "kp *"),
+    (56, "left alt"),
+    (57, "space"),
+    (58, "caps lock"),
+    (59, "f1"),
+    (60, "f2"),
+    (61, "f3"),
+    (62, "f4"),
+    (63, "f5"),
+    (64, "f6"),
+    (65, "f7"),
+    (66, "f8"),
+    (67, "f9"),
+    (68, "f10"),
+    (69, "num lock"),
+    (70, "scroll lock"),
+    (71, "kp 7"),
+    (72, "kp 8"),
+    (73, "kp 9"),
+    (74, "kp -"),
+    (75, "kp 4"),
+    (76, "kp 5"),
+    (77, "kp 6"),
+    (78, "kp +"),
+    (79, "kp 1"),
+    (80, "kp 2"),
+    (81, "kp 3"),
+    (82, "kp 0"),
+    (83, "kp ."),
+    (87, "f11"),
+    (88, "f12"),
+    (96, "kp enter"),
+    (97, "right ctrl"),
+    (98, "kp /"),
+    (99, "print screen"),
+    (100, "right alt"),
+    (102, "home"),
+    (103, "up"),
+    (104, "page up"),
+    (105, "left"),
+    (106, "right"),
+    (107, "end"),
+    (108, "down"),
+    (109, "page down"),
+    (110, "insert"),
+    (111, "delete"),
+];
+
+/// The usual name of Linux key code `code`, if it is on a full-size keyboard.
+pub fn code_name(code: u16) -> Option<&'static str> {
+    CODE_NAMES
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, name)| *name)
+}
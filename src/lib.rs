@@ -1,2 +1,36 @@
+pub mod animation;
+pub mod async_client;
+pub mod audio;
+pub mod capture;
+pub mod chroma;
+pub mod color;
+pub mod commands;
+pub mod config;
+pub mod console;
+pub mod control;
+pub mod decode;
+pub mod descriptor;
+pub mod device;
+pub mod doctor;
+pub mod effect;
 pub mod emojis;
+pub mod emulator;
+pub mod frame;
+pub mod heatmap;
+pub mod http;
+pub mod idle;
+pub mod layout;
+pub mod logging;
+pub mod metrics;
+pub mod notify;
+pub mod openrgb;
+pub mod playback;
+pub mod profile;
 pub mod razer_report;
+pub mod schedule;
+pub mod script;
+pub mod sequence;
+pub mod transition;
+pub mod transport;
+pub mod udev;
+pub mod worker;
@@ -0,0 +1,241 @@
+//! Status messages as `tracing` events.
+//!
+//! Events carry an `icon` field holding one of the `emojis` constants, plus
+//! whatever structured fields apply (`device`, `command`, `status`,
+//! `latency_us` for USB exchanges). `init` prints them to stderr and, for the
+//! GUI, keeps the most recent ones in a `LogBuffer`. With
+//! `RAZER_RGB_LOG_STYLE=plain` icons become ASCII tags and lines get a level,
+//! which is what journald and log files want. `RAZER_RGB_LOG` sets the level
+//! (`error`, `warn`, `info`, `debug`, `trace`; default `info`).
+
+use crate::emojis::*;
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::io::Write as _;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+pub use tracing_subscriber::filter::LevelFilter;
+
+pub const LEVEL_ENV: &str = "RAZER_RGB_LOG";
+pub const STYLE_ENV: &str = "RAZER_RGB_LOG_STYLE";
+
+/// How many records a `LogBuffer` keeps unless told otherwise.
+pub const BUFFER_LEN: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStyle {
+    Emoji,
+    /// ASCII tags and level names, no emoji.
+    Plain,
+}
+
+impl LogStyle {
+    pub fn from_env() -> Self {
+        match std::env::var(STYLE_ENV) {
+            Ok(style) if style.eq_ignore_ascii_case("plain") => LogStyle::Plain,
+            _ => LogStyle::Emoji,
+        }
+    }
+}
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// `emoji`, or its ASCII tag once `init` chose the plain style. For output
+/// that isn't a log line but should look like one, like the doctor checklist.
+pub fn icon(emoji: &'static str) -> &'static str {
+    if PLAIN.load(Ordering::Relaxed) {
+        ascii_tag(emoji)
+    } else {
+        emoji
+    }
+}
+
+fn level_icon(level: Level) -> &'static str {
+    match level {
+        Level::ERROR => EMOJI_CROSS,
+        Level::WARN => EMOJI_WARNING,
+        Level::INFO => EMOJI_INFO,
+        Level::DEBUG | Level::TRACE => EMOJI_MAGNIFYING_GLASS,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Time since the subscriber was created.
+    pub elapsed: Duration,
+    pub level: Level,
+    pub icon: &'static str,
+    pub message: String,
+    /// Structured fields other than `message` and `icon`, in event order.
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl LogRecord {
+    /// The value of field `name`, if the event had one.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// One line, without a trailing newline.
+    pub fn format(&self, style: LogStyle) -> String {
+        let mut line = match style {
+            LogStyle::Emoji => format!("{} {}", self.icon, self.message),
+            LogStyle::Plain => format!(
+                "{:<5} {} {}",
+                self.level,
+                ascii_tag(self.icon),
+                self.message
+            ),
+        };
+        for (name, value) in &self.fields {
+            if value.is_empty() || value.contains(char::is_whitespace) {
+                let _ = write!(line, " {name}={value:?}");
+            } else {
+                let _ = write!(line, " {name}={value}");
+            }
+        }
+        line
+    }
+}
+
+/// The most recent records, shared between the subscriber and a viewer.
+#[derive(Debug, Clone)]
+pub struct LogBuffer {
+    records: Arc<Mutex<VecDeque<LogRecord>>>,
+    capacity: usize,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new(BUFFER_LEN)
+    }
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    fn push(&self, record: LogRecord) {
+        let mut records = self.records.lock().unwrap();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Oldest first.
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+}
+
+#[derive(Default)]
+struct RecordVisitor {
+    icon: Option<&'static str>,
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Visit for RecordVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            // Events name the constant, so map it back to get a 'static str.
+            "icon" => self.icon = icon_constant(value),
+            "message" => self.message = value.to_string(),
+            name => self.fields.push((name, value.to_string())),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{value:?}"),
+            name => self.fields.push((name, format!("{value:?}"))),
+        }
+    }
+}
+
+fn icon_constant(value: &str) -> Option<&'static str> {
+    ASCII_TAGS
+        .iter()
+        .find(|(emoji, _)| *emoji == value)
+        .map(|(emoji, _)| *emoji)
+}
+
+struct LogLayer {
+    started: Instant,
+    style: LogStyle,
+    console: bool,
+    buffer: Option<LogBuffer>,
+}
+
+impl<S: Subscriber> Layer<S> for LogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = RecordVisitor::default();
+        event.record(&mut visitor);
+        let level = *event.metadata().level();
+        let record = LogRecord {
+            elapsed: self.started.elapsed(),
+            level,
+            icon: visitor.icon.unwrap_or_else(|| level_icon(level)),
+            message: visitor.message,
+            fields: visitor.fields,
+        };
+        if self.console {
+            let _ = writeln!(std::io::stderr(), "{}", record.format(self.style));
+        }
+        if let Some(buffer) = &self.buffer {
+            buffer.push(record);
+        }
+    }
+}
+
+/// A subscriber printing to stderr when `console` is set and feeding
+/// `buffer` when there is one. `init` installs one; tests scope one with
+/// `tracing::subscriber::with_default`.
+pub fn subscriber(
+    level: LevelFilter,
+    style: LogStyle,
+    console: bool,
+    buffer: Option<LogBuffer>,
+) -> impl Subscriber + Send + Sync {
+    tracing_subscriber::registry().with(level).with(LogLayer {
+        started: Instant::now(),
+        style,
+        console,
+        buffer,
+    })
+}
+
+/// Level from `RAZER_RGB_LOG`, `info` when unset or unreadable.
+pub fn level_from_env() -> LevelFilter {
+    std::env::var(LEVEL_ENV)
+        .ok()
+        .and_then(|level| LevelFilter::from_str(&level).ok())
+        .unwrap_or(LevelFilter::INFO)
+}
+
+/// Installs the global subscriber, configured from the environment. Later
+/// calls do nothing.
+pub fn init(buffer: Option<LogBuffer>) {
+    let style = LogStyle::from_env();
+    PLAIN.store(style == LogStyle::Plain, Ordering::Relaxed);
+    let _ =
+        tracing::subscriber::set_global_default(subscriber(level_from_env(), style, true, buffer));
+}
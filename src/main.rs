@@ -1,34 +1,34 @@
 // Razer Ornata V3 RGB Control
 
 use eframe::egui;
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::config::config_dir;
+use razer_rgb_mac::console::{hex_dump, History, RawCommand};
+use razer_rgb_mac::descriptor::{DeviceDescriptor, ORNATA_V3};
+use razer_rgb_mac::device::{connect, LightingState};
+use razer_rgb_mac::doctor::{self, Status, UsbProbe};
+use razer_rgb_mac::effect::{Effect, DEFAULT_WAVE_DIRECTION, DEFAULT_WAVE_SPEED};
 use razer_rgb_mac::emojis::*;
+use razer_rgb_mac::layout::{Key, Layout};
+use razer_rgb_mac::logging::{self, LogBuffer, LogStyle};
+use razer_rgb_mac::profile::ProfileStore;
 use razer_rgb_mac::razer_report::RazerReport;
-use rusb::{Context, DeviceHandle, UsbContext};
-
-const RAZER_VENDOR_ID: u16 = 0x1532;
-const ORNATA_V3_PRODUCT_ID: u16 = 0x02A1;
-
-fn find_device() -> Option<DeviceHandle<Context>> {
-    let context = Context::new().ok()?;
-    for device in context.devices().ok()?.iter() {
-        let device_desc = device.device_descriptor().ok()?;
-        if device_desc.vendor_id() == RAZER_VENDOR_ID
-            && device_desc.product_id() == ORNATA_V3_PRODUCT_ID
-        {
-            println!(
-                "{EMOJI_TARGET} Found keyboard (Vendor ID: 0x{:04X}, Product ID: 0x{:04X})",
-                device_desc.vendor_id(),
-                device_desc.product_id()
-            );
-            return device.open().ok();
-        }
-    }
-    None
-}
+use razer_rgb_mac::schedule::{
+    self, Location, Rule, Schedule, Scheduler, SystemClock, When, SCHEDULE_FILE,
+};
+use razer_rgb_mac::sequence::{Keyframe, Sequence, SequenceStore, Trigger};
+use razer_rgb_mac::transition::{Easing, Target, Transition};
+use razer_rgb_mac::transport::TransportError;
+use razer_rgb_mac::worker::{Command, Event, Worker};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::Level;
 
 fn main() -> Result<(), eframe::Error> {
-    println!("{EMOJI_LAMP} Razer RGB Control");
-    println!("========================================================");
+    let log = LogBuffer::default();
+    logging::init(Some(log.clone()));
+    tracing::info!(icon = EMOJI_LAMP, "Razer RGB Control");
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size(egui::vec2(500.0, 600.0)),
@@ -37,88 +37,1048 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Razer RGB MacOS",
         options,
-        Box::new(|_cc| Ok(Box::new(RazerRGBMac::default()))),
+        Box::new(|cc| Ok(Box::new(RazerRGBMac::new(&cc.egui_ctx, log)))),
     )
 }
 
+/// How long a toast stays on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+
+struct Toast {
+    text: String,
+    success: bool,
+    shown_at: Instant,
+}
+
+/// Developer panel for sending hand-built reports.
+#[derive(Default)]
+struct Console {
+    class: String,
+    id: String,
+    data_size: String,
+    transaction_id: String,
+    arguments: String,
+    error: Option<String>,
+    history: History,
+    /// The last raw exchange, once the worker has answered it.
+    last: Option<(RazerReport, Result<RazerReport, TransportError>, Duration)>,
+}
+
+impl Console {
+    /// Fills the fields from a history entry.
+    fn load(&mut self, command: &RawCommand) {
+        self.class = format!("{:02X}", command.command_class);
+        self.id = format!("{:02X}", command.command_id);
+        self.data_size = format!("{:02X}", command.data_size);
+        self.transaction_id = format!("{:02X}", command.transaction_id);
+        let args: Vec<String> = command
+            .arguments
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect();
+        self.arguments = args.join(" ");
+    }
+}
+
+/// The schedule window works on a copy until it is saved.
+struct ScheduleEditor {
+    schedule: Schedule,
+    /// Profile names to pick from, read when the window opens.
+    profiles: Vec<String>,
+    error: Option<String>,
+}
+
+/// The sequence window works on a copy until it is saved or played.
+struct SequenceEditor {
+    sequence: Sequence,
+    /// Saved sequence names to load, read when the window opens.
+    saved: Vec<String>,
+    /// Where on the timeline the preview is.
+    playhead: Duration,
+    /// When the preview started playing, and from where.
+    previewing: Option<(Instant, Duration)>,
+    error: Option<String>,
+}
+
+impl SequenceEditor {
+    fn new(sequence: Sequence) -> Self {
+        Self {
+            sequence,
+            saved: SequenceStore::default().list().unwrap_or_default(),
+            playhead: Duration::ZERO,
+            previewing: None,
+            error: None,
+        }
+    }
+
+    /// Puts keyframes and triggers in time order and checks the result.
+    fn tidy(&mut self) -> Result<(), String> {
+        let sequence = &mut self.sequence;
+        sequence.keyframes.sort_by_key(|keyframe| keyframe.at);
+        sequence.triggers.sort_by_key(|trigger| trigger.at);
+        sequence.validate()
+    }
+}
+
 struct RazerRGBMac {
-    device_handle: Option<DeviceHandle<Context>>,
+    worker: Option<Worker>,
+    /// The connected keyboard, or the Ornata V3 without one.
+    descriptor: DeviceDescriptor,
+    /// Where the keys are, for drawing the keyboard.
+    layout: Layout,
+    device_name: String,
     device_status: String,
+    /// Commands submitted but not yet answered by the worker.
+    pending: usize,
+    toasts: Vec<Toast>,
     show_about: bool,
+    show_console: bool,
+    console: Console,
+    show_doctor: bool,
+    doctor_report: Option<doctor::Report>,
+    /// Set while a diagnostics run is in progress.
+    doctor_running: Option<Receiver<doctor::Report>>,
+    log: LogBuffer,
+    show_log: bool,
+    /// Most verbose level the log window shows.
+    log_level: Level,
+    /// How effect buttons switch; a zero duration switches at once.
+    transition: Transition,
+    scheduler: Scheduler,
+    schedule_editor: Option<ScheduleEditor>,
+    sequence_editor: Option<SequenceEditor>,
 }
 
-impl Default for RazerRGBMac {
-    fn default() -> Self {
-        let (handle, status) = match find_device() {
-            Some(handle) => (Some(handle), "Razer Ornata v3".to_string()),
-            None => (None, "No device found".to_string()),
+impl RazerRGBMac {
+    fn new(ctx: &egui::Context, log: LogBuffer) -> Self {
+        let mut descriptor = ORNATA_V3;
+        let (worker, status) = match connect() {
+            Ok(device) => {
+                let name = device.descriptor().name.to_string();
+                descriptor = *device.descriptor();
+                let device = Arc::new(Mutex::new(device));
+                let ctx = ctx.clone();
+                (
+                    Some(Worker::spawn(device, move || ctx.request_repaint())),
+                    name,
+                )
+            }
+            Err(err) => {
+                tracing::error!(icon = EMOJI_CROSS, "{err}");
+                (None, err.to_string())
+            }
         };
 
         Self {
-            device_handle: handle,
+            worker,
+            descriptor,
+            layout: Layout::for_device(&descriptor),
+            device_name: status.clone(),
             device_status: status,
+            pending: 0,
+            toasts: Vec::new(),
             show_about: false,
+            show_console: false,
+            console: Console::default(),
+            show_doctor: false,
+            doctor_report: None,
+            doctor_running: None,
+            log,
+            show_log: false,
+            log_level: Level::INFO,
+            transition: Transition::default(),
+            scheduler: Scheduler::new(load_schedule(), SystemClock),
+            schedule_editor: None,
+            sequence_editor: None,
         }
     }
-}
 
-impl RazerRGBMac {
-    fn static_color(&mut self, r: u8, g: u8, b: u8) {
-        if let Some(ref mut handle) = self.device_handle {
-            let cmd = RazerReport::static_rgb(r, g, b);
-            if cmd.send(handle) {
-                println!("   {EMOJI_CHECK} Color set successfully.");
+    fn apply(&mut self, effect: Effect) {
+        if let Some(ref worker) = self.worker {
+            tracing::info!(icon = EMOJI_PAINT, "Setting {}", effect.name());
+            if self.transition.duration.is_zero() {
+                worker.submit(Command::SetEffect(effect));
             } else {
-                println!("   {EMOJI_CROSS} Failed to set color.");
+                worker.submit(Command::Fade(Target::effect(effect), self.transition));
             }
-            println!("{EMOJI_SUCCESS} RGB Control Done!");
+            self.pending += 1;
         } else {
-            println!("{EMOJI_WRONG_WAY} No device connected.");
+            tracing::warn!(icon = EMOJI_WRONG_WAY, "No device connected");
+        }
+    }
+
+    /// Switches profiles when the schedule says so.
+    fn poll_schedule(&mut self, ctx: &egui::Context) {
+        if self.scheduler.schedule().rules.is_empty() {
+            return;
+        }
+        ctx.request_repaint_after(schedule::POLL_INTERVAL);
+        // Without a keyboard the change waits until there is one.
+        let Some(ref worker) = self.worker else {
+            return;
+        };
+        let Some(name) = self.scheduler.poll() else {
+            return;
+        };
+        match ProfileStore::default().load(&name) {
+            Ok(profile) => {
+                tracing::info!(icon = EMOJI_STOPWATCH, "Schedule switched to \"{name}\"");
+                let transition = profile.transition.unwrap_or_default();
+                worker.submit(Command::Fade(profile.target(), transition));
+                self.pending += 1;
+            }
+            Err(err) => {
+                tracing::warn!(
+                    icon = EMOJI_WARNING,
+                    "Schedule could not load \"{name}\": {err}"
+                )
+            }
         }
     }
 
-    fn breathing(&mut self, r: u8, g: u8, b: u8) {
-        if let Some(ref mut handle) = self.device_handle {
-            println!("{EMOJI_PAINT} Setting breathing");
+    fn send_raw(&mut self, command: RawCommand) {
+        let Some(ref worker) = self.worker else {
+            self.console.error = Some("No device connected".to_string());
+            return;
+        };
+        let report = command.report();
+        tracing::info!(icon = EMOJI_WRENCH, "Sending raw {report}");
+        worker.submit(Command::Raw(report));
+        self.console.history.push(command);
+        self.console.error = None;
+        self.pending += 1;
+    }
 
-            let cmd = RazerReport::breathing(r, g, b);
-            if cmd.send(handle) {
-                println!("   {EMOJI_CHECK} Color set successfully.");
-            } else {
-                println!("   {EMOJI_CROSS} Failed to set color.");
+    /// Turns worker events into status text and toasts.
+    fn poll_events(&mut self) {
+        let Some(ref worker) = self.worker else {
+            return;
+        };
+        for event in worker.events() {
+            if let Event::Triggered { sequence, trigger } = &event {
+                tracing::info!(icon = EMOJI_BELL, "{sequence} reached \"{trigger}\"");
+                continue;
             }
-        } else {
-            println!("{EMOJI_WRONG_WAY} No device connected.");
+            self.pending = self.pending.saturating_sub(1);
+            match event {
+                Event::Applied { command, .. } => {
+                    tracing::info!(icon = EMOJI_CHECK, "{command} applied");
+                    self.device_status = format!("{} - {command}", self.device_name);
+                    self.toasts.push(Toast {
+                        text: format!("{EMOJI_CHECK} {command} applied"),
+                        success: true,
+                        shown_at: Instant::now(),
+                    });
+                }
+                Event::Failed { command, error } => {
+                    tracing::error!(icon = EMOJI_CROSS, "{command} failed: {error}");
+                    self.toasts.push(Toast {
+                        text: format!("{EMOJI_CROSS} {command} failed: {error}"),
+                        success: false,
+                        shown_at: Instant::now(),
+                    });
+                }
+                Event::Exchanged {
+                    request,
+                    response,
+                    latency,
+                } => {
+                    self.console.last = Some((request, response, latency));
+                }
+                Event::Superseded { .. } | Event::Triggered { .. } => {}
+            }
+        }
+    }
+
+    fn render_toasts(&mut self, ctx: &egui::Context) {
+        self.toasts
+            .retain(|toast| toast.shown_at.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() {
+            return;
         }
+
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+            .show(ctx, |ui| {
+                for toast in &self.toasts {
+                    let fill = if toast.success {
+                        egui::Color32::from_rgb(30, 80, 40)
+                    } else {
+                        egui::Color32::from_rgb(100, 30, 30)
+                    };
+                    egui::Frame::new()
+                        .fill(fill)
+                        .corner_radius(6.0)
+                        .inner_margin(10.0)
+                        .show(ui, |ui| {
+                            ui.label(egui::RichText::new(&toast.text).color(egui::Color32::WHITE));
+                        });
+                    ui.add_space(5.0);
+                }
+            });
+        // Wake up again to fade out the oldest toast.
+        ctx.request_repaint_after(Duration::from_millis(250));
     }
 
-    fn spectrum(&mut self) {
-        if let Some(ref mut handle) = self.device_handle {
-            println!("{EMOJI_PAINT} Setting spectrum");
+    fn render_console(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_console;
+        let mut to_send = None;
+        let default_id = self.descriptor.transaction_id;
+        egui::Window::new(format!("{EMOJI_WRENCH} Raw command console"))
+            .open(&mut open)
+            .default_width(460.0)
+            .show(ctx, |ui| {
+                let console = &mut self.console;
+                egui::Grid::new("console_fields")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Class");
+                        ui.text_edit_singleline(&mut console.class);
+                        ui.end_row();
+                        ui.label("ID");
+                        ui.text_edit_singleline(&mut console.id);
+                        ui.end_row();
+                        ui.label("Data size");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut console.data_size)
+                                .hint_text("number of arguments"),
+                        );
+                        ui.end_row();
+                        ui.label("Transaction ID");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut console.transaction_id)
+                                .hint_text(format!("{default_id:02X}")),
+                        );
+                        ui.end_row();
+                        ui.label("Arguments");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut console.arguments)
+                                .hint_text("01 05 02 ..."),
+                        );
+                        ui.end_row();
+                    });
+                ui.label(
+                    egui::RichText::new("All values in hex. The CRC is filled in for you.")
+                        .color(egui::Color32::GRAY),
+                );
+
+                if ui.button(format!("{EMOJI_BOLT} Send")).clicked() {
+                    match RawCommand::parse(
+                        &console.class,
+                        &console.id,
+                        &console.data_size,
+                        &console.transaction_id,
+                        &console.arguments,
+                        default_id,
+                    ) {
+                        Ok(command) => to_send = Some(command),
+                        Err(err) => console.error = Some(err),
+                    }
+                }
+                if let Some(err) = &console.error {
+                    ui.colored_label(egui::Color32::from_rgb(255, 80, 80), err);
+                }
 
-            let cmd = RazerReport::spectrum();
-            if cmd.send(handle) {
-                println!("   {EMOJI_CHECK} Color set successfully.");
-            } else {
-                println!("   {EMOJI_CROSS} Failed to set color.");
+                if let Some((request, response, latency)) = &console.last {
+                    ui.separator();
+                    ui.label(format!("Sent: {request}"));
+                    match response {
+                        Ok(response) => {
+                            ui.label(format!(
+                                "Received after {}ms: {response}",
+                                latency.as_millis()
+                            ));
+                            ui.label(
+                                egui::RichText::new(hex_dump(&response.to_bytes())).monospace(),
+                            );
+                        }
+                        Err(err) => {
+                            ui.colored_label(egui::Color32::from_rgb(255, 80, 80), err.to_string());
+                        }
+                    }
+                }
+
+                if !console.history.is_empty() {
+                    ui.separator();
+                    ui.label("History");
+                    let mut load = None;
+                    egui::ScrollArea::vertical()
+                        .max_height(150.0)
+                        .show(ui, |ui| {
+                            for command in console.history.iter() {
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("Resend").clicked() {
+                                        to_send = Some(command.clone());
+                                    }
+                                    if ui.small_button("Edit").clicked() {
+                                        load = Some(command.clone());
+                                    }
+                                    ui.label(egui::RichText::new(command.label()).monospace());
+                                });
+                            }
+                        });
+                    if let Some(command) = load {
+                        console.load(&command);
+                    }
+                }
+            });
+        self.show_console = open;
+        if let Some(command) = to_send {
+            self.send_raw(command);
+        }
+    }
+
+    /// Runs the checks off the UI thread; they sleep between USB transfers.
+    fn run_doctor(&mut self, ctx: &egui::Context) {
+        tracing::info!(icon = EMOJI_MAGNIFYING_GLASS, "Running diagnostics");
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(doctor::run(&mut UsbProbe::new()));
+            ctx.request_repaint();
+        });
+        self.doctor_report = None;
+        self.doctor_running = Some(rx);
+    }
+
+    fn render_doctor(&mut self, ctx: &egui::Context) {
+        if let Some(report) = self
+            .doctor_running
+            .as_ref()
+            .and_then(|rx| rx.try_recv().ok())
+        {
+            for check in &report.checks {
+                match check.status {
+                    Status::Fail => tracing::warn!(icon = EMOJI_CROSS, "{}", check.label),
+                    _ => tracing::info!(icon = EMOJI_CHECK, "{}", check.label),
+                }
             }
-        } else {
-            println!("{EMOJI_WRONG_WAY} No device connected.");
+            self.doctor_report = Some(report);
+            self.doctor_running = None;
+        }
+
+        let mut open = self.show_doctor;
+        let mut run = false;
+        egui::Window::new("Diagnostics")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                if self.worker.is_some() {
+                    // The checks open the keyboard themselves, and on Linux
+                    // our own claim would show up as "busy".
+                    ui.label(format!(
+                        "{EMOJI_CHECK} Connected to {}. Diagnostics run when no keyboard is connected.",
+                        self.device_name
+                    ));
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    let idle = self.doctor_running.is_none();
+                    if ui.add_enabled(idle, egui::Button::new("Run checks")).clicked() {
+                        run = true;
+                    }
+                    if !idle {
+                        ui.spinner();
+                    }
+                });
+                let Some(report) = &self.doctor_report else {
+                    return;
+                };
+                ui.separator();
+                for check in &report.checks {
+                    let (mark, color) = match check.status {
+                        Status::Pass => (EMOJI_CHECK, egui::Color32::from_rgb(0, 255, 100)),
+                        Status::Warn => (EMOJI_WARNING, egui::Color32::from_rgb(255, 200, 0)),
+                        Status::Fail => (EMOJI_CROSS, egui::Color32::from_rgb(255, 80, 80)),
+                    };
+                    ui.label(egui::RichText::new(format!("{mark} {}", check.label)).color(color));
+                    if let Some(fix) = &check.fix {
+                        ui.label(format!("    {EMOJI_WRENCH} {fix}"));
+                    }
+                }
+            });
+        self.show_doctor = open;
+        if run {
+            self.run_doctor(ctx);
+        }
+    }
+
+    fn render_log(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_log;
+        egui::Window::new("Log")
+            .open(&mut open)
+            .default_size(egui::vec2(560.0, 320.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Level")
+                        .selected_text(self.log_level.as_str())
+                        .show_ui(ui, |ui| {
+                            for level in [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG] {
+                                ui.selectable_value(&mut self.log_level, level, level.as_str());
+                            }
+                        });
+                    if ui.button("Clear").clicked() {
+                        self.log.clear();
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        // More verbose levels compare greater.
+                        for record in self.log.records() {
+                            if record.level > self.log_level {
+                                continue;
+                            }
+                            let color = match record.level {
+                                Level::ERROR => egui::Color32::from_rgb(255, 80, 80),
+                                Level::WARN => egui::Color32::from_rgb(255, 200, 0),
+                                Level::INFO => egui::Color32::WHITE,
+                                _ => egui::Color32::GRAY,
+                            };
+                            let line = format!(
+                                "{:>8.3}s {}",
+                                record.elapsed.as_secs_f64(),
+                                record.format(LogStyle::Emoji)
+                            );
+                            ui.label(egui::RichText::new(line).monospace().color(color));
+                        }
+                    });
+            });
+        self.show_log = open;
+        if open {
+            // Events from server and worker threads don't wake the UI.
+            ctx.request_repaint_after(Duration::from_millis(250));
         }
     }
 
-    fn wave(&mut self, direction: u8, speed: u8) {
-        if let Some(ref mut handle) = self.device_handle {
-            println!("{EMOJI_PAINT} Setting wave");
+    fn render_schedule(&mut self, ctx: &egui::Context) {
+        let Some(editor) = &mut self.schedule_editor else {
+            return;
+        };
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new(format!("{EMOJI_STOPWATCH} Schedule"))
+            .open(&mut open)
+            .default_width(460.0)
+            .show(ctx, |ui| {
+                let schedule = &mut editor.schedule;
+                let mut located = schedule.location.is_some();
+                ui.checkbox(&mut located, "Location (for sunrise and sunset)");
+                match (located, &mut schedule.location) {
+                    (true, None) => {
+                        schedule.location = Some(Location {
+                            latitude: 0.0,
+                            longitude: 0.0,
+                        })
+                    }
+                    (false, Some(_)) => schedule.location = None,
+                    _ => {}
+                }
+                if let Some(location) = &mut schedule.location {
+                    ui.horizontal(|ui| {
+                        ui.label("Latitude");
+                        ui.add(
+                            egui::DragValue::new(&mut location.latitude)
+                                .range(-90.0..=90.0)
+                                .speed(0.1),
+                        );
+                        ui.label("Longitude");
+                        ui.add(
+                            egui::DragValue::new(&mut location.longitude)
+                                .range(-180.0..=180.0)
+                                .speed(0.1),
+                        );
+                    });
+                }
+                ui.separator();
 
-            let cmd = RazerReport::wave(direction, speed);
-            if cmd.send(handle) {
-                println!("   {EMOJI_CHECK} Color set successfully.");
-            } else {
-                println!("   {EMOJI_CROSS} Failed to set color.");
+                let mut remove = None;
+                egui::Grid::new("schedule_rules")
+                    .num_columns(4)
+                    .show(ui, |ui| {
+                        for (i, rule) in schedule.rules.iter_mut().enumerate() {
+                            egui::ComboBox::from_id_salt(("when", i))
+                                .selected_text(match rule.when {
+                                    When::At(_) => "At",
+                                    When::Sunrise(_) => "Sunrise",
+                                    When::Sunset(_) => "Sunset",
+                                })
+                                .show_ui(ui, |ui| {
+                                    let evening = chrono::NaiveTime::from_hms_opt(19, 0, 0)
+                                        .expect("19:00 is a valid time");
+                                    for (label, when) in [
+                                        ("At", When::At(evening)),
+                                        ("Sunrise", When::Sunrise(0)),
+                                        ("Sunset", When::Sunset(0)),
+                                    ] {
+                                        let selected = std::mem::discriminant(&rule.when)
+                                            == std::mem::discriminant(&when);
+                                        if ui.selectable_label(selected, label).clicked()
+                                            && !selected
+                                        {
+                                            rule.when = when;
+                                        }
+                                    }
+                                });
+                            ui.horizontal(|ui| match &mut rule.when {
+                                When::At(time) => {
+                                    use chrono::Timelike;
+                                    let (mut hour, mut minute) = (time.hour(), time.minute());
+                                    ui.add(egui::DragValue::new(&mut hour).range(0..=23));
+                                    ui.label(":");
+                                    ui.add(egui::DragValue::new(&mut minute).range(0..=59));
+                                    if let Some(changed) =
+                                        chrono::NaiveTime::from_hms_opt(hour, minute, 0)
+                                    {
+                                        *time = changed;
+                                    }
+                                }
+                                When::Sunrise(minutes) | When::Sunset(minutes) => {
+                                    ui.add(
+                                        egui::DragValue::new(minutes)
+                                            .range(-720..=720)
+                                            .suffix(" min"),
+                                    );
+                                }
+                            });
+                            egui::ComboBox::from_id_salt(("profile", i))
+                                .selected_text(rule.profile.as_str())
+                                .show_ui(ui, |ui| {
+                                    for name in &editor.profiles {
+                                        ui.selectable_value(&mut rule.profile, name.clone(), name);
+                                    }
+                                });
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::DragValue::new(&mut rule.priority).prefix("priority "),
+                                );
+                                if ui.small_button("Remove").clicked() {
+                                    remove = Some(i);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+                if let Some(i) = remove {
+                    schedule.rules.remove(i);
+                }
+                if editor.profiles.is_empty() {
+                    ui.label("Save a profile first; rules switch between profiles.");
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Add rule").clicked() {
+                        let profile = editor.profiles.first().cloned().unwrap_or_default();
+                        let evening = chrono::NaiveTime::from_hms_opt(19, 0, 0)
+                            .expect("19:00 is a valid time");
+                        schedule.rules.push(Rule::new(When::At(evening), &profile));
+                    }
+                    if ui.button("Save").clicked() {
+                        save = true;
+                    }
+                });
+                if let Some(err) = &editor.error {
+                    ui.colored_label(egui::Color32::from_rgb(255, 80, 80), err);
+                }
+
+                ui.separator();
+                ui.label("Upcoming");
+                match schedule.validate() {
+                    Ok(()) => {
+                        let upcoming = schedule.upcoming(self.scheduler.now(), 8);
+                        if upcoming.is_empty() {
+                            ui.label(
+                                egui::RichText::new("Nothing scheduled").color(egui::Color32::GRAY),
+                            );
+                        }
+                        for change in upcoming {
+                            ui.label(format!(
+                                "{}  {}  ({})",
+                                change.at.format("%a %H:%M"),
+                                change.profile,
+                                schedule.rules[change.rule].when
+                            ));
+                        }
+                    }
+                    Err(err) => {
+                        ui.label(egui::RichText::new(err).color(egui::Color32::GRAY));
+                    }
+                }
+            });
+
+        if save {
+            let path = config_dir().join(SCHEDULE_FILE);
+            match editor.schedule.save(&path) {
+                Ok(()) => {
+                    tracing::info!(icon = EMOJI_FILE, "Saved {}", path.display());
+                    self.scheduler.set_schedule(editor.schedule.clone());
+                    editor.error = None;
+                }
+                Err(err) => editor.error = Some(err.to_string()),
+            }
+        }
+        if !open {
+            self.schedule_editor = None;
+        }
+    }
+
+    fn render_sequences(&mut self, ctx: &egui::Context) {
+        let Some(editor) = &mut self.sequence_editor else {
+            return;
+        };
+        if let Some((started, from)) = editor.previewing {
+            editor.playhead = from + started.elapsed();
+            if !editor.sequence.looping && editor.playhead >= editor.sequence.length() {
+                editor.playhead = editor.sequence.length();
+                editor.previewing = None;
+            }
+            ctx.request_repaint();
+        }
+        let mut open = true;
+        let mut save = false;
+        let mut load = None;
+        let mut play = false;
+        egui::Window::new(format!("{EMOJI_STOPWATCH} Sequences"))
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut editor.sequence.name);
+                    egui::ComboBox::from_id_salt("sequence_load")
+                        .selected_text("Load")
+                        .show_ui(ui, |ui| {
+                            for name in &editor.saved {
+                                if ui.selectable_label(false, name).clicked() {
+                                    load = Some(name.clone());
+                                }
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    let sequence = &mut editor.sequence;
+                    ui.checkbox(&mut sequence.looping, "Loop");
+                    let mut fixed = sequence.length.is_some();
+                    ui.checkbox(&mut fixed, "Length");
+                    match (fixed, sequence.length) {
+                        (true, None) => sequence.length = Some(sequence.length()),
+                        (false, Some(_)) => sequence.length = None,
+                        _ => {}
+                    }
+                    let mut millis = sequence.length().as_millis() as u64;
+                    if ui
+                        .add_enabled(
+                            fixed,
+                            egui::DragValue::new(&mut millis).speed(10).suffix(" ms"),
+                        )
+                        .changed()
+                    {
+                        sequence.length = Some(Duration::from_millis(millis));
+                    }
+                });
+
+                Self::render_timeline(ui, &editor.sequence, &mut editor.playhead);
+                // The preview starts from dark keys; on the keyboard a
+                // sequence starts from whatever the keys show.
+                let base = LightingState {
+                    effect: Effect::Off,
+                    ..LightingState::default()
+                };
+                let state = editor.sequence.state_at(editor.playhead, &base);
+                Self::paint_keyboard(ui, &self.layout, &state, editor.playhead);
+                ui.horizontal(|ui| {
+                    let label = if editor.previewing.is_some() {
+                        "Stop"
+                    } else {
+                        "Preview"
+                    };
+                    if ui.button(label).clicked() {
+                        editor.previewing = match editor.previewing {
+                            Some(_) => None,
+                            None if editor.playhead >= editor.sequence.length() => {
+                                Some((Instant::now(), Duration::ZERO))
+                            }
+                            None => Some((Instant::now(), editor.playhead)),
+                        };
+                    }
+                    ui.label(format!(
+                        "{:.2} s of {:.2} s",
+                        editor.playhead.as_secs_f32(),
+                        editor.sequence.length().as_secs_f32()
+                    ));
+                });
+                ui.separator();
+
+                let mut remove = None;
+                egui::Grid::new("sequence_keyframes")
+                    .num_columns(5)
+                    .show(ui, |ui| {
+                        for (i, keyframe) in editor.sequence.keyframes.iter_mut().enumerate() {
+                            let mut millis = keyframe.at.as_millis() as u64;
+                            if ui
+                                .add(egui::DragValue::new(&mut millis).speed(10).suffix(" ms"))
+                                .changed()
+                            {
+                                keyframe.at = Duration::from_millis(millis);
+                            }
+                            ui.horizontal(|ui| Self::effect_picker(ui, i, &mut keyframe.effect));
+                            ui.horizontal(|ui| {
+                                let mut set = keyframe.brightness.is_some();
+                                ui.checkbox(&mut set, "Brightness");
+                                let mut brightness = keyframe.brightness.unwrap_or(255);
+                                ui.add_enabled(
+                                    set,
+                                    egui::DragValue::new(&mut brightness).range(0..=255),
+                                );
+                                keyframe.brightness = set.then_some(brightness);
+                            });
+                            egui::ComboBox::from_id_salt(("easing", i))
+                                .selected_text(keyframe.easing.map_or("jump", Easing::name))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut keyframe.easing, None, "jump");
+                                    for easing in Easing::ALL {
+                                        ui.selectable_value(
+                                            &mut keyframe.easing,
+                                            Some(easing),
+                                            easing.name(),
+                                        );
+                                    }
+                                });
+                            if ui.small_button("Remove").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(i) = remove {
+                    editor.sequence.keyframes.remove(i);
+                }
+
+                let mut remove = None;
+                egui::Grid::new("sequence_triggers")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for (i, trigger) in editor.sequence.triggers.iter_mut().enumerate() {
+                            let mut millis = trigger.at.as_millis() as u64;
+                            if ui
+                                .add(egui::DragValue::new(&mut millis).speed(10).suffix(" ms"))
+                                .changed()
+                            {
+                                trigger.at = Duration::from_millis(millis);
+                            }
+                            ui.text_edit_singleline(&mut trigger.name);
+                            if ui.small_button("Remove").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(i) = remove {
+                    editor.sequence.triggers.remove(i);
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Add keyframe").clicked() {
+                        // Start from what the keys show there, so adding
+                        // one changes nothing until it is edited.
+                        let mut keyframe = Keyframe::new(editor.playhead, state.effect);
+                        keyframe.frame = (state.effect == Effect::Custom).then_some(state.frame);
+                        editor.sequence.keyframes.push(keyframe);
+                        editor
+                            .sequence
+                            .keyframes
+                            .sort_by_key(|keyframe| keyframe.at);
+                    }
+                    if ui.button("Add trigger").clicked() {
+                        let name = format!("trigger {}", editor.sequence.triggers.len() + 1);
+                        editor.sequence.triggers.push(Trigger {
+                            at: editor.playhead,
+                            name,
+                        });
+                    }
+                    if ui.button("Save").clicked() {
+                        save = true;
+                    }
+                    if ui.button("Play on keyboard").clicked() {
+                        play = true;
+                    }
+                });
+                if let Some(err) = &editor.error {
+                    ui.colored_label(egui::Color32::from_rgb(255, 80, 80), err);
+                }
+            });
+
+        if let Some(name) = load {
+            match SequenceStore::default().load(&name) {
+                Ok(sequence) => {
+                    editor.sequence = sequence;
+                    editor.playhead = Duration::ZERO;
+                    editor.previewing = None;
+                    editor.error = None;
+                }
+                Err(err) => editor.error = Some(format!("Cannot load \"{name}\": {err}")),
             }
-        } else {
-            println!("{EMOJI_WRONG_WAY} No device connected.");
+        }
+        if save {
+            let store = SequenceStore::default();
+            match editor
+                .tidy()
+                .and_then(|()| store.save(&editor.sequence).map_err(|err| err.to_string()))
+            {
+                Ok(()) => {
+                    tracing::info!(
+                        icon = EMOJI_FILE,
+                        "Saved sequence \"{}\"",
+                        editor.sequence.name
+                    );
+                    editor.saved = store.list().unwrap_or_default();
+                    editor.error = None;
+                }
+                Err(err) => editor.error = Some(err),
+            }
+        }
+        if play {
+            match (editor.tidy(), &self.worker) {
+                (Err(err), _) => editor.error = Some(err),
+                (Ok(()), None) => editor.error = Some("No device connected".to_string()),
+                (Ok(()), Some(worker)) => {
+                    tracing::info!(
+                        icon = EMOJI_PAINT,
+                        "Playing sequence \"{}\"",
+                        editor.sequence.name
+                    );
+                    worker.submit(Command::Play(editor.sequence.clone()));
+                    self.pending += 1;
+                    editor.error = None;
+                }
+            }
+        }
+        if !open {
+            self.sequence_editor = None;
+        }
+    }
+
+    /// A bar with a marker per keyframe and trigger; click or drag to move
+    /// the playhead.
+    fn render_timeline(ui: &mut egui::Ui, sequence: &Sequence, playhead: &mut Duration) {
+        let size = egui::vec2(ui.available_width(), 44.0);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        let length = sequence.length().max(Duration::from_millis(1));
+        let x = |at: Duration| {
+            rect.left() + rect.width() * (at.as_secs_f32() / length.as_secs_f32()).min(1.0)
+        };
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let t = ((pointer.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+            *playhead = length.mul_f32(t);
+        }
+
+        let painter = ui.painter();
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(rect.left(), rect.center().y - 4.0),
+            egui::pos2(rect.right(), rect.center().y + 4.0),
+        );
+        painter.rect_filled(bar, 4.0, egui::Color32::from_gray(50));
+        let mut previous = rect.left();
+        for keyframe in &sequence.keyframes {
+            let at = x(keyframe.at);
+            if keyframe.easing.is_some() {
+                // Eased stretches light up the bar.
+                let span = egui::Rect::from_min_max(
+                    egui::pos2(previous, bar.top()),
+                    egui::pos2(at, bar.bottom()),
+                );
+                painter.rect_filled(span, 4.0, egui::Color32::from_rgb(70, 70, 120));
+            }
+            let color = match keyframe.effect {
+                Effect::Static(color) | Effect::Breathing(color) => color,
+                Effect::Off => Rgb::new(60, 60, 60),
+                _ => Rgb::WHITE,
+            };
+            painter.circle(
+                egui::pos2(at, rect.center().y),
+                6.0,
+                egui::Color32::from_rgb(color.r, color.g, color.b),
+                egui::Stroke::new(1.0, egui::Color32::WHITE),
+            );
+            previous = at;
+        }
+        for trigger in &sequence.triggers {
+            let at = x(trigger.at);
+            let yellow = egui::Color32::from_rgb(255, 200, 0);
+            painter.line_segment(
+                [
+                    egui::pos2(at, bar.top() - 6.0),
+                    egui::pos2(at, bar.bottom() + 6.0),
+                ],
+                egui::Stroke::new(2.0, yellow),
+            );
+            painter.text(
+                egui::pos2(at, rect.top()),
+                egui::Align2::CENTER_TOP,
+                &trigger.name,
+                egui::FontId::proportional(10.0),
+                yellow,
+            );
+        }
+        let at = x(*playhead);
+        painter.line_segment(
+            [egui::pos2(at, rect.top()), egui::pos2(at, rect.bottom())],
+            egui::Stroke::new(2.0, egui::Color32::from_rgb(0, 255, 100)),
+        );
+    }
+
+    /// Picks an effect and, for those that have one, its color.
+    fn effect_picker(ui: &mut egui::Ui, id: usize, effect: &mut Effect) {
+        let color = match *effect {
+            Effect::Static(color) | Effect::Breathing(color) => color,
+            _ => Rgb::WHITE,
+        };
+        egui::ComboBox::from_id_salt(("effect", id))
+            .selected_text(effect.name())
+            .show_ui(ui, |ui| {
+                for choice in [
+                    Effect::Off,
+                    Effect::Static(color),
+                    Effect::Breathing(color),
+                    Effect::Spectrum,
+                    Effect::Wave {
+                        direction: DEFAULT_WAVE_DIRECTION,
+                        speed: DEFAULT_WAVE_SPEED,
+                    },
+                    Effect::Custom,
+                ] {
+                    let selected = effect.name() == choice.name();
+                    if ui.selectable_label(selected, choice.name()).clicked() && !selected {
+                        *effect = choice;
+                    }
+                }
+            });
+        if let Effect::Static(color) | Effect::Breathing(color) = effect {
+            let mut rgb = [color.r, color.g, color.b];
+            if ui.color_edit_button_srgb(&mut rgb).changed() {
+                *color = Rgb::new(rgb[0], rgb[1], rgb[2]);
+            }
+        }
+    }
+
+    /// Draws the keys as `state` would light them `t` into playback.
+    fn paint_keyboard(ui: &mut egui::Ui, layout: &Layout, state: &LightingState, t: Duration) {
+        let (width, height) = layout.size();
+        let scale = ui.available_width() / width.max(1.0);
+        let (rect, _) =
+            ui.allocate_exact_size(egui::vec2(width, height) * scale, egui::Sense::hover());
+        let painter = ui.painter();
+        for key in &layout.keys {
+            let min = rect.min + egui::vec2(key.x, key.y) * scale;
+            let cap = egui::Rect::from_min_size(min, egui::vec2(key.width, key.height) * scale)
+                .shrink(scale * 0.06);
+            let color = key_color(state, key, t.as_secs_f32());
+            painter.rect_filled(
+                cap,
+                scale * 0.12,
+                egui::Color32::from_rgb(color.r, color.g, color.b),
+            );
         }
     }
 
@@ -147,10 +1107,45 @@ impl eframe::App for RazerRGBMac {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Set a dark theme
         ctx.set_visuals(egui::Visuals::dark());
+        self.poll_events();
+        self.poll_schedule(ctx);
 
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
+                ui.menu_button("Developer", |ui| {
+                    if ui.button("Raw command console").clicked() {
+                        self.show_console = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Log").clicked() {
+                        self.show_log = true;
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("Tools", |ui| {
+                    if ui.button("Schedule").clicked() {
+                        self.schedule_editor = Some(ScheduleEditor {
+                            schedule: self.scheduler.schedule().clone(),
+                            profiles: ProfileStore::default().list().unwrap_or_default(),
+                            error: None,
+                        });
+                        ui.close_menu();
+                    }
+                    if ui.button("Sequences").clicked() {
+                        let start = Keyframe::new(Duration::ZERO, Effect::Off);
+                        let mut end =
+                            Keyframe::new(Duration::from_secs(2), Effect::Static(Rgb::WHITE));
+                        end.easing = Some(Easing::Linear);
+                        self.sequence_editor =
+                            Some(SequenceEditor::new(Sequence::new("new", vec![start, end])));
+                        ui.close_menu();
+                    }
+                });
                 ui.menu_button("Help", |ui| {
+                    if ui.button("Diagnostics").clicked() {
+                        self.show_doctor = true;
+                        ui.close_menu();
+                    }
                     if ui.button("About").clicked() {
                         self.show_about = true;
                     }
@@ -164,7 +1159,7 @@ impl eframe::App for RazerRGBMac {
             // Title
             ui.vertical_centered(|ui| {
                 ui.add_space(10.0);
-                let title = egui::RichText::new(&format!("{EMOJI_GAMEPAD} Razer RGB Control"))
+                let title = egui::RichText::new(format!("{EMOJI_GAMEPAD} Razer RGB Control"))
                     .size(28.0)
                     .color(egui::Color32::from_rgb(0, 255, 100));
                 ui.label(title);
@@ -189,7 +1184,7 @@ impl eframe::App for RazerRGBMac {
                         let (rect, _) =
                             ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::hover());
 
-                        let status_color = if self.device_handle.is_some() {
+                        let status_color = if self.worker.is_some() {
                             egui::Color32::from_rgb(0, 255, 100)
                         } else {
                             egui::Color32::from_rgb(255, 80, 80)
@@ -203,6 +1198,18 @@ impl eframe::App for RazerRGBMac {
                             .size(16.0)
                             .color(egui::Color32::WHITE);
                         ui.label(status_text);
+
+                        if self.worker.is_none() && ui.button("Diagnose").clicked() {
+                            self.show_doctor = true;
+                            if self.doctor_running.is_none() {
+                                self.run_doctor(ui.ctx());
+                            }
+                        }
+
+                        if self.pending > 0 {
+                            ui.add_space(10.0);
+                            ui.spinner();
+                        }
                     });
                 });
 
@@ -241,13 +1248,13 @@ impl eframe::App for RazerRGBMac {
                 });
 
             if green_clicked {
-                self.static_color(0, 255, 0);
+                self.apply(Effect::Static(Rgb::GREEN));
             }
             if blue_clicked {
-                self.static_color(0, 0, 255);
+                self.apply(Effect::Static(Rgb::BLUE));
             }
             if red_clicked {
-                self.static_color(255, 0, 0);
+                self.apply(Effect::Static(Rgb::RED));
             }
 
             ui.add_space(10.0);
@@ -285,13 +1292,13 @@ impl eframe::App for RazerRGBMac {
                 });
 
             if green_breathing {
-                self.breathing(0, 255, 0);
+                self.apply(Effect::Breathing(Rgb::GREEN));
             }
             if blue_breathing {
-                self.breathing(0, 0, 255);
+                self.apply(Effect::Breathing(Rgb::BLUE));
             }
             if red_breathing {
-                self.breathing(255, 0, 0);
+                self.apply(Effect::Breathing(Rgb::RED));
             }
 
             ui.add_space(10.0);
@@ -304,7 +1311,7 @@ impl eframe::App for RazerRGBMac {
                             .add_sized(
                                 [120.0, 45.0],
                                 egui::Button::new(
-                                    egui::RichText::new(&format!("{EMOJI_RAINBOW} Spectrum"))
+                                    egui::RichText::new(format!("{EMOJI_RAINBOW} Spectrum"))
                                         .size(14.0),
                                 )
                                 .fill(egui::Color32::from_rgb(80, 40, 120)),
@@ -317,7 +1324,7 @@ impl eframe::App for RazerRGBMac {
                             .add_sized(
                                 [120.0, 45.0],
                                 egui::Button::new(
-                                    egui::RichText::new(&format!("{EMOJI_WAVE} Wave")).size(14.0),
+                                    egui::RichText::new(format!("{EMOJI_WAVE} Wave")).size(14.0),
                                 )
                                 .fill(egui::Color32::from_rgb(40, 80, 120)),
                             )
@@ -329,13 +1336,44 @@ impl eframe::App for RazerRGBMac {
                 .inner;
 
             if spectrum_clicked {
-                self.spectrum();
+                self.apply(Effect::Spectrum);
             }
             if wave_clicked {
-                self.wave(0x00, 0x01);
+                self.apply(Effect::Wave {
+                    direction: 0x00,
+                    speed: 0x01,
+                });
             }
+
+            ui.add_space(10.0);
+
+            Self::render_section(ui, "Transition", |ui| {
+                let transition = &mut self.transition;
+                let mut millis = transition.duration.as_millis() as u64;
+                ui.horizontal(|ui| {
+                    ui.label("Fade");
+                    ui.add(egui::Slider::new(&mut millis, 0..=3000).suffix(" ms"));
+                });
+                transition.duration = Duration::from_millis(millis);
+                ui.add_enabled_ui(millis > 0, |ui| {
+                    egui::ComboBox::from_label("Easing")
+                        .selected_text(transition.easing.name())
+                        .show_ui(ui, |ui| {
+                            for easing in Easing::ALL {
+                                ui.selectable_value(&mut transition.easing, easing, easing.name());
+                            }
+                        });
+                });
+            });
         });
 
+        self.render_console(ctx);
+        self.render_doctor(ctx);
+        self.render_log(ctx);
+        self.render_schedule(ctx);
+        self.render_sequences(ctx);
+        self.render_toasts(ctx);
+
         // About window (shows when button is clicked)
         if self.show_about {
             egui::Window::new("About Razer RGB Control")
@@ -345,7 +1383,7 @@ impl eframe::App for RazerRGBMac {
                 .show(ctx, |ui| {
                     ui.vertical_centered(|ui| {
                         ui.label(
-                            egui::RichText::new(&format!("{EMOJI_GAMEPAD} Razer RGB Control"))
+                            egui::RichText::new(format!("{EMOJI_GAMEPAD} Razer RGB Control"))
                                 .size(20.0),
                         );
                         ui.add_space(10.0);
@@ -362,3 +1400,30 @@ impl eframe::App for RazerRGBMac {
         }
     }
 }
+
+/// The color of `key` under `state`. The keyboard runs the animated effects
+/// itself, so for those this only gives the idea.
+fn key_color(state: &LightingState, key: &Key, t: f32) -> Rgb {
+    let color = match state.effect {
+        Effect::Off => Rgb::BLACK,
+        Effect::Static(color) => color,
+        Effect::Custom if key.row < state.frame.rows() && key.col < state.frame.cols() => {
+            state.frame.get(key.row, key.col)
+        }
+        Effect::Custom => Rgb::BLACK,
+        Effect::Breathing(color) => {
+            color.lerp(Rgb::BLACK, 0.5 + 0.5 * (t * std::f32::consts::PI).cos())
+        }
+        Effect::Spectrum => Rgb::from_hsv(t * 36.0 % 360.0, 1.0, 1.0),
+        Effect::Wave { .. } => Rgb::from_hsv((key.x * 15.0 + t * 90.0) % 360.0, 1.0, 1.0),
+    };
+    color.lerp(Rgb::BLACK, 1.0 - state.brightness as f32 / 255.0)
+}
+
+fn load_schedule() -> Schedule {
+    let path = config_dir().join(SCHEDULE_FILE);
+    Schedule::load(&path).unwrap_or_else(|err| {
+        tracing::warn!(icon = EMOJI_WARNING, "Ignoring {}: {err}", path.display());
+        Schedule::default()
+    })
+}
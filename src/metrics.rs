@@ -0,0 +1,424 @@
+//! Shows system load on the keys: by default each core's usage across the
+//! F-row (matrix row 0) and memory as a bar filling the number row (row 1).
+//!
+//! Bindings live in `<config dir>/metrics.json`:
+//!
+//! ```json
+//! {
+//!   "interval_ms": 1000,
+//!   "gradient": ["green", "yellow", "red"],
+//!   "temperature_range": [40, 90],
+//!   "bindings": [
+//!     { "metric": "cpu", "keys": { "row": 0 } },
+//!     { "metric": "temperature", "keys": { "row": 5, "from": 0, "to": 4 }, "style": "fill" }
+//!   ]
+//! }
+//! ```
+//!
+//! Key groups are spans of the lighting matrix, not key names. Numbers come
+//! from a `MetricsSource`; `ProcSource` reads Linux's `/proc` and `/sys`.
+
+use crate::animation::Animation;
+use crate::color::{Gradient, Rgb};
+use crate::emojis::EMOJI_WARNING;
+use crate::frame::Frame;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const METRICS_FILE: &str = "metrics.json";
+
+pub trait MetricsSource: Send {
+    /// Share of time each core was busy since the previous call, 0..=1.
+    fn cpu(&mut self) -> io::Result<Vec<f32>>;
+    /// Share of memory in use, 0..=1.
+    fn memory(&mut self) -> io::Result<f32>;
+    /// Every thermal sensor, in °C.
+    fn temperatures(&mut self) -> io::Result<Vec<f32>>;
+}
+
+/// Reads `/proc/stat`, `/proc/meminfo` and `/sys/class/thermal` under
+/// `root`, which is `/` except in tests.
+pub struct ProcSource {
+    root: PathBuf,
+    /// Busy and total jiffies per core at the previous call.
+    previous: Vec<(u64, u64)>,
+}
+
+impl ProcSource {
+    pub fn new() -> Self {
+        Self::with_root("/")
+    }
+
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            previous: Vec::new(),
+        }
+    }
+}
+
+impl Default for ProcSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Busy and total jiffies for each `cpuN` line. Time waiting on I/O counts
+/// as idle.
+fn parse_stat(text: &str) -> io::Result<Vec<(u64, u64)>> {
+    text.lines()
+        .filter(|line| line.starts_with("cpu") && !line.starts_with("cpu "))
+        .map(|line| {
+            let fields = line
+                .split_whitespace()
+                .skip(1)
+                .take(8)
+                .map(str::parse::<u64>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid(format!("bad /proc/stat line: {line}")))?;
+            if fields.len() < 5 {
+                return Err(invalid(format!("short /proc/stat line: {line}")));
+            }
+            let total: u64 = fields.iter().sum();
+            Ok((total - fields[3] - fields[4], total))
+        })
+        .collect()
+}
+
+/// The `kB` value of one `/proc/meminfo` field.
+fn meminfo_field(text: &str, name: &str) -> io::Result<u64> {
+    text.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .and_then(|rest| rest.split_whitespace().next()?.parse().ok())
+        .ok_or_else(|| invalid(format!("no {name} in /proc/meminfo")))
+}
+
+impl MetricsSource for ProcSource {
+    fn cpu(&mut self) -> io::Result<Vec<f32>> {
+        let text = fs::read_to_string(self.root.join("proc/stat"))?;
+        let now = parse_stat(&text)?;
+        let usage = now
+            .iter()
+            .enumerate()
+            .map(|(i, &(busy, total))| {
+                // Since boot on the first call.
+                let (was_busy, was_total) = self.previous.get(i).copied().unwrap_or((0, 0));
+                let total = total.saturating_sub(was_total);
+                if total == 0 {
+                    0.0
+                } else {
+                    busy.saturating_sub(was_busy) as f32 / total as f32
+                }
+            })
+            .collect();
+        self.previous = now;
+        Ok(usage)
+    }
+
+    fn memory(&mut self) -> io::Result<f32> {
+        let text = fs::read_to_string(self.root.join("proc/meminfo"))?;
+        let total = meminfo_field(&text, "MemTotal")?;
+        let available = meminfo_field(&text, "MemAvailable")?;
+        if total == 0 {
+            return Err(invalid("MemTotal is 0".to_string()));
+        }
+        Ok(1.0 - available.min(total) as f32 / total as f32)
+    }
+
+    fn temperatures(&mut self) -> io::Result<Vec<f32>> {
+        let mut zones: Vec<PathBuf> = fs::read_dir(self.root.join("sys/class/thermal"))?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("thermal_zone"))
+            })
+            .collect();
+        zones.sort();
+        // Zones that can't be read right now (sensor asleep) are skipped.
+        Ok(zones
+            .iter()
+            .filter_map(|zone| fs::read_to_string(zone.join("temp")).ok())
+            .filter_map(|text| text.trim().parse::<i64>().ok())
+            .map(|millis| millis as f32 / 1000.0)
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    Cpu,
+    Memory,
+    Temperature,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    /// One key per value (core or sensor), colored by its level.
+    Keys,
+    /// A bar lighting more keys as the level rises.
+    Bar,
+    /// The whole group in one color.
+    Fill,
+}
+
+impl Metric {
+    pub fn default_style(self) -> Style {
+        match self {
+            Metric::Cpu => Style::Keys,
+            Metric::Memory => Style::Bar,
+            Metric::Temperature => Style::Fill,
+        }
+    }
+}
+
+/// Columns `from..to` of one matrix row; `to` defaults to the end of the row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyGroup {
+    pub row: usize,
+    #[serde(default)]
+    pub from: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<usize>,
+}
+
+impl KeyGroup {
+    pub fn row(row: usize) -> Self {
+        Self {
+            row,
+            from: 0,
+            to: None,
+        }
+    }
+
+    /// The group's `(row, column)` positions that exist in `frame`.
+    fn keys(&self, frame: &Frame) -> Vec<(usize, usize)> {
+        if self.row >= frame.rows() {
+            return Vec::new();
+        }
+        let to = self.to.unwrap_or(frame.cols()).min(frame.cols());
+        (self.from..to).map(|col| (self.row, col)).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub metric: Metric,
+    pub keys: KeyGroup,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<Style>,
+}
+
+impl Binding {
+    pub fn new(metric: Metric, keys: KeyGroup) -> Self {
+        Self {
+            metric,
+            keys,
+            style: None,
+        }
+    }
+
+    pub fn style(&self) -> Style {
+        self.style.unwrap_or(self.metric.default_style())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    #[serde(rename = "interval_ms", with = "crate::transition::millis")]
+    pub interval: Duration,
+    pub gradient: Gradient,
+    /// Temperatures mapped to the bottom and top of the gradient, in °C.
+    pub temperature_range: [f32; 2],
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            gradient: Gradient::default(),
+            temperature_range: [40.0, 90.0],
+            bindings: vec![
+                Binding::new(Metric::Cpu, KeyGroup::row(0)),
+                Binding::new(Metric::Memory, KeyGroup::row(1)),
+            ],
+        }
+    }
+}
+
+impl MetricsConfig {
+    /// Missing file means the defaults.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        let config: Self = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        config
+            .validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval.is_zero() {
+            return Err("the interval must be above 0".to_string());
+        }
+        let [low, high] = self.temperature_range;
+        if low >= high {
+            return Err(format!("empty temperature range {low}..{high}"));
+        }
+        Ok(())
+    }
+}
+
+/// One reading of every metric; whatever failed is left empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sample {
+    pub cpu: Vec<f32>,
+    pub memory: Option<f32>,
+    pub temperatures: Vec<f32>,
+}
+
+impl Sample {
+    /// Levels in 0..=1 for `metric`, one per core or sensor.
+    fn levels(&self, metric: Metric, range: [f32; 2]) -> Vec<f32> {
+        match metric {
+            Metric::Cpu => self.cpu.clone(),
+            Metric::Memory => self.memory.into_iter().collect(),
+            Metric::Temperature => self
+                .temperatures
+                .iter()
+                .map(|celsius| ((celsius - range[0]) / (range[1] - range[0])).clamp(0.0, 1.0))
+                .collect(),
+        }
+    }
+
+    /// The single level shown by bars and fills: average load across
+    /// cores, but the hottest sensor.
+    fn summary(&self, metric: Metric, range: [f32; 2]) -> Option<f32> {
+        let levels = self.levels(metric, range);
+        if levels.is_empty() {
+            return None;
+        }
+        Some(match metric {
+            Metric::Temperature => levels.iter().copied().fold(0.0, f32::max),
+            _ => levels.iter().sum::<f32>() / levels.len() as f32,
+        })
+    }
+}
+
+/// Samples the source once per frame and draws the bindings. Keys outside
+/// every binding stay dark.
+pub struct MetricsEffect {
+    source: Box<dyn MetricsSource>,
+    config: MetricsConfig,
+    /// Metrics already reported as failing, to warn only once.
+    failing: HashSet<Metric>,
+}
+
+impl MetricsEffect {
+    pub fn new(source: impl MetricsSource + 'static, config: MetricsConfig) -> Self {
+        Self {
+            source: Box::new(source),
+            config,
+            failing: HashSet::new(),
+        }
+    }
+
+    fn check<T: Default>(&mut self, metric: Metric, result: io::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.failing.remove(&metric);
+                Some(value)
+            }
+            Err(err) => {
+                if self.failing.insert(metric) {
+                    tracing::warn!(icon = EMOJI_WARNING, "Cannot read {metric:?}: {err}");
+                }
+                None
+            }
+        }
+    }
+
+    /// Reads only the metrics some binding shows.
+    pub fn sample(&mut self) -> Sample {
+        let wanted = |metric| self.config.bindings.iter().any(|b| b.metric == metric);
+        let (cpu, memory, temperatures) = (
+            wanted(Metric::Cpu),
+            wanted(Metric::Memory),
+            wanted(Metric::Temperature),
+        );
+        let mut sample = Sample::default();
+        if cpu {
+            let result = self.source.cpu();
+            sample.cpu = self.check(Metric::Cpu, result).unwrap_or_default();
+        }
+        if memory {
+            let result = self.source.memory();
+            sample.memory = self.check(Metric::Memory, result);
+        }
+        if temperatures {
+            let result = self.source.temperatures();
+            sample.temperatures = self.check(Metric::Temperature, result).unwrap_or_default();
+        }
+        sample
+    }
+
+    pub fn draw(&self, sample: &Sample, frame: &mut Frame) {
+        frame.fill(Rgb::BLACK);
+        let gradient = &self.config.gradient;
+        let range = self.config.temperature_range;
+        for binding in &self.config.bindings {
+            let keys = binding.keys.keys(frame);
+            match binding.style() {
+                Style::Keys => {
+                    let levels = sample.levels(binding.metric, range);
+                    for (&(row, col), &level) in keys.iter().zip(&levels) {
+                        frame.set(row, col, gradient.at(level));
+                    }
+                }
+                Style::Bar => {
+                    let Some(level) = sample.summary(binding.metric, range) else {
+                        continue;
+                    };
+                    let lit = (level * keys.len() as f32).round() as usize;
+                    let last = keys.len().saturating_sub(1).max(1) as f32;
+                    for (i, &(row, col)) in keys.iter().take(lit).enumerate() {
+                        frame.set(row, col, gradient.at(i as f32 / last));
+                    }
+                }
+                Style::Fill => {
+                    let Some(level) = sample.summary(binding.metric, range) else {
+                        continue;
+                    };
+                    for &(row, col) in &keys {
+                        frame.set(row, col, gradient.at(level));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Animation for MetricsEffect {
+    fn render(&mut self, _t: Duration, frame: &mut Frame) -> Option<Duration> {
+        let sample = self.sample();
+        self.draw(&sample, frame);
+        Some(self.config.interval)
+    }
+}
@@ -0,0 +1,186 @@
+//! Short attention-grabbing flashes, e.g. red when a build fails, after
+//! which the keyboard goes back to exactly what it showed before.
+//!
+//! The firmware can report its brightness but not its effect, so the snapshot
+//! takes the brightness from the keyboard and the effect and frame from the
+//! device's cache. Animated effects restart from the beginning of their cycle
+//! when restored.
+
+use crate::color::Rgb;
+use crate::device::{Device, LightingState};
+use crate::effect::Effect;
+use crate::transition::{millis, Step, STEP_INTERVAL};
+use crate::transport::TransportError;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest notification accepted; the device is held while it plays.
+pub const MAX_LENGTH: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pattern {
+    /// On for the first half of each repeat, off for the second.
+    #[default]
+    Flash,
+    /// Fades in and out once per repeat.
+    Pulse,
+    /// Holds the color for the whole notification.
+    Solid,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 3] = [Pattern::Flash, Pattern::Pulse, Pattern::Solid];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pattern::Flash => "flash",
+            Pattern::Pulse => "pulse",
+            Pattern::Solid => "solid",
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pattern::ALL
+            .into_iter()
+            .find(|pattern| pattern.name() == s)
+            .ok_or_else(|| format!("unknown pattern \"{s}\" (flash, pulse, solid)"))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn default_repeat() -> u32 {
+    3
+}
+
+fn default_duration() -> Duration {
+    Duration::from_millis(500)
+}
+
+/// In JSON: `{"notify": "flash", "color": "red", "repeat": 3, "duration_ms": 500}`,
+/// where everything but the color may be left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    #[serde(rename = "notify", default)]
+    pub pattern: Pattern,
+    pub color: Rgb,
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    /// Length of one repeat.
+    #[serde(rename = "duration_ms", with = "millis", default = "default_duration")]
+    pub duration: Duration,
+}
+
+impl Notification {
+    pub fn new(pattern: Pattern, color: Rgb) -> Self {
+        Self {
+            pattern,
+            color,
+            repeat: default_repeat(),
+            duration: default_duration(),
+        }
+    }
+
+    pub fn length(&self) -> Duration {
+        self.duration.saturating_mul(self.repeat)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.repeat == 0 || self.duration.is_zero() {
+            return Err("repeat and duration must be above zero".to_string());
+        }
+        if self.length() > MAX_LENGTH {
+            return Err(format!(
+                "notification is {}ms long, the limit is {}ms",
+                self.length().as_millis(),
+                MAX_LENGTH.as_millis()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The steps of the pattern, each with its time from the start. Leaves
+    /// out the restore at the end.
+    pub fn steps(&self, snapshot: &LightingState) -> Vec<(Duration, Step)> {
+        let lit = |brightness| {
+            Step::Settle(LightingState {
+                effect: Effect::Static(self.color),
+                frame: snapshot.frame.clone(),
+                brightness,
+            })
+        };
+        let mut steps = Vec::new();
+        match self.pattern {
+            Pattern::Flash => {
+                let dark = Step::Settle(LightingState {
+                    effect: Effect::Off,
+                    frame: snapshot.frame.clone(),
+                    brightness: 0xFF,
+                });
+                for i in 0..self.repeat {
+                    let start = self.duration * i;
+                    steps.push((start, lit(0xFF)));
+                    steps.push((start + self.duration / 2, dark.clone()));
+                }
+            }
+            Pattern::Pulse => {
+                steps.push((Duration::ZERO, lit(0)));
+                let count = (self.duration.as_millis() / STEP_INTERVAL.as_millis()).max(2) as u32;
+                for i in 0..self.repeat {
+                    let start = self.duration * i;
+                    for k in 1..=count {
+                        let t = k as f32 / count as f32;
+                        let brightness = ((PI * t).sin() * 255.0).round() as u8;
+                        steps.push((
+                            start + self.duration * k / count,
+                            Step::Brightness(brightness),
+                        ));
+                    }
+                }
+            }
+            Pattern::Solid => steps.push((Duration::ZERO, lit(0xFF))),
+        }
+        steps
+    }
+}
+
+/// What the keyboard shows now: the brightness as reported by the keyboard
+/// when it answers, everything else from the cache.
+pub fn snapshot(device: &mut Device) -> LightingState {
+    if let Err(err) = device.read_brightness() {
+        tracing::debug!("Using cached brightness: {err}");
+    }
+    device.state().clone()
+}
+
+/// Plays `notification` and puts the snapshot back, even when a step fails.
+pub fn notify(device: &mut Device, notification: &Notification) -> Result<(), TransportError> {
+    let before = snapshot(device);
+    let started = Instant::now();
+    let mut played = Ok(());
+    for (at, step) in notification.steps(&before) {
+        thread::sleep(at.saturating_sub(started.elapsed()));
+        played = step.apply(device);
+        if played.is_err() {
+            break;
+        }
+    }
+    if played.is_ok() {
+        thread::sleep(notification.length().saturating_sub(started.elapsed()));
+    }
+    let restored = device.restore(&before);
+    played.and(restored)
+}
@@ -0,0 +1,532 @@
+//! A minimal OpenRGB SDK server, so OpenRGB clients see the keyboard as a
+//! single controller with one matrix zone.
+//!
+//! Only the packets a client needs to list the controller and drive it are
+//! handled; anything else is read and ignored.
+
+use crate::color::Rgb;
+use crate::device::Device;
+use crate::effect::{Effect, DEFAULT_WAVE_DIRECTION, DEFAULT_WAVE_SPEED};
+use crate::emojis::EMOJI_WARNING;
+use crate::frame::Frame;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+pub const DEFAULT_PORT: u16 = 6742;
+pub const PROTOCOL_VERSION: u32 = 3;
+pub const MAGIC: &[u8; 4] = b"ORGB";
+pub const HEADER_LEN: usize = 16;
+/// Far above any real request; a full LED update is under 1 KiB.
+pub const MAX_PAYLOAD_LEN: usize = 1 << 20;
+
+pub const REQUEST_CONTROLLER_COUNT: u32 = 0;
+pub const REQUEST_CONTROLLER_DATA: u32 = 1;
+pub const REQUEST_PROTOCOL_VERSION: u32 = 40;
+pub const SET_CLIENT_NAME: u32 = 50;
+pub const RGBCONTROLLER_RESIZEZONE: u32 = 1000;
+pub const RGBCONTROLLER_UPDATELEDS: u32 = 1050;
+pub const RGBCONTROLLER_UPDATEZONELEDS: u32 = 1051;
+pub const RGBCONTROLLER_UPDATESINGLELED: u32 = 1052;
+pub const RGBCONTROLLER_SETCUSTOMMODE: u32 = 1100;
+pub const RGBCONTROLLER_UPDATEMODE: u32 = 1101;
+pub const RGBCONTROLLER_SAVEMODE: u32 = 1102;
+
+pub const DEVICE_TYPE_KEYBOARD: i32 = 5;
+pub const ZONE_TYPE_MATRIX: i32 = 2;
+
+pub const MODE_FLAG_HAS_SPEED: u32 = 1 << 0;
+pub const MODE_FLAG_HAS_DIRECTION_LR: u32 = 1 << 1;
+pub const MODE_FLAG_HAS_PER_LED_COLOR: u32 = 1 << 5;
+pub const MODE_FLAG_HAS_MODE_SPECIFIC_COLOR: u32 = 1 << 6;
+
+pub const MODE_COLORS_NONE: u32 = 0;
+pub const MODE_COLORS_PER_LED: u32 = 1;
+pub const MODE_COLORS_MODE_SPECIFIC: u32 = 2;
+
+pub const MODE_DIRECTION_LEFT: u32 = 0;
+pub const MODE_DIRECTION_RIGHT: u32 = 1;
+
+pub const MODE_DIRECT: i32 = 0;
+pub const MODE_STATIC: i32 = 1;
+pub const MODE_BREATHING: i32 = 2;
+pub const MODE_SPECTRUM: i32 = 3;
+pub const MODE_WAVE: i32 = 4;
+pub const MODE_OFF: i32 = 5;
+
+const MODE_NAMES: [&str; 6] = [
+    "Direct",
+    "Static",
+    "Breathing",
+    "Spectrum Cycle",
+    "Wave",
+    "Off",
+];
+
+/// One OpenRGB mode, in the shape the SDK serializes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mode {
+    pub name: String,
+    pub value: i32,
+    pub flags: u32,
+    pub speed_min: u32,
+    pub speed_max: u32,
+    pub brightness_min: u32,
+    pub brightness_max: u32,
+    pub colors_min: u32,
+    pub colors_max: u32,
+    pub speed: u32,
+    pub brightness: u32,
+    pub direction: u32,
+    pub color_mode: u32,
+    pub colors: Vec<Rgb>,
+}
+
+impl Mode {
+    fn new(value: i32) -> Self {
+        Self {
+            name: MODE_NAMES[value as usize].to_string(),
+            value,
+            flags: 0,
+            speed_min: 0,
+            speed_max: 0,
+            brightness_min: 0,
+            brightness_max: 0,
+            colors_min: 0,
+            colors_max: 0,
+            speed: 0,
+            brightness: 0,
+            direction: 0,
+            color_mode: MODE_COLORS_NONE,
+            colors: Vec::new(),
+        }
+    }
+
+    fn with_color(value: i32, color: Rgb) -> Self {
+        Self {
+            flags: MODE_FLAG_HAS_MODE_SPECIFIC_COLOR,
+            colors_min: 1,
+            colors_max: 1,
+            color_mode: MODE_COLORS_MODE_SPECIFIC,
+            colors: vec![color],
+            ..Self::new(value)
+        }
+    }
+
+    /// The mode list, with the active effect's parameters filled in.
+    pub fn all(effect: Effect) -> Vec<Mode> {
+        let (static_color, breathing_color) = match effect {
+            Effect::Static(color) => (color, color),
+            Effect::Breathing(color) => (color, color),
+            _ => (Rgb::WHITE, Rgb::WHITE),
+        };
+        let (direction, speed) = match effect {
+            Effect::Wave { direction, speed } => (direction, speed),
+            _ => (DEFAULT_WAVE_DIRECTION, DEFAULT_WAVE_SPEED),
+        };
+
+        vec![
+            Mode {
+                flags: MODE_FLAG_HAS_PER_LED_COLOR,
+                color_mode: MODE_COLORS_PER_LED,
+                ..Mode::new(MODE_DIRECT)
+            },
+            Mode::with_color(MODE_STATIC, static_color),
+            Mode::with_color(MODE_BREATHING, breathing_color),
+            Mode::new(MODE_SPECTRUM),
+            Mode {
+                flags: MODE_FLAG_HAS_SPEED | MODE_FLAG_HAS_DIRECTION_LR,
+                speed_min: 0x01,
+                speed_max: 0xFF,
+                speed: speed as u32,
+                direction: if direction == 0x02 {
+                    MODE_DIRECTION_LEFT
+                } else {
+                    MODE_DIRECTION_RIGHT
+                },
+                ..Mode::new(MODE_WAVE)
+            },
+            Mode::new(MODE_OFF),
+        ]
+    }
+
+    /// The effect a client asks for by sending this mode back to us.
+    pub fn effect(&self) -> Option<Effect> {
+        let color = self.colors.first().copied().unwrap_or(Rgb::WHITE);
+        match self.value {
+            MODE_DIRECT => Some(Effect::Custom),
+            MODE_STATIC => Some(Effect::Static(color)),
+            MODE_BREATHING => Some(Effect::Breathing(color)),
+            MODE_SPECTRUM => Some(Effect::Spectrum),
+            MODE_WAVE => Some(Effect::Wave {
+                direction: if self.direction == MODE_DIRECTION_LEFT {
+                    0x02
+                } else {
+                    0x01
+                },
+                speed: self.speed.clamp(0x01, 0xFF) as u8,
+            }),
+            MODE_OFF => Some(Effect::Off),
+            _ => None,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>, protocol: u32) {
+        put_str(out, &self.name);
+        put_i32(out, self.value);
+        put_u32(out, self.flags);
+        put_u32(out, self.speed_min);
+        put_u32(out, self.speed_max);
+        if protocol >= 3 {
+            put_u32(out, self.brightness_min);
+            put_u32(out, self.brightness_max);
+        }
+        put_u32(out, self.colors_min);
+        put_u32(out, self.colors_max);
+        put_u32(out, self.speed);
+        if protocol >= 3 {
+            put_u32(out, self.brightness);
+        }
+        put_u32(out, self.direction);
+        put_u32(out, self.color_mode);
+        put_colors(out, &self.colors);
+    }
+
+    fn read(reader: &mut Reader, protocol: u32) -> Option<Self> {
+        let name = reader.str()?;
+        let value = reader.i32()?;
+        let flags = reader.u32()?;
+        let speed_min = reader.u32()?;
+        let speed_max = reader.u32()?;
+        let (brightness_min, brightness_max) = if protocol >= 3 {
+            (reader.u32()?, reader.u32()?)
+        } else {
+            (0, 0)
+        };
+        let colors_min = reader.u32()?;
+        let colors_max = reader.u32()?;
+        let speed = reader.u32()?;
+        let brightness = if protocol >= 3 { reader.u32()? } else { 0 };
+        let direction = reader.u32()?;
+        let color_mode = reader.u32()?;
+        let colors = reader.colors()?;
+        Some(Self {
+            name,
+            value,
+            flags,
+            speed_min,
+            speed_max,
+            brightness_min,
+            brightness_max,
+            colors_min,
+            colors_max,
+            speed,
+            brightness,
+            direction,
+            color_mode,
+            colors,
+        })
+    }
+}
+
+/// Index of the mode that corresponds to `effect`.
+pub fn active_mode(effect: Effect) -> i32 {
+    match effect {
+        Effect::Custom => MODE_DIRECT,
+        Effect::Static(_) => MODE_STATIC,
+        Effect::Breathing(_) => MODE_BREATHING,
+        Effect::Spectrum => MODE_SPECTRUM,
+        Effect::Wave { .. } => MODE_WAVE,
+        Effect::Off => MODE_OFF,
+    }
+}
+
+/// Serializes the keyboard as an OpenRGB controller description.
+pub fn controller_data(device: &Device, protocol: u32) -> Vec<u8> {
+    let frame = device.frame();
+    let (rows, cols) = (frame.rows() as u32, frame.cols() as u32);
+    let led_count = rows * cols;
+
+    let mut out = Vec::new();
+    put_u32(&mut out, 0); // data_size, patched below
+    put_i32(&mut out, DEVICE_TYPE_KEYBOARD);
+    let descriptor = device.descriptor();
+    put_str(&mut out, descriptor.name);
+    if protocol >= 1 {
+        put_str(&mut out, "Razer");
+    }
+    put_str(&mut out, "Razer RGB Control");
+    put_str(&mut out, env!("CARGO_PKG_VERSION"));
+    put_str(&mut out, "");
+    put_str(&mut out, &format!("USB 1532:{:04X}", descriptor.product_id));
+
+    let modes = Mode::all(device.effect());
+    put_u16(&mut out, modes.len() as u16);
+    put_i32(&mut out, active_mode(device.effect()));
+    for mode in &modes {
+        mode.write(&mut out, protocol);
+    }
+
+    put_u16(&mut out, 1);
+    put_str(&mut out, "Keyboard");
+    put_i32(&mut out, ZONE_TYPE_MATRIX);
+    put_u32(&mut out, led_count);
+    put_u32(&mut out, led_count);
+    put_u32(&mut out, led_count);
+    put_u16(&mut out, (8 + led_count * 4) as u16);
+    put_u32(&mut out, rows);
+    put_u32(&mut out, cols);
+    for index in 0..led_count {
+        put_u32(&mut out, index);
+    }
+
+    put_u16(&mut out, led_count as u16);
+    for index in 0..led_count {
+        put_str(&mut out, &format!("Key {}:{}", index / cols, index % cols));
+        put_u32(&mut out, index);
+    }
+    put_colors(&mut out, frame.pixels());
+
+    let data_size = out.len() as u32;
+    out[..4].copy_from_slice(&data_size.to_le_bytes());
+    out
+}
+
+/// Builds a packet: header followed by `payload`.
+pub fn packet(device_index: u32, packet_id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(MAGIC);
+    put_u32(&mut out, device_index);
+    put_u32(&mut out, packet_id);
+    put_u32(&mut out, payload.len() as u32);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Reads one packet, returning `(device_index, packet_id, payload)`.
+pub fn read_packet(stream: &mut impl Read) -> io::Result<(u32, u32, Vec<u8>)> {
+    let mut header = [0u8; HEADER_LEN];
+    stream.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "bad magic"));
+    }
+    let field = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+    let len = field(12) as usize;
+    if len > MAX_PAYLOAD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "payload too large",
+        ));
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    Ok((field(4), field(8), payload))
+}
+
+pub fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn put_i32(out: &mut Vec<u8>, value: i32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Strings are a u16 length, counting the trailing NUL, then the bytes.
+pub fn put_str(out: &mut Vec<u8>, value: &str) {
+    put_u16(out, value.len() as u16 + 1);
+    out.extend_from_slice(value.as_bytes());
+    out.push(0);
+}
+
+/// Colors go over the wire as `0x00BBGGRR`.
+pub fn put_colors(out: &mut Vec<u8>, colors: &[Rgb]) {
+    put_u16(out, colors.len() as u16);
+    for &Rgb { r, g, b } in colors {
+        out.extend_from_slice(&[r, g, b, 0]);
+    }
+}
+
+/// Little-endian cursor over a packet payload.
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Some(head)
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub fn i32(&mut self) -> Option<i32> {
+        Some(i32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub fn str(&mut self) -> Option<String> {
+        let len = self.u16()? as usize;
+        let bytes = self.take(len)?;
+        let text = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+        Some(String::from_utf8_lossy(text).into_owned())
+    }
+
+    pub fn color(&mut self) -> Option<Rgb> {
+        let bytes = self.take(4)?;
+        Some(Rgb::new(bytes[0], bytes[1], bytes[2]))
+    }
+
+    pub fn colors(&mut self) -> Option<Vec<Rgb>> {
+        let count = self.u16()?;
+        (0..count).map(|_| self.color()).collect()
+    }
+}
+
+pub struct OpenRgbServer {
+    listener: TcpListener,
+    device: Arc<Mutex<Device>>,
+}
+
+impl OpenRgbServer {
+    pub fn bind(addr: impl ToSocketAddrs, device: Arc<Mutex<Device>>) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            device,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts clients forever, one thread each.
+    pub fn run(self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            // A failed accept (out of file descriptors, a client that gave
+            // up) only loses that client.
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::warn!(
+                        icon = EMOJI_WARNING,
+                        "OpenRGB server could not accept a client: {err}"
+                    );
+                    continue;
+                }
+            };
+            let device = Arc::clone(&self.device);
+            thread::spawn(move || {
+                let _ = Connection::new(device).serve(stream);
+            });
+        }
+        Ok(())
+    }
+
+    pub fn spawn(self) -> JoinHandle<io::Result<()>> {
+        thread::spawn(move || self.run())
+    }
+}
+
+struct Connection {
+    device: Arc<Mutex<Device>>,
+    protocol: u32,
+}
+
+impl Connection {
+    fn new(device: Arc<Mutex<Device>>) -> Self {
+        Self {
+            device,
+            protocol: 0,
+        }
+    }
+
+    fn serve(mut self, mut stream: TcpStream) -> io::Result<()> {
+        loop {
+            let (device_index, packet_id, payload) = read_packet(&mut stream)?;
+            if let Some(reply) = self.handle(device_index, packet_id, &payload) {
+                stream.write_all(&packet(device_index, packet_id, &reply))?;
+            }
+        }
+    }
+
+    /// Handles one packet, returning the reply payload if the packet gets one.
+    fn handle(&mut self, device_index: u32, packet_id: u32, payload: &[u8]) -> Option<Vec<u8>> {
+        let mut reader = Reader::new(payload);
+        match packet_id {
+            REQUEST_PROTOCOL_VERSION => {
+                let client = reader.u32().unwrap_or(0);
+                self.protocol = client.min(PROTOCOL_VERSION);
+                Some(PROTOCOL_VERSION.to_le_bytes().to_vec())
+            }
+            REQUEST_CONTROLLER_COUNT => Some(1u32.to_le_bytes().to_vec()),
+            REQUEST_CONTROLLER_DATA if device_index == 0 => {
+                let protocol = reader.u32().unwrap_or(0).min(PROTOCOL_VERSION);
+                Some(controller_data(&self.device.lock().unwrap(), protocol))
+            }
+            _ if device_index != 0 => None,
+            RGBCONTROLLER_UPDATELEDS => {
+                reader.u32()?;
+                let colors = reader.colors()?;
+                self.update_leds(0, &colors);
+                None
+            }
+            RGBCONTROLLER_UPDATEZONELEDS => {
+                reader.u32()?;
+                if reader.u32()? != 0 {
+                    return None;
+                }
+                let colors = reader.colors()?;
+                self.update_leds(0, &colors);
+                None
+            }
+            RGBCONTROLLER_UPDATESINGLELED => {
+                let index = reader.i32()?;
+                let color = reader.color()?;
+                if index >= 0 {
+                    self.update_leds(index as usize, &[color]);
+                }
+                None
+            }
+            RGBCONTROLLER_SETCUSTOMMODE => {
+                let _ = self.device.lock().unwrap().set_effect(Effect::Custom);
+                None
+            }
+            RGBCONTROLLER_UPDATEMODE | RGBCONTROLLER_SAVEMODE => {
+                reader.u32()?;
+                reader.i32()?;
+                let effect = Mode::read(&mut reader, self.protocol)?.effect()?;
+                let _ = self.device.lock().unwrap().set_effect(effect);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Writes `colors` into the frame starting at LED `first` and pushes it.
+    fn update_leds(&self, first: usize, colors: &[Rgb]) {
+        let mut device = self.device.lock().unwrap();
+        let mut frame: Frame = device.frame().clone();
+        for (pixel, &color) in frame.pixels_mut().iter_mut().skip(first).zip(colors) {
+            *pixel = color;
+        }
+        let _ = device.set_frame(&frame);
+    }
+}
@@ -0,0 +1,237 @@
+//! Plays PNG images and animated GIFs on the keys.
+//!
+//! Each key shows the average of the pixels under it, with the picture
+//! stretched over the keyboard's `Layout`, so a wide key covers more of the
+//! image than a letter key. Transparent pixels are black.
+
+use crate::animation::Animation;
+use crate::color::Rgb;
+use crate::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use crate::layout::Layout;
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, ImageFormat, ImageReader, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Cursor};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::Duration;
+
+/// Browsers show GIF frames with a delay under 20 ms for 100 ms; so do we.
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+fn invalid(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Decoded pictures with how long each one shows.
+pub struct Clip {
+    frames: Vec<(RgbaImage, Duration)>,
+}
+
+impl Clip {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    /// Reads a PNG (one frame) or a GIF (all of its frames).
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let format = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()?
+            .format();
+        let frames = match format {
+            Some(ImageFormat::Gif) => GifDecoder::new(Cursor::new(bytes))
+                .map_err(invalid)?
+                .into_frames()
+                .collect_frames()
+                .map_err(invalid)?
+                .into_iter()
+                .map(|frame| {
+                    let delay = Duration::from(frame.delay());
+                    let delay = if delay < MIN_DELAY {
+                        DEFAULT_DELAY
+                    } else {
+                        delay
+                    };
+                    (frame.into_buffer(), delay)
+                })
+                .collect(),
+            Some(ImageFormat::Png) => {
+                let image = image::load_from_memory_with_format(bytes, ImageFormat::Png)
+                    .map_err(invalid)?;
+                vec![(image.to_rgba8(), DEFAULT_DELAY)]
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "not a PNG or GIF image",
+                ))
+            }
+        };
+        if frames.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "no frames"));
+        }
+        Ok(Self { frames })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|(_, delay)| *delay).sum()
+    }
+
+    /// Every frame as key colors on a `rows` x `cols` matrix.
+    pub fn resample(&self, layout: &Layout, rows: usize, cols: usize) -> Vec<(Frame, Duration)> {
+        self.frames
+            .iter()
+            .map(|(image, delay)| (resample(image, layout, rows, cols), *delay))
+            .collect()
+    }
+}
+
+/// Averages the pixels whose centers fall on each key, or takes the pixel
+/// under the key's center when the key is smaller than a pixel.
+pub fn resample(image: &RgbaImage, layout: &Layout, rows: usize, cols: usize) -> Frame {
+    let mut frame = Frame::new(rows, cols);
+    let (width, height) = layout.size();
+    if width <= 0.0 || height <= 0.0 || image.width() == 0 || image.height() == 0 {
+        return frame;
+    }
+    let scale_x = image.width() as f32 / width;
+    let scale_y = image.height() as f32 / height;
+    // First pixel whose center is at or past `edge`, in pixels.
+    let first =
+        |edge: f32, scale: f32, size: u32| ((edge * scale - 0.5).ceil().max(0.0) as u32).min(size);
+    for key in layout
+        .keys
+        .iter()
+        .filter(|key| key.row < rows && key.col < cols)
+    {
+        let (left, right) = (
+            first(key.x, scale_x, image.width()),
+            first(key.x + key.width, scale_x, image.width()),
+        );
+        let (top, bottom) = (
+            first(key.y, scale_y, image.height()),
+            first(key.y + key.height, scale_y, image.height()),
+        );
+        let mut sum = [0u64; 3];
+        let mut count = 0u64;
+        for y in top..bottom {
+            for x in left..right {
+                let [r, g, b, a] = image.get_pixel(x, y).0;
+                let a = a as u64;
+                sum[0] += r as u64 * a;
+                sum[1] += g as u64 * a;
+                sum[2] += b as u64 * a;
+                count += 255;
+            }
+        }
+        if count == 0 {
+            let (x, y) = key.center();
+            let x = ((x * scale_x) as u32).min(image.width() - 1);
+            let y = ((y * scale_y) as u32).min(image.height() - 1);
+            let [r, g, b, a] = image.get_pixel(x, y).0;
+            let a = a as u64;
+            sum = [r as u64 * a, g as u64 * a, b as u64 * a];
+            count = 255;
+        }
+        let channel = |total: u64| ((total + count / 2) / count) as u8;
+        frame.set(
+            key.row,
+            key.col,
+            Rgb::new(channel(sum[0]), channel(sum[1]), channel(sum[2])),
+        );
+    }
+    frame
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaybackSettings {
+    /// 2 plays twice as fast.
+    pub speed: f32,
+    /// Starts over at the end instead of stopping.
+    #[serde(rename = "loop")]
+    pub looping: bool,
+}
+
+impl Default for PlaybackSettings {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            looping: true,
+        }
+    }
+}
+
+impl PlaybackSettings {
+    /// Speeds outside this range would overflow the frame times.
+    pub const SPEEDS: RangeInclusive<f32> = 0.01..=100.0;
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !Self::SPEEDS.contains(&self.speed) {
+            return Err(format!(
+                "speed must be from {} to {}, not {}",
+                Self::SPEEDS.start(),
+                Self::SPEEDS.end(),
+                self.speed
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub struct Playback {
+    frames: Vec<(Frame, Duration)>,
+    length: Duration,
+    settings: PlaybackSettings,
+}
+
+impl Playback {
+    pub fn new(clip: &Clip, layout: &Layout, settings: PlaybackSettings) -> Self {
+        let frames = clip.resample(layout, MATRIX_ROWS, MATRIX_COLS);
+        Self {
+            length: clip.duration(),
+            frames,
+            settings,
+        }
+    }
+
+    /// The frame showing `at` into the clip, with when it ends.
+    fn frame_at(&self, at: Duration) -> (&Frame, Duration) {
+        let mut end = Duration::ZERO;
+        for (frame, delay) in &self.frames {
+            end += *delay;
+            if at < end {
+                return (frame, end);
+            }
+        }
+        let (frame, _) = self.frames.last().expect("a clip has frames");
+        (frame, self.length)
+    }
+}
+
+impl Animation for Playback {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> Option<Duration> {
+        let speed = self.settings.speed as f64;
+        let at = Duration::from_secs_f64(t.as_secs_f64() * speed);
+        let at = if self.settings.looping {
+            Duration::from_nanos((at.as_nanos() % self.length.as_nanos().max(1)) as u64)
+        } else if at >= self.length {
+            return None;
+        } else {
+            at
+        };
+        let (shown, end) = self.frame_at(at);
+        frame.clone_from(shown);
+        Some(Duration::from_secs_f64((end - at).as_secs_f64() / speed))
+    }
+}
@@ -0,0 +1,155 @@
+use crate::animation::Animation;
+use crate::config::{config_dir, NamedFileStore};
+use crate::device::Device;
+use crate::effect::Effect;
+use crate::frame::Frame;
+use crate::layout::Layout;
+use crate::playback::{Clip, Playback, PlaybackSettings};
+use crate::script::ScriptStore;
+use crate::transition::{Fade, Target, Transition};
+use crate::transport::TransportError;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+
+/// A named lighting setup that can be saved and applied later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub effect: Effect,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    /// Key colors for `Effect::Custom`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<Frame>,
+    /// Fades into the profile instead of switching at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<Transition>,
+    /// An animation drawn on the keys once the profile is applied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<Layer>,
+}
+
+/// A software effect a profile plays on top of its lighting.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Layer {
+    /// A PNG or GIF; relative paths start in the config directory.
+    Image {
+        path: PathBuf,
+        #[serde(flatten)]
+        playback: PlaybackSettings,
+    },
+    /// A script from the scripts directory, by name.
+    Script { name: String },
+}
+
+impl Layer {
+    pub fn animation(&self, layout: &Layout) -> io::Result<Box<dyn Animation>> {
+        match self {
+            Layer::Image { path, playback } => {
+                playback
+                    .validate()
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+                let clip = Clip::open(&config_dir().join(path))?;
+                Ok(Box::new(Playback::new(&clip, layout, *playback)))
+            }
+            Layer::Script { name } => {
+                Ok(Box::new(ScriptStore::default().open(name, layout.clone())?))
+            }
+        }
+    }
+}
+
+impl Profile {
+    pub fn new(name: &str, effect: Effect) -> Self {
+        Self {
+            name: name.to_string(),
+            effect,
+            brightness: None,
+            frame: None,
+            transition: None,
+            layer: None,
+        }
+    }
+
+    pub fn target(&self) -> Target {
+        Target {
+            effect: self.effect,
+            frame: self.frame.clone(),
+            brightness: self.brightness,
+        }
+    }
+
+    pub fn apply(&self, device: &mut Device) -> Result<(), TransportError> {
+        if let Some(transition) = &self.transition {
+            let to = self.target().resolve(device.state());
+            return Fade::new(&device.state().clone(), &to, transition).run(device);
+        }
+        if let Some(brightness) = self.brightness {
+            device.set_brightness(brightness)?;
+        }
+        match (&self.effect, &self.frame) {
+            (Effect::Custom, Some(frame)) => device.set_frame(frame),
+            (effect, _) => device.set_effect(*effect),
+        }
+    }
+
+    /// Applies to a shared device, locking it only for each step of the
+    /// transition.
+    pub fn play(&self, device: &Mutex<Device>) -> Result<(), TransportError> {
+        let Some(transition) = &self.transition else {
+            return self.apply(&mut device.lock().unwrap());
+        };
+        let from = device.lock().unwrap().state().clone();
+        let to = self.target().resolve(&from);
+        Fade::new(&from, &to, transition)
+            .play(device, |duration| {
+                thread::sleep(duration);
+                true
+            })
+            .map(|_| ())
+    }
+}
+
+/// Profiles stored as one JSON file each in a directory.
+#[derive(Debug, Clone)]
+pub struct ProfileStore {
+    files: NamedFileStore,
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self::new(config_dir().join("profiles"))
+    }
+}
+
+impl ProfileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            files: NamedFileStore::new(dir, "json", "profile"),
+        }
+    }
+
+    /// Names of all stored profiles, sorted.
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        self.files.list()
+    }
+
+    pub fn load(&self, name: &str) -> io::Result<Profile> {
+        let text = self.files.read(name)?;
+        serde_json::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, profile: &Profile) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(profile)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.files.write(&profile.name, &text)
+    }
+
+    pub fn delete(&self, name: &str) -> io::Result<()> {
+        self.files.delete(name)
+    }
+}
@@ -1,8 +1,8 @@
-use rusb::{DeviceHandle, UsbContext};
-use std::time::Duration;
+use crate::color::Rgb;
+use crate::commands;
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RazerReport {
     pub status: u8,
     pub transaction_id: u8,
@@ -17,18 +17,37 @@ pub struct RazerReport {
 }
 
 // Constants needed
+pub const NOSTORE: u8 = 0x00;
 pub const VARSTORE: u8 = 0x01;
 pub const BACKLIGHT_LED: u8 = 0x05;
 
+// Extended matrix effect IDs: spectrum is 0x03 and wave 0x04, as the
+// spectrum CRC test has always expected.
+pub const EXT_EFFECT_NONE: u8 = 0x00;
 pub const EXT_EFFECT_STATIC: u8 = 0x01;
 pub const EXT_EFFECT_BREATHING: u8 = 0x02;
-pub const EXT_EFFECT_SPECTRUM: u8 = 0x04;
-pub const EXT_EFFECT_WAVE: u8 = 0x03;
+pub const EXT_EFFECT_SPECTRUM: u8 = 0x03;
+pub const EXT_EFFECT_WAVE: u8 = 0x04;
+pub const EXT_EFFECT_CUSTOM_FRAME: u8 = 0x08;
 
+pub const REPORT_LEN: usize = 90;
+
+pub const RAZER_CMD_NEW: u8 = 0x00;
+pub const RAZER_CMD_BUSY: u8 = 0x01;
 pub const RAZER_CMD_SUCCESSFUL: u8 = 0x02;
+pub const RAZER_CMD_FAILURE: u8 = 0x03;
+pub const RAZER_CMD_TIMEOUT: u8 = 0x04;
 pub const RAZER_CMD_NOT_SUPPORTED: u8 = 0x05;
 
+impl Default for RazerReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RazerReport {
+    /// An empty report with the Ornata V3's transaction id. `Device` swaps in
+    /// the id of the keyboard it actually talks to.
     pub fn new() -> Self {
         Self {
             status: 0x00,
@@ -44,115 +63,99 @@ impl RazerReport {
         }
     }
 
-    pub fn calculate_crc(&mut self) {
-        let mut crc_val: u8 = 0;
-        let report_ptr = self as *const Self as *const u8;
-        for i in 2..=87 {
-            crc_val ^= unsafe { *report_ptr.add(i) };
+    /// Parses a report from the 90 raw bytes exchanged with the device.
+    pub fn from_bytes(bytes: &[u8; REPORT_LEN]) -> Self {
+        let mut arguments = [0u8; 80];
+        arguments.copy_from_slice(&bytes[8..88]);
+        Self {
+            status: bytes[0],
+            transaction_id: bytes[1],
+            remaining_packets: u16::from_ne_bytes([bytes[2], bytes[3]]),
+            protocol_type: bytes[4],
+            data_size: bytes[5],
+            command_class: bytes[6],
+            command_id: bytes[7],
+            arguments,
+            crc: bytes[88],
+            reserved: bytes[89],
         }
-        self.crc = crc_val;
     }
 
-    pub fn static_rgb(red: u8, green: u8, blue: u8) -> Self {
-        let mut report = Self::new();
-        report.command_class = 0x0F;
-        report.command_id = 0x02;
-        report.data_size = 0x09;
+    /// The report laid out exactly as it goes over the wire.
+    pub fn to_bytes(&self) -> [u8; REPORT_LEN] {
+        let mut bytes = [0u8; REPORT_LEN];
+        bytes[0] = self.status;
+        bytes[1] = self.transaction_id;
+        bytes[2..4].copy_from_slice(&self.remaining_packets.to_ne_bytes());
+        bytes[4] = self.protocol_type;
+        bytes[5] = self.data_size;
+        bytes[6] = self.command_class;
+        bytes[7] = self.command_id;
+        bytes[8..88].copy_from_slice(&self.arguments);
+        bytes[88] = self.crc;
+        bytes[89] = self.reserved;
+        bytes
+    }
 
-        report.arguments[0] = VARSTORE;
-        report.arguments[1] = BACKLIGHT_LED;
-        report.arguments[2] = EXT_EFFECT_STATIC;
-        report.arguments[5] = 0x01;
-        report.arguments[6] = red;
-        report.arguments[7] = green;
-        report.arguments[8] = blue;
+    /// The wire bytes as lowercase hex, no separators.
+    pub fn to_hex(&self) -> String {
+        self.to_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
 
-        report.calculate_crc();
-        report
+    /// Parses 90 bytes of hex. Whitespace, `:` and `-` between bytes are
+    /// ignored so dumps from other tools can be pasted as they are.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let digits: Vec<u8> = hex
+            .bytes()
+            .filter(|b| !b.is_ascii_whitespace() && *b != b':' && *b != b'-')
+            .collect();
+        if digits.len() != REPORT_LEN * 2 {
+            return None;
+        }
+        let mut bytes = [0u8; REPORT_LEN];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks_exact(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+        }
+        Some(Self::from_bytes(&bytes))
     }
 
-    pub fn spectrum() -> Self {
-        let mut report = Self::new();
-        report.command_class = 0x0F;
-        report.command_id = 0x02;
-        report.data_size = 0x06;
-        report.arguments[0] = VARSTORE;
-        report.arguments[1] = BACKLIGHT_LED;
-        report.arguments[2] = EXT_EFFECT_SPECTRUM;
-        report.calculate_crc();
-        report
+    pub fn calculate_crc(&mut self) {
+        self.crc = self.expected_crc();
     }
 
-    pub fn breathing(red: u8, green: u8, blue: u8) -> Self {
-        let mut report = Self::new();
-        report.command_class = 0x0F;
-        report.command_id = 0x02;
-        report.data_size = 0x09;
-        report.arguments[0] = VARSTORE;
-        report.arguments[1] = BACKLIGHT_LED;
-        report.arguments[2] = EXT_EFFECT_BREATHING;
-        report.arguments[3] = 0x01;
-        report.arguments[5] = 0x01;
-        report.arguments[6] = red;
-        report.arguments[7] = green;
-        report.arguments[8] = blue;
-        report.calculate_crc();
-        report
+    /// XOR of bytes 2 to 87, which is what the firmware checks `crc` against.
+    pub fn expected_crc(&self) -> u8 {
+        self.to_bytes()[2..=87]
+            .iter()
+            .fold(0, |crc, byte| crc ^ byte)
     }
 
-    pub fn wave(direction: u8, speed: u8) -> Self {
-        let mut report = Self::new();
-        report.command_class = 0x0F;
-        report.command_id = 0x02;
-        report.data_size = 0x06;
-        report.arguments[0] = VARSTORE;
-        report.arguments[1] = BACKLIGHT_LED;
-        report.arguments[2] = EXT_EFFECT_WAVE;
-        report.arguments[3] = direction;
-        report.arguments[4] = speed;
-        report.calculate_crc();
-        report
+    pub fn crc_valid(&self) -> bool {
+        self.crc == self.expected_crc()
     }
 
-    pub fn send<T: UsbContext>(&self, handle: &mut DeviceHandle<T>) -> bool {
-        assert_eq!(
-            std::mem::size_of::<Self>(),
-            90,
-            "RazerReport struct size is not 90 bytes!"
-        );
+    // Most constructors are generated from the table in `commands.rs`.
 
-        let command_bytes =
-            unsafe { std::slice::from_raw_parts(self as *const _ as *const u8, 90) };
-
-        match handle.write_control(
-            0x21,
-            0x09,
-            0x0300,
-            0x02,
-            command_bytes,
-            Duration::from_secs(5),
-        ) {
-            Ok(_) => {
-                std::thread::sleep(Duration::from_micros(600));
-                let mut response_buffer = [0u8; 90];
-                match handle.read_control(
-                    0xA1,
-                    0x01,
-                    0x0300,
-                    0x02,
-                    &mut response_buffer,
-                    Duration::from_secs(5),
-                ) {
-                    Ok(bytes_read) if bytes_read >= 90 => {
-                        let response: RazerReport = unsafe {
-                            std::ptr::read(response_buffer.as_ptr() as *const RazerReport)
-                        };
-                        response.status == RAZER_CMD_SUCCESSFUL
-                    }
-                    _ => false,
-                }
-            }
-            Err(_) => false,
+    /// Uploads one row of the custom frame buffer, starting at `start_col`.
+    /// Nothing is shown until `custom_frame` switches the keyboard over.
+    pub fn custom_frame_row(row: u8, start_col: u8, colors: &[Rgb]) -> Self {
+        assert!(
+            !colors.is_empty() && colors.len() <= 25,
+            "a custom frame row holds 1 to 25 colors"
+        );
+        let stop_col = start_col
+            .checked_add((colors.len() - 1) as u8)
+            .expect("a custom frame row ends at column 255 at the latest");
+        let mut report = commands::find("custom_frame_row")
+            .expect("custom_frame_row is in the command table")
+            .build(&[row, start_col, stop_col]);
+        for (i, color) in colors.iter().enumerate() {
+            report.arguments[5 + i * 3..8 + i * 3].copy_from_slice(&<[u8; 3]>::from(*color));
         }
+        report.calculate_crc();
+        report
     }
 }
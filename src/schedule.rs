@@ -0,0 +1,372 @@
+//! Switches profiles by time of day, e.g. warm and dim at 19:00 and off half
+//! an hour after sunset.
+//!
+//! Rules live in `<config dir>/schedule.json`:
+//!
+//! ```json
+//! {
+//!   "location": { "latitude": 52.52, "longitude": 13.40 },
+//!   "rules": [
+//!     { "at": "19:00", "profile": "evening" },
+//!     { "sunset": 30, "profile": "off", "priority": 1 }
+//!   ]
+//! }
+//! ```
+//!
+//! Sunrise and sunset take an offset in minutes (negative for before) and
+//! need the location. Whichever rule fired last is in effect, so a sunset
+//! rule can land before or after a fixed-time one depending on the season.
+//! Rules firing at the same minute go to the higher priority, then to the
+//! one further down the list.
+//!
+//! Time comes from a `Clock` so tests can run a day in a few lines.
+
+use crate::device::Device;
+use crate::emojis::{EMOJI_STOPWATCH, EMOJI_WARNING};
+use crate::profile::ProfileStore;
+use chrono::{
+    DateTime, Duration as TimeDelta, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Utc,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const SCHEDULE_FILE: &str = "schedule.json";
+
+/// How often a running scheduler looks at the clock.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(20);
+
+/// How far back `Schedule::active` looks for the rule in effect.
+const LOOKBACK_DAYS: i64 = 2;
+
+pub trait Clock: Send {
+    /// The local time, with its UTC offset.
+    fn now(&self) -> DateTime<FixedOffset>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        Local::now().fixed_offset()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    /// Degrees, north positive.
+    pub latitude: f64,
+    /// Degrees, east positive.
+    pub longitude: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum When {
+    /// A fixed local time, written `"19:00"`.
+    #[serde(with = "hhmm")]
+    At(NaiveTime),
+    /// Minutes after sunrise; negative for before.
+    Sunrise(i32),
+    /// Minutes after sunset; negative for before.
+    Sunset(i32),
+}
+
+impl When {
+    /// When this fires on `date`, in local time. Sunrise and sunset don't
+    /// fire without a location or on days the sun doesn't rise or set.
+    pub fn on(
+        &self,
+        date: NaiveDate,
+        location: Option<Location>,
+        offset: FixedOffset,
+    ) -> Option<NaiveDateTime> {
+        let (sun, minutes) = match *self {
+            When::At(time) => return Some(date.and_time(time)),
+            When::Sunrise(minutes) => (sun_times(date, location?)?.0, minutes),
+            When::Sunset(minutes) => (sun_times(date, location?)?.1, minutes),
+        };
+        let local = sun.with_timezone(&offset).naive_local();
+        Some(local + TimeDelta::minutes(minutes.into()))
+    }
+}
+
+impl fmt::Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, minutes) = match *self {
+            When::At(time) => return write!(f, "at {}", time.format("%H:%M")),
+            When::Sunrise(minutes) => ("sunrise", minutes),
+            When::Sunset(minutes) => ("sunset", minutes),
+        };
+        match minutes {
+            0 => write!(f, "at {name}"),
+            m if m < 0 => write!(f, "{} min before {name}", -m),
+            m => write!(f, "{m} min after {name}"),
+        }
+    }
+}
+
+mod hhmm {
+    use chrono::NaiveTime;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&time.format("%H:%M"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        NaiveTime::parse_from_str(&text, "%H:%M")
+            .map_err(|_| serde::de::Error::custom(format!("\"{text}\" is not a time like 19:00")))
+    }
+}
+
+/// Sunrise and sunset on `date` in UTC, or `None` during polar day or night.
+///
+/// Uses the sunrise equation with the usual -0.833° for refraction and the
+/// sun's radius, which is within a minute or two away from the poles.
+pub fn sun_times(date: NaiveDate, location: Location) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let j2000 = NaiveDate::from_ymd_opt(2000, 1, 1)?;
+    let n = (date - j2000).num_days() as f64;
+    let mean_solar_noon = n - location.longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_solar_noon).rem_euclid(360.0);
+    let m = anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.02 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic = (anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit = 2451545.0 + mean_solar_noon + 0.0053 * m.sin() - 0.0069 * (2.0 * ecliptic).sin();
+    let declination = (ecliptic.sin() * 23.4397f64.to_radians().sin()).asin();
+    let latitude = location.latitude.to_radians();
+    let cos_hour_angle = ((-0.833f64).to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let half_day = cos_hour_angle.acos().to_degrees() / 360.0;
+    let utc = |julian: f64| {
+        let seconds = (julian - 2440587.5) * 86400.0;
+        Utc.timestamp_opt(seconds.round() as i64, 0).single()
+    };
+    Some((utc(transit - half_day)?, utc(transit + half_day)?))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    #[serde(flatten)]
+    pub when: When,
+    pub profile: String,
+    /// Wins over rules firing at the same minute with a lower priority.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
+}
+
+fn is_zero(value: &i32) -> bool {
+    *value == 0
+}
+
+impl Rule {
+    pub fn new(when: When, profile: &str) -> Self {
+        Self {
+            when,
+            profile: profile.to_string(),
+            priority: 0,
+        }
+    }
+}
+
+/// A point where a rule takes over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub at: NaiveDateTime,
+    /// Index into `Schedule::rules`.
+    pub rule: usize,
+    pub profile: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl Schedule {
+    /// Reads a schedule file; a missing file is an empty schedule.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        let schedule: Self = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        schedule
+            .validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(schedule)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        self.validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, text)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(location) = self.location {
+            if !(-90.0..=90.0).contains(&location.latitude)
+                || !(-180.0..=180.0).contains(&location.longitude)
+            {
+                return Err("latitude must be within ±90 and longitude within ±180".to_string());
+            }
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.profile.is_empty() {
+                return Err(format!("rule {} has no profile", i + 1));
+            }
+            let solar = matches!(rule.when, When::Sunrise(_) | When::Sunset(_));
+            if solar && self.location.is_none() {
+                return Err(format!("rule {} ({}) needs a location", i + 1, rule.when));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every change after `from` up to and including `to`, in order. Of
+    /// the rules firing at the same minute only the winner is kept.
+    pub fn changes(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        offset: FixedOffset,
+    ) -> Vec<Change> {
+        let mut fired: Vec<(NaiveDateTime, usize)> = Vec::new();
+        // A day earlier too, for sunset offsets that spill past midnight.
+        let mut date = from.date() - TimeDelta::days(1);
+        while date <= to.date() + TimeDelta::days(1) {
+            for (i, rule) in self.rules.iter().enumerate() {
+                if let Some(at) = rule.when.on(date, self.location, offset) {
+                    if at > from && at <= to {
+                        fired.push((at, i));
+                    }
+                }
+            }
+            date += TimeDelta::days(1);
+        }
+        fired.sort_by_key(|&(at, i)| (at, self.rules[i].priority, i));
+
+        let mut changes: Vec<Change> = Vec::new();
+        for (at, rule) in fired {
+            if changes.last().is_some_and(|last| last.at == at) {
+                changes.pop();
+            }
+            changes.push(Change {
+                at,
+                rule,
+                profile: self.rules[rule].profile.clone(),
+            });
+        }
+        changes
+    }
+
+    /// The change in effect at `now`.
+    pub fn active(&self, now: DateTime<FixedOffset>) -> Option<Change> {
+        let local = now.naive_local();
+        self.changes(local - TimeDelta::days(LOOKBACK_DAYS), local, *now.offset())
+            .pop()
+    }
+
+    /// The next `count` changes after `now`, up to a week ahead.
+    pub fn upcoming(&self, now: DateTime<FixedOffset>, count: usize) -> Vec<Change> {
+        let local = now.naive_local();
+        let mut changes = self.changes(local, local + TimeDelta::days(7), *now.offset());
+        changes.truncate(count);
+        changes
+    }
+}
+
+/// Keeps track of which rule was applied last, so each change is acted on
+/// once and a profile picked by hand stays until the next change.
+pub struct Scheduler {
+    schedule: Schedule,
+    clock: Box<dyn Clock>,
+    applied: Option<Change>,
+}
+
+impl Scheduler {
+    pub fn new(schedule: Schedule, clock: impl Clock + 'static) -> Self {
+        Self {
+            schedule,
+            clock: Box::new(clock),
+            applied: None,
+        }
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// Replaces the rules; the next `poll` applies whatever they say now.
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.schedule = schedule;
+        self.applied = None;
+    }
+
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        self.clock.now()
+    }
+
+    /// The profile to switch to, if a different change is in effect than at
+    /// the last call. The first call returns the one in effect right now.
+    pub fn poll(&mut self) -> Option<String> {
+        let active = self.schedule.active(self.clock.now())?;
+        if self.applied.as_ref() == Some(&active) {
+            return None;
+        }
+        let profile = active.profile.clone();
+        self.applied = Some(active);
+        Some(profile)
+    }
+
+    pub fn upcoming(&self, count: usize) -> Vec<Change> {
+        self.schedule.upcoming(self.clock.now(), count)
+    }
+
+    /// Polls on a thread and applies profiles from `profiles` to `device`.
+    pub fn spawn(mut self, device: Arc<Mutex<Device>>, profiles: ProfileStore) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            if let Some(name) = self.poll() {
+                match profiles.load(&name) {
+                    Ok(profile) => match profile.play(&device) {
+                        Ok(()) => tracing::info!(
+                            icon = EMOJI_STOPWATCH,
+                            "Schedule switched to profile \"{name}\""
+                        ),
+                        Err(err) => tracing::warn!(
+                            icon = EMOJI_WARNING,
+                            "Schedule could not apply \"{name}\": {err}"
+                        ),
+                    },
+                    Err(err) => tracing::warn!(
+                        icon = EMOJI_WARNING,
+                        "Schedule could not load \"{name}\": {err}"
+                    ),
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        })
+    }
+}
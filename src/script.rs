@@ -0,0 +1,330 @@
+//! Effects written in [Rhai](https://rhai.rs) instead of Rust.
+//!
+//! A script runs once per frame, starting from a dark keyboard, and works
+//! like a shader: what it draws depends only on the time. It sees
+//!
+//! - `t`: seconds since the effect started,
+//! - `keys`: every key as a map with `row`, `col`, `x` and `y` (its center,
+//!   in key units from the top left, see `Layout`), `w`, `h` and `name`,
+//! - `width` and `height`: the size of the keyboard in key units,
+//!
+//! and can call `set(row, col, color)`, `set(key, color)` and `fill(color)`.
+//! Colors come from `rgb(r, g, b)`, `hsv(hue, saturation, value)` with floats,
+//! `color("teal")` or `color("#ff8800")`, and `mix(a, b, amount)`:
+//!
+//! ```rhai
+//! for key in keys {
+//!     set(key, hsv(key.x * 20.0 + t * 90.0, 1.0, 1.0));
+//! }
+//! ```
+//!
+//! Scripts live in `<config dir>/scripts` as `<name>.rhai` and are picked up
+//! again whenever the file changes. Each frame may only run a bounded number
+//! of operations for a bounded time, and scripts can't import modules or
+//! reach the file system.
+
+use crate::animation::Animation;
+use crate::color::Rgb;
+use crate::config::{config_dir, NamedFileStore};
+use crate::emojis::{EMOJI_CHAT, EMOJI_RELOAD, EMOJI_WARNING};
+use crate::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use crate::layout::Layout;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+pub const SCRIPT_DIR: &str = "scripts";
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// How much work one frame of a script may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Rhai operations (roughly, expressions evaluated).
+    pub operations: u64,
+    /// Wall time, checked every few hundred operations.
+    pub time: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            operations: 500_000,
+            time: Duration::from_millis(20),
+        }
+    }
+}
+
+type Outcome<T> = Result<T, Box<EvalAltResult>>;
+
+fn cell(row: i64, col: i64) -> Outcome<(usize, usize)> {
+    if (0..MATRIX_ROWS as i64).contains(&row) && (0..MATRIX_COLS as i64).contains(&col) {
+        Ok((row as usize, col as usize))
+    } else {
+        Err(format!("no key at row {row}, column {col}").into())
+    }
+}
+
+fn key_cell(key: &Map) -> Outcome<(usize, usize)> {
+    let field = |name: &str| {
+        key.get(name)
+            .and_then(|value| value.as_int().ok())
+            .ok_or_else(|| format!("a key needs an integer `{name}`"))
+    };
+    cell(field("row")?, field("col")?)
+}
+
+/// An engine drawing into `canvas`, stopped once `deadline` passes.
+fn engine(canvas: &Arc<Mutex<Frame>>, deadline: &Arc<Mutex<Instant>>, limits: Limits) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(limits.operations);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(4096);
+    engine.set_max_array_size(4096);
+    engine.set_max_map_size(256);
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+
+    let deadline = Arc::clone(deadline);
+    engine.on_progress(move |operations| {
+        if operations % 256 == 0 && Instant::now() > *deadline.lock().unwrap() {
+            Some(Dynamic::from("time limit"))
+        } else {
+            None
+        }
+    });
+    engine.on_print(|text| tracing::info!(icon = EMOJI_CHAT, "Script: {text}"));
+    engine.on_debug(|text, _, position| {
+        tracing::debug!(icon = EMOJI_CHAT, "Script {position}: {text}")
+    });
+
+    engine
+        .register_type_with_name::<Rgb>("Color")
+        .register_fn("rgb", |r: i64, g: i64, b: i64| {
+            let channel = |value: i64| value.clamp(0, 255) as u8;
+            Rgb::new(channel(r), channel(g), channel(b))
+        })
+        .register_fn("hsv", |hue: f64, saturation: f64, value: f64| {
+            Rgb::from_hsv(hue as f32, saturation as f32, value as f32)
+        })
+        .register_fn("color", |text: &str| -> Outcome<Rgb> {
+            text.parse().map_err(|err| format!("{err}").into())
+        })
+        .register_fn("mix", |a: Rgb, b: Rgb, amount: f64| {
+            a.lerp(b, amount as f32)
+        })
+        .register_get("r", |color: &mut Rgb| color.r as i64)
+        .register_get("g", |color: &mut Rgb| color.g as i64)
+        .register_get("b", |color: &mut Rgb| color.b as i64)
+        .register_fn("to_string", |color: &mut Rgb| color.to_string())
+        .register_fn("to_debug", |color: &mut Rgb| color.to_string());
+
+    let target = Arc::clone(canvas);
+    engine.register_fn(
+        "set",
+        move |row: i64, col: i64, color: Rgb| -> Outcome<()> {
+            let (row, col) = cell(row, col)?;
+            target.lock().unwrap().set(row, col, color);
+            Ok(())
+        },
+    );
+    let target = Arc::clone(canvas);
+    engine.register_fn("set", move |key: Map, color: Rgb| -> Outcome<()> {
+        let (row, col) = key_cell(&key)?;
+        target.lock().unwrap().set(row, col, color);
+        Ok(())
+    });
+    let target = Arc::clone(canvas);
+    engine.register_fn("fill", move |color: Rgb| target.lock().unwrap().fill(color));
+    engine
+}
+
+/// The `keys` array scripts see.
+fn key_maps(layout: &Layout) -> Array {
+    layout
+        .keys
+        .iter()
+        .map(|key| {
+            let (x, y) = key.center();
+            let mut map = Map::new();
+            map.insert("row".into(), (key.row as i64).into());
+            map.insert("col".into(), (key.col as i64).into());
+            map.insert("x".into(), (x as f64).into());
+            map.insert("y".into(), (y as f64).into());
+            map.insert("w".into(), (key.width as f64).into());
+            map.insert("h".into(), (key.height as f64).into());
+            map.insert("name".into(), key.name.clone().unwrap_or_default().into());
+            Dynamic::from_map(map)
+        })
+        .collect()
+}
+
+/// A compiled script with its own sandboxed engine.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    canvas: Arc<Mutex<Frame>>,
+    deadline: Arc<Mutex<Instant>>,
+    limits: Limits,
+    keys: Array,
+    size: (f32, f32),
+}
+
+impl Script {
+    pub fn compile(source: &str, layout: &Layout, limits: Limits) -> Result<Self, String> {
+        let canvas = Arc::new(Mutex::new(Frame::new(MATRIX_ROWS, MATRIX_COLS)));
+        let deadline = Arc::new(Mutex::new(Instant::now()));
+        let engine = engine(&canvas, &deadline, limits);
+        let ast = engine.compile(source).map_err(|err| err.to_string())?;
+        Ok(Self {
+            engine,
+            ast,
+            canvas,
+            deadline,
+            limits,
+            keys: key_maps(layout),
+            size: layout.size(),
+        })
+    }
+
+    /// Runs one frame. On error `frame` is left untouched.
+    pub fn render(&mut self, t: Duration, frame: &mut Frame) -> Result<(), String> {
+        *self.canvas.lock().unwrap() = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        let mut scope = Scope::new();
+        scope.push_constant("t", t.as_secs_f64());
+        scope.push_constant("keys", self.keys.clone());
+        scope.push_constant("width", self.size.0 as f64);
+        scope.push_constant("height", self.size.1 as f64);
+        *self.deadline.lock().unwrap() = Instant::now() + self.limits.time;
+
+        self.engine
+            .run_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|err| match *err {
+                EvalAltResult::ErrorTerminated(..) => {
+                    format!("frame took longer than {:?}", self.limits.time)
+                }
+                EvalAltResult::ErrorTooManyOperations(..) => {
+                    format!("frame ran over {} operations", self.limits.operations)
+                }
+                err => err.to_string(),
+            })?;
+        frame.clone_from(&self.canvas.lock().unwrap());
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Plays a script file, compiling it again whenever it changes. A script
+/// that fails leaves the keys dark until it is fixed.
+pub struct ScriptEffect {
+    path: PathBuf,
+    layout: Layout,
+    limits: Limits,
+    script: Script,
+    modified: Option<SystemTime>,
+    /// The last error logged, so each one is logged once.
+    error: Option<String>,
+}
+
+impl ScriptEffect {
+    pub fn open(path: &Path, layout: Layout, limits: Limits) -> io::Result<Self> {
+        let modified = modified(path);
+        let source = fs::read_to_string(path)?;
+        let script = Script::compile(&source, &layout, limits)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            layout,
+            limits,
+            script,
+            modified,
+            error: None,
+        })
+    }
+
+    fn report(&mut self, error: String) {
+        if self.error.as_ref() != Some(&error) {
+            tracing::warn!(icon = EMOJI_WARNING, "{}: {error}", self.path.display());
+            self.error = Some(error);
+        }
+    }
+
+    /// Compiles the file again if it changed. A broken edit keeps the
+    /// previous version running.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        let compiled = fs::read_to_string(&self.path)
+            .map_err(|err| err.to_string())
+            .and_then(|source| Script::compile(&source, &self.layout, self.limits));
+        match compiled {
+            Ok(script) => {
+                tracing::info!(icon = EMOJI_RELOAD, "Reloaded {}", self.path.display());
+                self.script = script;
+                self.error = None;
+                true
+            }
+            Err(err) => {
+                self.report(err);
+                false
+            }
+        }
+    }
+}
+
+impl Animation for ScriptEffect {
+    fn render(&mut self, t: Duration, frame: &mut Frame) -> Option<Duration> {
+        self.reload_if_changed();
+        match self.script.render(t, frame) {
+            Ok(()) => self.error = None,
+            Err(err) => {
+                frame.fill(Rgb::BLACK);
+                self.report(err);
+            }
+        }
+        Some(FRAME_INTERVAL)
+    }
+}
+
+/// Scripts stored as `<name>.rhai` in a directory.
+#[derive(Debug, Clone)]
+pub struct ScriptStore {
+    files: NamedFileStore,
+}
+
+impl Default for ScriptStore {
+    fn default() -> Self {
+        Self::new(config_dir().join(SCRIPT_DIR))
+    }
+}
+
+impl ScriptStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            files: NamedFileStore::new(dir, "rhai", "script"),
+        }
+    }
+
+    pub fn path(&self, name: &str) -> io::Result<PathBuf> {
+        self.files.path(name)
+    }
+
+    /// Names of all stored scripts, sorted.
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        self.files.list()
+    }
+
+    pub fn open(&self, name: &str, layout: Layout) -> io::Result<ScriptEffect> {
+        ScriptEffect::open(&self.path(name)?, layout, Limits::default())
+    }
+}
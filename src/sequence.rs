@@ -0,0 +1,432 @@
+//! Timelines of lighting states: keyframes at set times, eased into or
+//! jumped to, played once or in a loop, with named triggers along the way.
+//!
+//! Each keyframe only changes what it names, on top of the keyframes before
+//! it and whatever the keys showed when the sequence started. Easing between
+//! two still pictures (off, static, custom) crossfades custom frames; an
+//! animated effect can't be blended, so it holds until its keyframe and only
+//! the brightness eases.
+//!
+//! Like `Fade`, `SequencePlayer` only works out what to send when, which
+//! keeps it testable; `Sequence::play` runs it against a shared device and
+//! can be interrupted.
+
+use crate::config::{config_dir, NamedFileStore};
+use crate::device::{Device, LightingState};
+use crate::effect::Effect;
+use crate::frame::Frame;
+use crate::transition::{
+    millis, mix, optional_millis, still_picture, Easing, Step, Target, STEP_INTERVAL,
+};
+use crate::transport::TransportError;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Directory under the config directory holding one JSON file per sequence.
+pub const SEQUENCE_DIR: &str = "sequences";
+
+/// A lighting state the sequence reaches at `at`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keyframe {
+    #[serde(rename = "at_ms", with = "millis")]
+    pub at: Duration,
+    pub effect: Effect,
+    /// Key colors for `Effect::Custom`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<Frame>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    /// How to get here from the keyframe before; without one the keys jump
+    /// at `at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub easing: Option<Easing>,
+}
+
+impl Keyframe {
+    pub fn new(at: Duration, effect: Effect) -> Self {
+        Self {
+            at,
+            effect,
+            frame: None,
+            brightness: None,
+            easing: None,
+        }
+    }
+
+    pub fn target(&self) -> Target {
+        Target {
+            effect: self.effect,
+            frame: self.frame.clone(),
+            brightness: self.brightness,
+        }
+    }
+}
+
+/// A named point on the timeline, reported every time playback passes it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trigger {
+    #[serde(rename = "at_ms", with = "millis")]
+    pub at: Duration,
+    pub name: String,
+}
+
+/// Stored as `sequences/<name>.json`:
+/// `{"name": "sunrise", "keyframes": [{"at_ms": 0, "effect": {"effect": "off"}}], "loop": true}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sequence {
+    pub name: String,
+    pub keyframes: Vec<Keyframe>,
+    #[serde(rename = "loop", default)]
+    pub looping: bool,
+    /// Defaults to the last keyframe or trigger.
+    #[serde(
+        rename = "length_ms",
+        default,
+        with = "optional_millis",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub length: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<Trigger>,
+}
+
+impl Sequence {
+    pub fn new(name: &str, keyframes: Vec<Keyframe>) -> Self {
+        Self {
+            name: name.to_string(),
+            keyframes,
+            looping: false,
+            length: None,
+            triggers: Vec::new(),
+        }
+    }
+
+    /// How long one pass takes.
+    pub fn length(&self) -> Duration {
+        self.length.unwrap_or_else(|| {
+            let keyframes = self.keyframes.iter().map(|keyframe| keyframe.at);
+            let triggers = self.triggers.iter().map(|trigger| trigger.at);
+            keyframes.chain(triggers).max().unwrap_or_default()
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keyframes.is_empty() {
+            return Err("a sequence needs at least one keyframe".to_string());
+        }
+        if let Some(pair) = self
+            .keyframes
+            .windows(2)
+            .find(|pair| pair[0].at >= pair[1].at)
+        {
+            return Err(format!(
+                "keyframes must be in time order, but {}ms comes after {}ms",
+                pair[1].at.as_millis(),
+                pair[0].at.as_millis()
+            ));
+        }
+        let length = self.length();
+        let last = self.keyframes.iter().map(|keyframe| keyframe.at);
+        let triggers = self.triggers.iter().map(|trigger| trigger.at);
+        if let Some(at) = last.chain(triggers).find(|at| *at > length) {
+            return Err(format!(
+                "{}ms is past the end of the sequence ({}ms)",
+                at.as_millis(),
+                length.as_millis()
+            ));
+        }
+        if self.looping && length.is_zero() {
+            return Err("a looping sequence can't be 0ms long".to_string());
+        }
+        if let Some(trigger) = self.triggers.iter().find(|trigger| trigger.name.is_empty()) {
+            return Err(format!(
+                "the trigger at {}ms needs a name",
+                trigger.at.as_millis()
+            ));
+        }
+        Ok(())
+    }
+
+    /// `t` within one pass: wrapped when looping, otherwise held at the end.
+    fn local(&self, t: Duration) -> Duration {
+        let length = self.length();
+        if !self.looping || length.is_zero() {
+            return t.min(length);
+        }
+        Duration::from_nanos((t.as_nanos() % length.as_nanos()) as u64)
+    }
+
+    /// What the keys show `t` after starting from `base`.
+    pub fn state_at(&self, t: Duration, base: &LightingState) -> LightingState {
+        let t = self.local(t);
+        let mut state = base.clone();
+        let mut since = Duration::ZERO;
+        for keyframe in &self.keyframes {
+            let reached = keyframe.target().resolve(&state);
+            if keyframe.at <= t {
+                state = reached;
+                since = keyframe.at;
+                continue;
+            }
+            // Nothing has moved yet right at the keyframe before.
+            if let Some(easing) = keyframe.easing.filter(|_| t > since) {
+                let progress = (t - since).as_secs_f32() / (keyframe.at - since).as_secs_f32();
+                return between(&state, &reached, easing.apply(progress));
+            }
+            break;
+        }
+        state
+    }
+
+    /// Whether `t` (within a pass) is on the way to an eased keyframe.
+    fn easing_at(&self, t: Duration) -> bool {
+        self.keyframes
+            .iter()
+            .find(|keyframe| keyframe.at > t)
+            .is_some_and(|keyframe| keyframe.easing.is_some())
+    }
+
+    /// Time from `t` (within a pass) to the next keyframe, trigger or end.
+    fn until_next(&self, t: Duration) -> Duration {
+        let keyframes = self.keyframes.iter().map(|keyframe| keyframe.at);
+        let triggers = self.triggers.iter().map(|trigger| trigger.at);
+        keyframes
+            .chain(triggers)
+            .chain([self.length()])
+            .filter(|at| *at > t)
+            .min()
+            .map_or(Duration::ZERO, |at| at - t)
+    }
+
+    /// Names of the triggers passed after `from` up to and including `to`,
+    /// both counted from the start. `None` includes triggers at `to` == 0.
+    fn passed(&self, from: Option<Duration>, to: Duration) -> Vec<String> {
+        let length = self.length();
+        let pass = |t: Duration| {
+            if self.looping && !length.is_zero() {
+                (t.as_nanos() / length.as_nanos()) as u32
+            } else {
+                0
+            }
+        };
+        let mut names = Vec::new();
+        for i in from.map_or(0, pass)..=pass(to) {
+            for trigger in &self.triggers {
+                let at = length * i + trigger.at;
+                if from.is_none_or(|from| at > from) && at <= to {
+                    names.push(trigger.name.clone());
+                }
+            }
+        }
+        names
+    }
+
+    /// Plays on a shared device from whatever it shows, locking it only to
+    /// send each change. Between changes `wait` gets the time until the next
+    /// one and returns false to stop. `on_trigger` gets each trigger's name
+    /// as it is passed. Returns whether the sequence ran to its end, which
+    /// a looping one never does.
+    pub fn play(
+        &self,
+        device: &Mutex<Device>,
+        wait: impl FnMut(Duration) -> bool,
+        on_trigger: impl FnMut(&str),
+    ) -> Result<bool, TransportError> {
+        let base = device.lock().unwrap().state().clone();
+        self.play_with(
+            &base,
+            |step| step.apply(&mut device.lock().unwrap()),
+            wait,
+            on_trigger,
+        )
+    }
+
+    pub fn play_with(
+        &self,
+        base: &LightingState,
+        mut apply: impl FnMut(&Step) -> Result<(), TransportError>,
+        mut wait: impl FnMut(Duration) -> bool,
+        mut on_trigger: impl FnMut(&str),
+    ) -> Result<bool, TransportError> {
+        let mut player = SequencePlayer::new(self, base.clone());
+        let started = Instant::now();
+        loop {
+            let tick = player.advance(started.elapsed());
+            for step in &tick.steps {
+                apply(step)?;
+            }
+            for name in &tick.triggers {
+                on_trigger(name);
+            }
+            match tick.next {
+                Some(next) if !wait(next) => return Ok(false),
+                Some(_) => {}
+                None => return Ok(true),
+            }
+        }
+    }
+
+    /// Plays one pass, even of a looping sequence, sleeping between changes.
+    pub fn run(&self, device: &mut Device) -> Result<(), TransportError> {
+        let once = Sequence {
+            looping: false,
+            ..self.clone()
+        };
+        let base = device.state().clone();
+        once.play_with(
+            &base,
+            |step| step.apply(device),
+            |duration| {
+                thread::sleep(duration);
+                true
+            },
+            |_| {},
+        )
+        .map(|_| ())
+    }
+}
+
+/// `t` of the way from `from` to `to`.
+fn between(from: &LightingState, to: &LightingState, t: f32) -> LightingState {
+    let brightness = mix(from.brightness, to.brightness, t);
+    match (still_picture(from), still_picture(to)) {
+        (Some(start), Some(end))
+            if start != end && start.rows() == end.rows() && start.cols() == end.cols() =>
+        {
+            LightingState {
+                effect: Effect::Custom,
+                frame: start.lerp(&end, t),
+                brightness,
+            }
+        }
+        _ => LightingState {
+            brightness,
+            ..from.clone()
+        },
+    }
+}
+
+/// What to do at one point of playback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tick {
+    /// Changes to send, in order.
+    pub steps: Vec<Step>,
+    /// Triggers passed since the last tick.
+    pub triggers: Vec<String>,
+    /// Time until the next tick, or `None` once the sequence is over.
+    pub next: Option<Duration>,
+}
+
+/// Steps through a sequence, sending only what changed since the last tick.
+pub struct SequencePlayer<'a> {
+    sequence: &'a Sequence,
+    base: LightingState,
+    /// What the keys show after the last tick.
+    shown: LightingState,
+    /// Time of the last tick from the start.
+    reached: Option<Duration>,
+}
+
+impl<'a> SequencePlayer<'a> {
+    pub fn new(sequence: &'a Sequence, base: LightingState) -> Self {
+        Self {
+            sequence,
+            shown: base.clone(),
+            base,
+            reached: None,
+        }
+    }
+
+    /// Moves playback to `t` from the start.
+    pub fn advance(&mut self, t: Duration) -> Tick {
+        let sequence = self.sequence;
+        let state = sequence.state_at(t, &self.base);
+        let steps = changes(&self.shown, &state);
+        self.shown = state;
+        let triggers = sequence.passed(self.reached, t);
+        self.reached = Some(t);
+
+        let local = sequence.local(t);
+        let next = if !sequence.looping && t >= sequence.length() {
+            None
+        } else if sequence.easing_at(local) {
+            Some(STEP_INTERVAL.min(sequence.until_next(local)))
+        } else {
+            Some(sequence.until_next(local))
+        };
+        Tick {
+            steps,
+            triggers,
+            next,
+        }
+    }
+}
+
+/// The steps that take the keys from `from` to `to`.
+fn changes(from: &LightingState, to: &LightingState) -> Vec<Step> {
+    let mut steps = Vec::new();
+    if to.effect == Effect::Custom {
+        if from.effect != Effect::Custom || from.frame != to.frame {
+            steps.push(Step::Frame(to.frame.clone()));
+        }
+    } else if to.effect != from.effect {
+        // Restoring sets the brightness too.
+        return vec![Step::Settle(to.clone())];
+    }
+    if to.brightness != from.brightness {
+        steps.push(Step::Brightness(to.brightness));
+    }
+    steps
+}
+
+/// Sequences stored as one JSON file each in a directory.
+#[derive(Debug, Clone)]
+pub struct SequenceStore {
+    files: NamedFileStore,
+}
+
+impl Default for SequenceStore {
+    fn default() -> Self {
+        Self::new(config_dir().join(SEQUENCE_DIR))
+    }
+}
+
+impl SequenceStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            files: NamedFileStore::new(dir, "json", "sequence"),
+        }
+    }
+
+    /// Names of all stored sequences, sorted.
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        self.files.list()
+    }
+
+    pub fn load(&self, name: &str) -> io::Result<Sequence> {
+        let text = self.files.read(name)?;
+        let sequence: Sequence = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        sequence
+            .validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(sequence)
+    }
+
+    pub fn save(&self, sequence: &Sequence) -> io::Result<()> {
+        sequence
+            .validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let text = serde_json::to_string_pretty(sequence)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.files.write(&sequence.name, &text)
+    }
+
+    pub fn delete(&self, name: &str) -> io::Result<()> {
+        self.files.delete(name)
+    }
+}
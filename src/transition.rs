@@ -0,0 +1,330 @@
+//! Fades from whatever the keys show to a new lighting state.
+//!
+//! When both ends are still pictures (off, static, custom) the fade is a
+//! crossfade of custom frames that settles onto the target effect at the end,
+//! so a static color ends up as the hardware effect rather than a frame. The
+//! keyboard can't tell us where an animated effect (spectrum, wave,
+//! breathing) is in its cycle, so fades from or to one dim the brightness
+//! instead: down on the old effect, switch, up on the new one. When only the
+//! brightness changes, only the brightness fades.
+//!
+//! `Fade::new` only plans the steps, which keeps it testable; `play` runs
+//! them against a shared device and can be interrupted, `run` blocks on a
+//! device the caller already holds.
+
+use crate::device::{Device, LightingState};
+use crate::effect::Effect;
+use crate::frame::Frame;
+use crate::transport::TransportError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time between two steps, about 30 per second.
+pub const STEP_INTERVAL: Duration = Duration::from_millis(33);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    /// Maps progress `t` in 0..=1 onto the curve (cubic for the eased ones).
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::EaseInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Easing::Linear => "linear",
+            Easing::EaseIn => "ease-in",
+            Easing::EaseOut => "ease-out",
+            Easing::EaseInOut => "ease-in-out",
+        }
+    }
+}
+
+impl FromStr for Easing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Easing::ALL
+            .into_iter()
+            .find(|easing| easing.name() == s)
+            .ok_or_else(|| {
+                format!("unknown easing \"{s}\" (linear, ease-in, ease-out, ease-in-out)")
+            })
+    }
+}
+
+impl fmt::Display for Easing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How to get to a new state. Stored in profiles as
+/// `{"duration_ms": 500, "easing": "ease-in-out"}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Transition {
+    #[serde(rename = "duration_ms", with = "millis")]
+    pub duration: Duration,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+impl Transition {
+    pub fn new(duration: Duration, easing: Easing) -> Self {
+        Self { duration, easing }
+    }
+}
+
+pub(crate) mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+pub(crate) mod optional_millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_u64(duration.as_millis() as u64),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<u64>::deserialize(deserializer).map(|ms| ms.map(Duration::from_millis))
+    }
+}
+
+/// `500ms`, `1.5s`, `10m`, or a bare number of milliseconds.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let error = || format!("\"{text}\" is not a duration (e.g. 500ms, 1.5s or 10m)");
+    let (number, scale) = if let Some(ms) = text.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(s) = text.strip_suffix('s') {
+        (s, 1.0)
+    } else if let Some(m) = text.strip_suffix('m') {
+        (m, 60.0)
+    } else {
+        (text, 0.001)
+    };
+    let value: f64 = number.trim().parse().map_err(|_| error())?;
+    // Also turns down negative, infinite and overflowing values.
+    Duration::try_from_secs_f64(value * scale).map_err(|_| error())
+}
+
+/// Where a fade goes. Fields left out keep their current value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub effect: Effect,
+    /// Key colors for `Effect::Custom`.
+    pub frame: Option<Frame>,
+    pub brightness: Option<u8>,
+}
+
+impl Target {
+    pub fn effect(effect: Effect) -> Self {
+        Self {
+            effect,
+            frame: None,
+            brightness: None,
+        }
+    }
+
+    pub fn resolve(&self, current: &LightingState) -> LightingState {
+        LightingState {
+            effect: self.effect,
+            frame: self.frame.clone().unwrap_or_else(|| current.frame.clone()),
+            brightness: self.brightness.unwrap_or(current.brightness),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Frame(Frame),
+    Brightness(u8),
+    /// Switches to the final effect with `Device::restore`.
+    Settle(LightingState),
+}
+
+impl Step {
+    pub fn apply(&self, device: &mut Device) -> Result<(), TransportError> {
+        match self {
+            Step::Frame(frame) => device.set_frame(frame),
+            Step::Brightness(brightness) => device.set_brightness(*brightness),
+            Step::Settle(state) => device.restore(state),
+        }
+    }
+}
+
+/// What the keys show in `state`, if it is a still picture.
+pub(crate) fn still_picture(state: &LightingState) -> Option<Frame> {
+    let (rows, cols) = (state.frame.rows(), state.frame.cols());
+    match state.effect {
+        Effect::Off => Some(Frame::new(rows, cols)),
+        Effect::Static(color) => Some(Frame::filled(rows, cols, color)),
+        Effect::Custom => Some(state.frame.clone()),
+        Effect::Breathing(_) | Effect::Spectrum | Effect::Wave { .. } => None,
+    }
+}
+
+pub(crate) fn mix(from: u8, to: u8, t: f32) -> u8 {
+    (from as f32 + (to as f32 - from as f32) * t).round() as u8
+}
+
+/// A planned fade: steps, each with its time from the start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fade {
+    pub steps: Vec<(Duration, Step)>,
+    pub target: LightingState,
+}
+
+impl Fade {
+    pub fn new(from: &LightingState, to: &LightingState, transition: &Transition) -> Self {
+        let duration = transition.duration;
+        let count = (duration.as_millis() / STEP_INTERVAL.as_millis()).max(1) as u32;
+        let at = |i: u32| duration * i / count;
+        let ease = |i: u32, of: u32| transition.easing.apply(i as f32 / of as f32);
+        let mut steps = Vec::new();
+
+        let same_picture =
+            from.effect == to.effect && (from.effect != Effect::Custom || from.frame == to.frame);
+        match (still_picture(from), still_picture(to)) {
+            _ if duration.is_zero() => steps.push((duration, Step::Settle(to.clone()))),
+            // Only the brightness changes, which works for animated effects too.
+            _ if same_picture => {
+                for i in 1..=count {
+                    let brightness = mix(from.brightness, to.brightness, ease(i, count));
+                    steps.push((at(i), Step::Brightness(brightness)));
+                }
+            }
+            (Some(start), Some(end))
+                if start.rows() == end.rows() && start.cols() == end.cols() =>
+            {
+                // The last step is the settle itself.
+                for i in 1..count {
+                    let t = ease(i, count);
+                    steps.push((at(i), Step::Frame(start.lerp(&end, t))));
+                    if from.brightness != to.brightness {
+                        let brightness = mix(from.brightness, to.brightness, t);
+                        steps.push((at(i), Step::Brightness(brightness)));
+                    }
+                }
+                steps.push((duration, Step::Settle(to.clone())));
+            }
+            _ => {
+                let down = (count / 2).max(1);
+                for i in 1..=down {
+                    let brightness = mix(from.brightness, 0, ease(i, down));
+                    steps.push((at(i), Step::Brightness(brightness)));
+                }
+                let dark = LightingState {
+                    brightness: 0,
+                    ..to.clone()
+                };
+                steps.push((at(down), Step::Settle(dark)));
+                // Settling again at the end would restart an animated effect.
+                let up = (count - down).max(1);
+                for i in 1..=up {
+                    let brightness = mix(0, to.brightness, ease(i, up));
+                    steps.push((at(down + i).min(duration), Step::Brightness(brightness)));
+                }
+            }
+        }
+        Self {
+            steps,
+            target: to.clone(),
+        }
+    }
+
+    /// Runs the steps on time. Before each new step time `wait` gets the
+    /// time left until it (zero when running late) and returns false to
+    /// abandon the fade, in which case the brightness is still set to the
+    /// target's so a dip doesn't leave the keys dark. Returns whether the
+    /// fade finished.
+    pub fn play_with(
+        &self,
+        mut apply: impl FnMut(&Step) -> Result<(), TransportError>,
+        mut wait: impl FnMut(Duration) -> bool,
+    ) -> Result<bool, TransportError> {
+        let started = Instant::now();
+        let mut last = Duration::ZERO;
+        let mut dimmed = false;
+        for (at, step) in &self.steps {
+            if *at > last {
+                if !wait(at.saturating_sub(started.elapsed())) {
+                    if dimmed {
+                        apply(&Step::Brightness(self.target.brightness))?;
+                    }
+                    return Ok(false);
+                }
+                last = *at;
+            }
+            dimmed |= matches!(step, Step::Brightness(_));
+            apply(step)?;
+        }
+        Ok(true)
+    }
+
+    /// Plays on a shared device, locking it only for each step.
+    pub fn play(
+        &self,
+        device: &Mutex<Device>,
+        wait: impl FnMut(Duration) -> bool,
+    ) -> Result<bool, TransportError> {
+        self.play_with(|step| step.apply(&mut device.lock().unwrap()), wait)
+    }
+
+    /// Plays to the end, sleeping between steps.
+    pub fn run(&self, device: &mut Device) -> Result<(), TransportError> {
+        self.play_with(
+            |step| step.apply(device),
+            |duration| {
+                thread::sleep(duration);
+                true
+            },
+        )
+        .map(|_| ())
+    }
+}
@@ -0,0 +1,207 @@
+use crate::descriptor::{DeviceDescriptor, ORNATA_V3};
+use crate::razer_report::{RazerReport, RAZER_CMD_SUCCESSFUL, REPORT_LEN};
+use rusb::{DeviceHandle, UsbContext};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const USB_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportError {
+    Usb(rusb::Error),
+    /// The device answered with fewer than 90 bytes.
+    ShortResponse(usize),
+    /// The device answered, but not with `RAZER_CMD_SUCCESSFUL`.
+    Status(u8),
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::Usb(err) => write!(f, "USB error: {err}"),
+            TransportError::ShortResponse(len) => {
+                write!(f, "short response ({len} of {REPORT_LEN} bytes)")
+            }
+            TransportError::Status(status) => write!(f, "command failed (status 0x{status:02X})"),
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl From<rusb::Error> for TransportError {
+    fn from(err: rusb::Error) -> Self {
+        TransportError::Usb(err)
+    }
+}
+
+/// Anything that can carry a `RazerReport` to a keyboard and bring back its answer.
+pub trait Transport: Send {
+    /// Sends `request` and returns the raw response, whatever its status.
+    fn exchange(&mut self, request: &RazerReport) -> Result<RazerReport, TransportError>;
+
+    /// Like `exchange`, but treats any status other than success as an error.
+    fn send(&mut self, request: &RazerReport) -> Result<RazerReport, TransportError> {
+        let response = self.exchange(request)?;
+        if response.status == RAZER_CMD_SUCCESSFUL {
+            Ok(response)
+        } else {
+            Err(TransportError::Status(response.status))
+        }
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn exchange(&mut self, request: &RazerReport) -> Result<RazerReport, TransportError> {
+        (**self).exchange(request)
+    }
+}
+
+/// Writes `request` as a SET_REPORT and reads the answer back with GET_REPORT
+/// `delay` later.
+pub fn control_exchange<T: UsbContext>(
+    handle: &DeviceHandle<T>,
+    index: u16,
+    delay: Duration,
+    request: &RazerReport,
+) -> Result<RazerReport, TransportError> {
+    handle.write_control(0x21, 0x09, 0x0300, index, &request.to_bytes(), USB_TIMEOUT)?;
+    std::thread::sleep(delay);
+
+    let mut response_buffer = [0u8; REPORT_LEN];
+    let bytes_read =
+        handle.read_control(0xA1, 0x01, 0x0300, index, &mut response_buffer, USB_TIMEOUT)?;
+    if bytes_read < REPORT_LEN {
+        return Err(TransportError::ShortResponse(bytes_read));
+    }
+    Ok(RazerReport::from_bytes(&response_buffer))
+}
+
+pub struct UsbTransport<T: UsbContext> {
+    handle: DeviceHandle<T>,
+    report_index: u16,
+    command_delay: Duration,
+    claimed: bool,
+    /// A kernel driver we detached and owe a reattach.
+    detached: bool,
+}
+
+impl<T: UsbContext> UsbTransport<T> {
+    /// A transport with the Ornata V3's timings.
+    pub fn new(handle: DeviceHandle<T>) -> Self {
+        Self::with_descriptor(handle, &ORNATA_V3)
+    }
+
+    pub fn with_descriptor(handle: DeviceHandle<T>, descriptor: &DeviceDescriptor) -> Self {
+        Self {
+            handle,
+            report_index: descriptor.report_index,
+            command_delay: descriptor.command_delay,
+            claimed: false,
+            detached: false,
+        }
+    }
+
+    /// The interface the reports go to; `wIndex` is its number.
+    fn interface(&self) -> u8 {
+        self.report_index as u8
+    }
+
+    /// Takes the report interface away from the kernel (`usbhid` on Linux
+    /// holds it, and usbfs refuses control transfers to an interface we
+    /// haven't claimed). Dropping the transport releases the interface and
+    /// gives it back to the kernel driver.
+    pub fn claim(&mut self) -> Result<(), rusb::Error> {
+        let interface = self.interface();
+        if rusb::supports_detach_kernel_driver()
+            && self.handle.kernel_driver_active(interface).unwrap_or(false)
+        {
+            self.handle.detach_kernel_driver(interface)?;
+            self.detached = true;
+        }
+        self.handle.claim_interface(interface)?;
+        self.claimed = true;
+        Ok(())
+    }
+}
+
+impl<T: UsbContext> Drop for UsbTransport<T> {
+    fn drop(&mut self) {
+        let interface = self.interface();
+        if self.claimed {
+            let _ = self.handle.release_interface(interface);
+        }
+        if self.detached {
+            let _ = self.handle.attach_kernel_driver(interface);
+        }
+    }
+}
+
+impl<T: UsbContext> Transport for UsbTransport<T> {
+    fn exchange(&mut self, request: &RazerReport) -> Result<RazerReport, TransportError> {
+        control_exchange(&self.handle, self.report_index, self.command_delay, request)
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    requests: Vec<RazerReport>,
+    responses: VecDeque<Result<RazerReport, TransportError>>,
+    latency: Duration,
+}
+
+/// In-memory transport for tests.
+///
+/// Every request is recorded. Responses come from the scripted queue first;
+/// once it is empty the request is echoed back with a success status, which
+/// is what the keyboard does for any command it accepts. Clones share state,
+/// so a test can keep one while the code under test owns the other.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_response(&self, response: RazerReport) {
+        self.state.lock().unwrap().responses.push_back(Ok(response));
+    }
+
+    pub fn push_error(&self, error: TransportError) {
+        self.state.lock().unwrap().responses.push_back(Err(error));
+    }
+
+    /// Makes every exchange take `latency`, like a slow USB round trip.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    /// Every request sent so far, oldest first.
+    pub fn requests(&self) -> Vec<RazerReport> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
+}
+
+impl Transport for MockTransport {
+    fn exchange(&mut self, request: &RazerReport) -> Result<RazerReport, TransportError> {
+        let latency = self.state.lock().unwrap().latency;
+        std::thread::sleep(latency);
+
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(*request);
+        state.responses.pop_front().unwrap_or_else(|| {
+            let mut response = *request;
+            response.status = RAZER_CMD_SUCCESSFUL;
+            Ok(response)
+        })
+    }
+}
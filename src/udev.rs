@@ -0,0 +1,44 @@
+//! udev rule that lets the logged-in user open supported keyboards on Linux
+//! without root.
+
+use crate::descriptor::DEVICES;
+use crate::device::RAZER_VENDOR_ID;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+pub const RULE_PATH: &str = "/etc/udev/rules.d/70-razer-rgb.rules";
+
+/// One line per supported keyboard. `uaccess` hands the device node to
+/// whoever sits at the machine, the same way sound cards and webcams work.
+pub fn rule() -> String {
+    let mut rule =
+        String::from("# Generated by razer-rgb: lets the active user reach the keyboards.\n");
+    for descriptor in DEVICES {
+        rule.push_str(&format!(
+            "# {}\nSUBSYSTEM==\"usb\", ATTRS{{idVendor}}==\"{:04x}\", ATTRS{{idProduct}}==\"{:04x}\", MODE=\"0660\", TAG+=\"uaccess\"\n",
+            descriptor.name, RAZER_VENDOR_ID, descriptor.product_id
+        ));
+    }
+    rule
+}
+
+/// Writes the rule to `path`.
+pub fn install(path: &Path) -> io::Result<()> {
+    fs::write(path, rule())
+}
+
+/// Makes udev pick up new rules and apply them to devices already plugged in.
+pub fn reload() -> io::Result<()> {
+    for args in [&["control", "--reload-rules"][..], &["trigger"][..]] {
+        let status = Command::new("udevadm").args(args).status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "udevadm {} failed ({status})",
+                args.join(" ")
+            )));
+        }
+    }
+    Ok(())
+}
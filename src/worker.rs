@@ -0,0 +1,314 @@
+//! Runs device commands on a background thread so callers (the GUI above all)
+//! never wait on USB.
+//!
+//! Commands are queued on a channel. Whenever the worker gets to the device it
+//! takes everything that piled up and drops commands a later one makes
+//! pointless, e.g. three quick color clicks only send the last color. Every
+//! command ends in exactly one `Event`.
+//!
+//! A `Fade` or a sequence only holds the device for each of its steps, and a
+//! command arriving while it runs cuts it short (`Event::Superseded`) so the
+//! new one doesn't wait for it to finish. A looping sequence plays until
+//! another command comes along.
+
+use crate::device::{Device, LightingState};
+use crate::effect::Effect;
+use crate::frame::Frame;
+use crate::razer_report::RazerReport;
+use crate::sequence::Sequence;
+use crate::transition::{Fade, Target, Transition};
+use crate::transport::TransportError;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryIter};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    SetEffect(Effect),
+    SetFrame(Frame),
+    SetBrightness(u8),
+    Restore(LightingState),
+    /// Sends a report as is; answered with `Event::Exchanged`.
+    Raw(RazerReport),
+    Fade(Target, Transition),
+    /// Plays a sequence, reporting its triggers with `Event::Triggered`.
+    Play(Sequence),
+}
+
+/// Commands in the same slot overwrite each other's result on the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Lighting,
+    Brightness,
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::SetEffect(effect) => effect.name(),
+            Command::SetFrame(_) => "frame",
+            Command::SetBrightness(_) => "brightness",
+            Command::Restore(_) => "restore",
+            Command::Raw(_) => "raw",
+            Command::Fade(target, _) => target.effect.name(),
+            Command::Play(_) => "sequence",
+        }
+    }
+
+    fn slot(&self) -> Slot {
+        match self {
+            Command::SetBrightness(_) => Slot::Brightness,
+            _ => Slot::Lighting,
+        }
+    }
+
+    /// Whether running `later` after `self` leaves no trace of `self`.
+    pub fn superseded_by(&self, later: &Command) -> bool {
+        match (self, later) {
+            // Whoever sent a raw report wants to see its answer.
+            (Command::Raw(_), _) | (_, Command::Raw(_)) => false,
+            (_, Command::Restore(_)) => true,
+            (Command::Restore(_), _) => false,
+            // Switching or fading to custom shows the frame this command
+            // uploads.
+            (Command::SetFrame(_), Command::SetEffect(Effect::Custom)) => false,
+            (
+                Command::SetFrame(_),
+                Command::Fade(
+                    Target {
+                        effect: Effect::Custom,
+                        frame: None,
+                        ..
+                    },
+                    _,
+                ),
+            ) => false,
+            _ => self.slot() == later.slot(),
+        }
+    }
+
+    pub fn run(&self, device: &mut Device) -> Result<(), TransportError> {
+        match self {
+            Command::SetEffect(effect) => device.set_effect(*effect),
+            Command::SetFrame(frame) => device.set_frame(frame),
+            Command::SetBrightness(brightness) => device.set_brightness(*brightness),
+            Command::Restore(state) => device.restore(state),
+            Command::Raw(report) => device.exchange(report).map(|_| ()),
+            Command::Fade(target, transition) => {
+                let to = target.resolve(device.state());
+                Fade::new(&device.state().clone(), &to, transition).run(device)
+            }
+            Command::Play(sequence) => sequence.run(device),
+        }
+    }
+}
+
+/// Splits a batch into the commands worth running and the ones a later
+/// command in the same batch supersedes. Order is preserved.
+pub fn coalesce(batch: Vec<Command>) -> (Vec<Command>, Vec<Command>) {
+    let mut keep = Vec::new();
+    let mut dropped = Vec::new();
+    for (i, command) in batch.iter().enumerate() {
+        if batch[i + 1..]
+            .iter()
+            .any(|later| command.superseded_by(later))
+        {
+            dropped.push(command.clone());
+        } else {
+            keep.push(command.clone());
+        }
+    }
+    (keep, dropped)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Applied {
+        command: &'static str,
+        state: LightingState,
+        latency: Duration,
+    },
+    Failed {
+        command: &'static str,
+        error: TransportError,
+    },
+    /// The answer to a `Command::Raw`, whatever its status.
+    Exchanged {
+        request: RazerReport,
+        response: Result<RazerReport, TransportError>,
+        latency: Duration,
+    },
+    /// Dropped because a newer command made it moot.
+    Superseded { command: &'static str },
+    /// A playing sequence passed one of its triggers. Comes on top of the
+    /// one event that ends the command.
+    Triggered { sequence: String, trigger: String },
+}
+
+pub struct Worker {
+    commands: Option<Sender<Command>>,
+    events: Receiver<Event>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    /// Starts the worker thread. `notify` runs after every event, so a GUI can
+    /// ask for a repaint.
+    pub fn spawn(device: Arc<Mutex<Device>>, notify: impl Fn() + Send + 'static) -> Self {
+        let (command_sender, commands) = mpsc::channel();
+        let (event_sender, events) = mpsc::channel();
+        let thread = thread::spawn(move || run(device, commands, event_sender, notify));
+        Self {
+            commands: Some(command_sender),
+            events,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn submit(&self, command: Command) {
+        if let Some(commands) = &self.commands {
+            let _ = commands.send(command);
+        }
+    }
+
+    /// Events produced since the last call, without blocking.
+    pub fn events(&self) -> TryIter<'_, Event> {
+        self.events.try_iter()
+    }
+
+    /// Blocks until the next event, for callers without a UI loop.
+    pub fn wait_event(&self, timeout: Duration) -> Option<Event> {
+        self.events.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.commands.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(
+    device: Arc<Mutex<Device>>,
+    commands: Receiver<Command>,
+    events: Sender<Event>,
+    notify: impl Fn(),
+) {
+    // Commands that cut a fade short, waiting for the next batch.
+    let mut early = Vec::new();
+    loop {
+        let mut batch = std::mem::take(&mut early);
+        if batch.is_empty() {
+            match commands.recv() {
+                Ok(first) => batch.push(first),
+                Err(_) => break,
+            }
+        }
+        let mut guard = Some(device.lock().unwrap());
+        // Whatever queued up while we waited for the lock joins this batch.
+        batch.extend(commands.try_iter());
+
+        let (keep, dropped) = coalesce(batch);
+        for command in dropped {
+            let _ = events.send(Event::Superseded {
+                command: command.name(),
+            });
+        }
+        for command in keep {
+            let started = Instant::now();
+            let device_guard = guard.get_or_insert_with(|| device.lock().unwrap());
+            if let Command::Raw(request) = command {
+                let response = device_guard.exchange(&request);
+                let _ = events.send(Event::Exchanged {
+                    request,
+                    response,
+                    latency: started.elapsed(),
+                });
+                continue;
+            }
+            let played = match &command {
+                Command::Fade(target, transition) => {
+                    let from = device_guard.state().clone();
+                    let fade = Fade::new(&from, &target.resolve(&from), transition);
+                    guard = None;
+                    Some(fade.play(&device, |duration| {
+                        wait_unless_interrupted(&commands, &mut early, duration)
+                    }))
+                }
+                Command::Play(sequence) => {
+                    guard = None;
+                    Some(sequence.play(
+                        &device,
+                        |duration| wait_unless_interrupted(&commands, &mut early, duration),
+                        |trigger| {
+                            let _ = events.send(Event::Triggered {
+                                sequence: sequence.name.clone(),
+                                trigger: trigger.to_string(),
+                            });
+                            notify();
+                        },
+                    ))
+                }
+                _ => None,
+            };
+            if let Some(result) = played {
+                let device_guard = guard.get_or_insert_with(|| device.lock().unwrap());
+                let event = match result {
+                    Ok(true) => Event::Applied {
+                        command: command.name(),
+                        state: device_guard.state().clone(),
+                        latency: started.elapsed(),
+                    },
+                    Ok(false) => Event::Superseded {
+                        command: command.name(),
+                    },
+                    Err(error) => Event::Failed {
+                        command: command.name(),
+                        error,
+                    },
+                };
+                let _ = events.send(event);
+                continue;
+            }
+            let device_guard = guard.get_or_insert_with(|| device.lock().unwrap());
+            let event = match command.run(device_guard) {
+                Ok(()) => Event::Applied {
+                    command: command.name(),
+                    state: device_guard.state().clone(),
+                    latency: started.elapsed(),
+                },
+                Err(error) => Event::Failed {
+                    command: command.name(),
+                    error,
+                },
+            };
+            let _ = events.send(event);
+        }
+        drop(guard);
+        notify();
+    }
+}
+
+/// Sleeps for `duration` unless a command arrives first, in which case it is
+/// kept for the next batch and the answer is false.
+fn wait_unless_interrupted(
+    commands: &Receiver<Command>,
+    early: &mut Vec<Command>,
+    duration: Duration,
+) -> bool {
+    match commands.recv_timeout(duration) {
+        Ok(command) => {
+            early.push(command);
+            false
+        }
+        Err(RecvTimeoutError::Timeout) => true,
+        Err(RecvTimeoutError::Disconnected) => {
+            thread::sleep(duration);
+            true
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use razer_rgb_mac::async_client::*;
+use razer_rgb_mac::razer_report::*;
+use razer_rgb_mac::transport::{MockTransport, Transport, TransportError};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_apply_sends_report() {
+        let mock = MockTransport::new();
+        let client = AsyncClient::new(mock.clone());
+
+        client.apply(RazerReport::spectrum()).await.unwrap();
+        assert_eq!(
+            mock.requests()[0].to_bytes(),
+            RazerReport::spectrum().to_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_parses_scripted_responses() {
+        let mock = MockTransport::new();
+        let client = AsyncClient::new(mock.clone());
+
+        let mut firmware = RazerReport::get_firmware();
+        firmware.status = RAZER_CMD_SUCCESSFUL;
+        firmware.arguments[0] = 1;
+        firmware.arguments[1] = 3;
+        mock.push_response(firmware);
+
+        let mut serial = RazerReport::get_serial();
+        serial.status = RAZER_CMD_SUCCESSFUL;
+        serial.arguments[..6].copy_from_slice(b"PM2143");
+        mock.push_response(serial);
+
+        assert_eq!(client.firmware_version().await.unwrap(), (1, 3));
+        assert_eq!(client.serial().await.unwrap(), "PM2143");
+    }
+
+    #[tokio::test]
+    async fn test_failed_status_is_an_error() {
+        let mock = MockTransport::new();
+        let client = AsyncClient::new(mock.clone());
+
+        let mut response = RazerReport::get_brightness();
+        response.status = RAZER_CMD_NOT_SUPPORTED;
+        mock.push_response(response);
+        assert_eq!(
+            client.brightness().await,
+            Err(AsyncError::Transport(TransportError::Status(
+                RAZER_CMD_NOT_SUPPORTED
+            )))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_timeout() {
+        let mock = MockTransport::new();
+        mock.set_latency(Duration::from_millis(300));
+        let client = AsyncClient::new(mock.clone()).with_timeout(Duration::from_millis(50));
+
+        assert_eq!(
+            client.apply(RazerReport::spectrum()).await,
+            Err(AsyncError::Timeout)
+        );
+    }
+
+    /// Holds the first exchange until the test releases it.
+    struct GatedTransport {
+        inner: MockTransport,
+        started: Option<Sender<()>>,
+        release: Receiver<()>,
+    }
+
+    impl Transport for GatedTransport {
+        fn exchange(&mut self, request: &RazerReport) -> Result<RazerReport, TransportError> {
+            if let Some(started) = self.started.take() {
+                started.send(()).unwrap();
+                self.release.recv().unwrap();
+            }
+            self.inner.exchange(request)
+        }
+    }
+
+    #[test]
+    fn test_dropped_call_never_reaches_device() {
+        let mock = MockTransport::new();
+        let (started, wait_started) = mpsc::channel();
+        let (release, gate) = mpsc::channel();
+        let client = AsyncClient::new(GatedTransport {
+            inner: mock.clone(),
+            started: Some(started),
+            release: gate,
+        });
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        runtime.block_on(async {
+            let slow = tokio::spawn({
+                let client = client.clone();
+                async move { client.apply(RazerReport::spectrum()).await }
+            });
+            wait_started.recv().unwrap();
+
+            // Queued behind the slow call, then given up on.
+            let queued = tokio::time::timeout(
+                Duration::ZERO,
+                client.apply(RazerReport::static_rgb(255, 0, 0)),
+            )
+            .await;
+            assert!(queued.is_err());
+
+            release.send(()).unwrap();
+            slow.await.unwrap().unwrap();
+        });
+        // Waits for the dropped call's blocking task to give up the device.
+        drop(runtime);
+        assert_eq!(mock.requests().len(), 1);
+    }
+}
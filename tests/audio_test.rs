@@ -0,0 +1,167 @@
+use razer_rgb_mac::animation::{self, Animation};
+use razer_rgb_mac::audio::*;
+use razer_rgb_mac::color::{Gradient, Rgb};
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::emulator::Emulator;
+use razer_rgb_mac::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use std::f32::consts::PI;
+use std::io::Cursor;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44100;
+    /// Where 1 kHz lands: columns run from 40 Hz to 16 kHz on a log scale.
+    const KHZ_COLUMN: usize = 11;
+
+    /// Samples of a sine, followed by `silence` seconds of nothing.
+    fn tone(hz: f32, amplitude: f32, seconds: f32, silence: f32) -> Vec<f32> {
+        let sound = (seconds * RATE as f32) as usize;
+        let quiet = (silence * RATE as f32) as usize;
+        (0..sound)
+            .map(|i| amplitude * (2.0 * PI * hz * i as f32 / RATE as f32).sin())
+            .chain(std::iter::repeat_n(0.0, quiet))
+            .collect()
+    }
+
+    /// 16-bit WAV with every channel carrying `samples`.
+    fn wav(samples: &[f32], channels: u16) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate: RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for &sample in samples {
+            for _ in 0..channels {
+                writer.write_sample((sample * 32767.0) as i16).unwrap();
+            }
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    fn effect(samples: &[f32], settings: SpectrumSettings) -> SpectrumEffect {
+        let source = WavSource::new(Cursor::new(wav(samples, 2))).unwrap();
+        SpectrumEffect::new(source, settings)
+    }
+
+    fn tallest(levels: &[f32]) -> usize {
+        (0..levels.len())
+            .max_by(|&a, &b| levels[a].total_cmp(&levels[b]))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_tone_lights_its_column() {
+        let mut effect = effect(&tone(1000.0, 1.0, 0.2, 0.0), SpectrumSettings::default());
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        for _ in 0..3 {
+            effect.render(Duration::ZERO, &mut frame).unwrap();
+        }
+
+        let levels = effect.levels();
+        assert_eq!(levels.len(), MATRIX_COLS);
+        assert_eq!(tallest(levels), KHZ_COLUMN);
+        assert!(levels[KHZ_COLUMN] > 0.9, "{levels:?}");
+        assert!(levels[..KHZ_COLUMN - 2].iter().all(|&level| level < 0.5));
+        assert!(levels[KHZ_COLUMN + 3..].iter().all(|&level| level < 0.5));
+        // A full bar, bottom row first.
+        let palette = Gradient::default();
+        assert_eq!(frame.get(MATRIX_ROWS - 1, KHZ_COLUMN), palette.at(0.0));
+        assert_eq!(frame.get(0, KHZ_COLUMN), palette.at(1.0));
+        assert_eq!(frame.get(0, 0), Rgb::BLACK);
+    }
+
+    #[test]
+    fn test_gain_lifts_quiet_input() {
+        let quiet = tone(1000.0, 0.01, 0.2, 0.0);
+        let level = |gain| {
+            let mut effect = effect(
+                &quiet,
+                SpectrumSettings {
+                    gain,
+                    ..Default::default()
+                },
+            );
+            for _ in 0..3 {
+                effect.advance(MATRIX_COLS).unwrap();
+            }
+            effect.levels()[KHZ_COLUMN]
+        };
+        // -40 dB is a third of the 60 dB range; 100x gain makes it full scale.
+        assert!((level(1.0) - 1.0 / 3.0).abs() < 0.05, "{}", level(1.0));
+        assert!(level(100.0) > 0.95);
+    }
+
+    #[test]
+    fn test_bars_decay_after_silence() {
+        let samples = tone(1000.0, 1.0, 0.1, 1.0);
+        let mut effect = effect(
+            &samples,
+            SpectrumSettings {
+                decay: 1.0,
+                ..Default::default()
+            },
+        );
+        for _ in 0..3 {
+            effect.advance(MATRIX_COLS).unwrap();
+        }
+        let peak = effect.levels()[KHZ_COLUMN];
+        let mut frames = 0;
+        // The tone leaves the FFT window within a few frames; from then on
+        // the bar falls at one full height per second.
+        while effect.levels()[KHZ_COLUMN] > 0.0 {
+            assert!(effect.advance(MATRIX_COLS).unwrap());
+            frames += 1;
+        }
+        let seconds = frames as f32 * 0.033;
+        assert!(seconds > peak * 0.9 && seconds < peak + 0.2, "{seconds}s");
+    }
+
+    #[test]
+    fn test_pcm_source_downmixes_whole_frames() {
+        let mut bytes = Vec::new();
+        for sample in [16384i16, 0, -32768, -32768, 100] {
+            bytes.extend(sample.to_le_bytes());
+        }
+        let mut source = PcmSource::new(Cursor::new(bytes), 8000, 2);
+        assert_eq!(source.sample_rate(), 8000);
+
+        let mut buf = [0.0; 4];
+        // The odd sample at the end is half a frame and is dropped.
+        assert_eq!(source.read(&mut buf).unwrap(), 2);
+        assert_eq!(buf[..2], [0.25, -1.0]);
+        assert_eq!(source.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_plays_until_the_stream_ends() {
+        assert!(WavSource::new(Cursor::new(b"not a wav".to_vec())).is_err());
+
+        let emulator = Emulator::new();
+        let device = Mutex::new(Device::new(emulator.clone()));
+        let settings = SpectrumSettings {
+            palette: Gradient::new(vec![Rgb::BLUE]).unwrap(),
+            ..Default::default()
+        };
+        let mut effect = effect(&tone(1000.0, 1.0, 0.2, 0.0), settings);
+        let mut frames = 0;
+        animation::play(&device, &mut effect, |next| {
+            assert_eq!(next, FRAME_INTERVAL);
+            frames += 1;
+            true
+        })
+        .unwrap();
+
+        // 0.2 s is 8820 samples, 1455 per frame.
+        assert_eq!(frames, 7);
+        let frame = emulator.state().frame;
+        assert_eq!(frame.get(MATRIX_ROWS - 1, KHZ_COLUMN), Rgb::BLUE);
+    }
+}
@@ -0,0 +1,113 @@
+use razer_rgb_mac::capture::*;
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::emulator::Emulator;
+use razer_rgb_mac::razer_report::*;
+use razer_rgb_mac::transport::{MockTransport, Transport, TransportError};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &Path) -> Vec<CaptureEntry> {
+        read_capture(BufReader::new(File::open(path).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn test_recorder_logs_every_exchange() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let mut recorder = Recorder::create(Emulator::new(), &path).unwrap();
+
+        let response = recorder.send(&RazerReport::static_rgb(255, 0, 0)).unwrap();
+        recorder.send(&RazerReport::get_firmware()).unwrap();
+
+        let entries = read(&path);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].request().unwrap().to_bytes(),
+            RazerReport::static_rgb(255, 0, 0).to_bytes()
+        );
+        assert_eq!(
+            entries[0].result().unwrap().unwrap().to_bytes(),
+            response.to_bytes()
+        );
+        assert!(entries[0].timestamp_us <= entries[1].timestamp_us);
+    }
+
+    #[test]
+    fn test_errors_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("errors.jsonl");
+        let mock = MockTransport::new();
+        mock.push_error(TransportError::Usb(rusb::Error::Pipe));
+        mock.push_error(TransportError::ShortResponse(12));
+        let mut recorder = Recorder::create(mock, &path).unwrap();
+        let _ = recorder.exchange(&RazerReport::spectrum());
+        let _ = recorder.exchange(&RazerReport::spectrum());
+
+        let mut replay = Replay::open(&path).unwrap();
+        assert_eq!(
+            replay.exchange(&RazerReport::spectrum()).unwrap_err(),
+            TransportError::Usb(rusb::Error::Pipe)
+        );
+        assert_eq!(
+            replay.exchange(&RazerReport::spectrum()).unwrap_err(),
+            TransportError::ShortResponse(12)
+        );
+        assert_eq!(
+            replay.exchange(&RazerReport::spectrum()).unwrap_err(),
+            TransportError::Usb(rusb::Error::NoDevice)
+        );
+    }
+
+    #[test]
+    fn test_replay_reports_mismatches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let mut device = Device::new(Recorder::create(Emulator::new(), &path).unwrap());
+        device
+            .set_effect(Effect::Static(Rgb::new(0, 0, 255)))
+            .unwrap();
+        device.set_brightness(0x80).unwrap();
+
+        let replay = Replay::open(&path).unwrap();
+        let mut device = Device::new(replay.clone());
+        device
+            .set_effect(Effect::Static(Rgb::new(0, 0, 255)))
+            .unwrap();
+        device.set_brightness(0x40).unwrap();
+
+        assert_eq!(replay.remaining(), 0);
+        let mismatches = replay.mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].0, 1);
+        assert_eq!(mismatches[0].1.arguments[2], 0x40);
+    }
+
+    #[test]
+    fn test_fixture_replays_cleanly() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/captures/static_then_wave.jsonl");
+        let replay = Replay::open(path).unwrap();
+        let mut device = Device::new(replay.clone());
+
+        device
+            .set_effect(Effect::Static(Rgb::new(255, 64, 0)))
+            .unwrap();
+        device
+            .set_effect(Effect::Wave {
+                direction: 0x01,
+                speed: 0x28,
+            })
+            .unwrap();
+        device.set_brightness(0xC0).unwrap();
+
+        assert!(replay.mismatches().is_empty());
+        assert_eq!(replay.remaining(), 0);
+    }
+}
@@ -0,0 +1,169 @@
+use razer_rgb_mac::chroma::*;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::http;
+use razer_rgb_mac::razer_report::*;
+use razer_rgb_mac::transport::MockTransport;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_server(timeout: Duration) -> (SocketAddr, MockTransport) {
+        let mock = MockTransport::new();
+        let device = Arc::new(Mutex::new(Device::new(mock.clone())));
+        let server = ChromaServer::bind("127.0.0.1:0", device)
+            .unwrap()
+            .with_session_timeout(timeout);
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+        (addr, mock)
+    }
+
+    fn call(addr: SocketAddr, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+        let (status, body) = http::request(addr, method, path, body.as_ref()).unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    /// Starts a session and returns its path prefix.
+    fn init(addr: SocketAddr) -> String {
+        let (status, body) = call(
+            addr,
+            "POST",
+            "/razer/chromasdk",
+            Some(json!({ "title": "test", "device_supported": ["keyboard"] })),
+        );
+        assert_eq!(status, 200);
+        let uri = body["uri"].as_str().unwrap();
+        assert!(uri.starts_with(&format!("http://{addr}/chromasdk/")));
+        uri.trim_start_matches(&format!("http://{addr}"))
+            .to_string()
+    }
+
+    #[test]
+    fn test_static_effect_uses_bgr_color() {
+        let (addr, mock) = start_server(SESSION_TIMEOUT);
+        let session = init(addr);
+
+        let (status, body) = call(
+            addr,
+            "PUT",
+            &format!("{session}/keyboard"),
+            Some(json!({ "effect": "CHROMA_STATIC", "param": { "color": 0x00FF8000u32 } })),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["result"], RZRESULT_SUCCESS);
+        assert_eq!(
+            mock.requests().last().unwrap().to_bytes(),
+            RazerReport::static_rgb(0x00, 0x80, 0xFF).to_bytes()
+        );
+    }
+
+    #[test]
+    fn test_custom_effect_uploads_frame() {
+        let (addr, mock) = start_server(SESSION_TIMEOUT);
+        let session = init(addr);
+
+        let mut grid = vec![vec![0u32; 22]; 6];
+        grid[3][4] = 0x000000FF;
+        let (status, _) = call(
+            addr,
+            "PUT",
+            &format!("{session}/keyboard"),
+            Some(json!({ "effect": "CHROMA_CUSTOM", "param": grid })),
+        );
+        assert_eq!(status, 200);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 7);
+        assert_eq!(requests[3].arguments[2], 3);
+        assert_eq!(&requests[3].arguments[5 + 4 * 3..8 + 4 * 3], &[0xFF, 0, 0]);
+        assert_eq!(requests[6].arguments[2], EXT_EFFECT_CUSTOM_FRAME);
+    }
+
+    #[test]
+    fn test_stored_effect_and_heartbeat() {
+        let (addr, mock) = start_server(SESSION_TIMEOUT);
+        let session = init(addr);
+
+        let (_, created) = call(
+            addr,
+            "POST",
+            &format!("{session}/keyboard"),
+            Some(json!({ "effect": "CHROMA_NONE" })),
+        );
+        assert!(mock.requests().is_empty());
+
+        let (status, _) = call(
+            addr,
+            "PUT",
+            &format!("{session}/effect"),
+            Some(json!({ "id": created["id"] })),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(mock.requests()[0].arguments[2], EXT_EFFECT_NONE);
+
+        let (_, tick) = call(addr, "PUT", &format!("{session}/heartbeat"), None);
+        assert_eq!(tick["tick"], 1);
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let (addr, _) = start_server(SESSION_TIMEOUT);
+        let session = init(addr);
+
+        let (status, body) = call(
+            addr,
+            "PUT",
+            &format!("{session}/keyboard"),
+            Some(json!({ "effect": "CHROMA_CUSTOM", "param": [[0]] })),
+        );
+        assert_eq!(status, 400);
+        assert_eq!(body["result"], RZRESULT_INVALID_PARAMETER);
+
+        let (status, _) = call(addr, "PUT", "/chromasdk/999/heartbeat", None);
+        assert_eq!(status, 404);
+    }
+
+    #[test]
+    fn test_uninit_restores_previous_lighting() {
+        let (addr, mock) = start_server(SESSION_TIMEOUT);
+        let session = init(addr);
+        call(
+            addr,
+            "PUT",
+            &format!("{session}/keyboard"),
+            Some(json!({ "effect": "CHROMA_STATIC", "param": { "color": 255 } })),
+        );
+
+        let (status, _) = call(addr, "DELETE", &session, None);
+        assert_eq!(status, 200);
+        assert_eq!(
+            mock.requests().last().unwrap().to_bytes(),
+            RazerReport::spectrum().to_bytes()
+        );
+    }
+
+    #[test]
+    fn test_session_expires_without_heartbeat() {
+        let (addr, mock) = start_server(Duration::from_millis(100));
+        let session = init(addr);
+        call(
+            addr,
+            "PUT",
+            &format!("{session}/keyboard"),
+            Some(json!({ "effect": "CHROMA_STATIC", "param": { "color": 255 } })),
+        );
+
+        std::thread::sleep(Duration::from_millis(400));
+        assert_eq!(
+            mock.requests().last().unwrap().to_bytes(),
+            RazerReport::spectrum().to_bytes()
+        );
+        let (status, _) = call(addr, "PUT", &format!("{session}/heartbeat"), None);
+        assert_eq!(status, 404);
+    }
+}
@@ -0,0 +1,115 @@
+use razer_rgb_mac::color::*;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::frame::Frame;
+use razer_rgb_mac::transport::MockTransport;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_and_names() {
+        assert_eq!("#FF8000".parse(), Ok(Rgb::new(255, 128, 0)));
+        assert_eq!("ff8000".parse(), Ok(Rgb::new(255, 128, 0)));
+        assert_eq!("#f80".parse(), Ok(Rgb::new(255, 136, 0)));
+        assert_eq!("RebeccaPurple".parse(), Ok(Rgb::new(102, 51, 153)));
+        assert_eq!(" orange ".parse(), Ok(Rgb::new(255, 165, 0)));
+        assert!("#12345".parse::<Rgb>().is_err());
+        assert!("not a color".parse::<Rgb>().is_err());
+
+        assert_eq!(Rgb::new(255, 128, 0).to_string(), "#FF8000");
+        assert_eq!(Rgb::new(255, 165, 0).name(), Some("orange"));
+        assert_eq!(Rgb::new(1, 2, 3).name(), None);
+    }
+
+    #[test]
+    fn test_hsv_and_hsl_round_trip() {
+        assert_eq!(Rgb::from_hsv(0.0, 1.0, 1.0), Rgb::RED);
+        assert_eq!(Rgb::from_hsv(120.0, 1.0, 1.0), Rgb::GREEN);
+        assert_eq!(Rgb::from_hsv(-120.0, 1.0, 1.0), Rgb::BLUE);
+        assert_eq!(Rgb::from_hsl(0.0, 0.0, 1.0), Rgb::WHITE);
+        assert_eq!(Rgb::from_hsl(30.0, 1.0, 0.5), Rgb::new(255, 128, 0));
+
+        for color in [
+            Rgb::new(12, 200, 99),
+            Rgb::new(255, 128, 0),
+            Rgb::new(40, 40, 40),
+        ] {
+            let (h, s, v) = color.to_hsv();
+            assert_eq!(Rgb::from_hsv(h, s, v), color);
+            let (h, s, l) = color.to_hsl();
+            assert_eq!(Rgb::from_hsl(h, s, l), color);
+        }
+    }
+
+    #[test]
+    fn test_json_accepts_channels_hex_and_names() {
+        let effect: Effect =
+            serde_json::from_str(r##"{"effect":"static","color":"#00FF00"}"##).unwrap();
+        assert_eq!(effect, Effect::Static(Rgb::GREEN));
+        let effect: Effect =
+            serde_json::from_str(r#"{"effect":"breathing","color":"navy"}"#).unwrap();
+        assert_eq!(effect, Effect::Breathing(Rgb::new(0, 0, 128)));
+        let effect: Effect =
+            serde_json::from_str(r#"{"effect":"static","color":[1,2,3]}"#).unwrap();
+        assert_eq!(effect, Effect::Static(Rgb::new(1, 2, 3)));
+        assert!(serde_json::from_str::<Effect>(r#"{"effect":"static","color":"nope"}"#).is_err());
+
+        // Still written as channels, so older readers keep working.
+        assert_eq!(
+            serde_json::to_string(&Effect::Static(Rgb::new(1, 2, 3))).unwrap(),
+            r#"{"effect":"static","color":[1,2,3]}"#
+        );
+    }
+
+    #[test]
+    fn test_device_sends_corrected_colors() {
+        let correction = ColorCorrection {
+            gamma: 2.0,
+            white_balance: [1.0, 0.5, 1.0],
+        };
+        assert_eq!(
+            correction.apply(Rgb::new(255, 255, 128)),
+            Rgb::new(255, 128, 64)
+        );
+        assert_eq!(
+            ColorCorrection::IDENTITY.apply(Rgb::new(7, 8, 9)),
+            Rgb::new(7, 8, 9)
+        );
+
+        let mock = MockTransport::new();
+        let mut device = Device::new(mock.clone());
+        device.set_correction(correction);
+        device.set_effect(Effect::Static(Rgb::WHITE)).unwrap();
+        let mut frame = Frame::default();
+        frame.set(0, 0, Rgb::new(255, 255, 128));
+        device.set_frame(&frame).unwrap();
+
+        let requests = mock.requests();
+        assert_eq!(&requests[0].arguments[6..9], &[255, 128, 255]);
+        assert_eq!(&requests[1].arguments[5..8], &[255, 128, 64]);
+        // The cached state keeps what was asked for.
+        assert_eq!(device.frame().get(0, 0), Rgb::new(255, 255, 128));
+    }
+
+    #[test]
+    fn test_load_corrections_by_product_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CORRECTION_FILE);
+        assert!(load_corrections(&path).unwrap().is_empty());
+
+        std::fs::write(
+            &path,
+            r#"{"02A1": {"gamma": 2.2}, "0x025d": {"white_balance": [1.0, 0.9, 0.8]}}"#,
+        )
+        .unwrap();
+        let corrections = load_corrections(&path).unwrap();
+        assert_eq!(corrections[&0x02A1].gamma, 2.2);
+        assert_eq!(corrections[&0x02A1].white_balance, [1.0, 1.0, 1.0]);
+        assert_eq!(corrections[&0x025D].gamma, 1.0);
+
+        std::fs::write(&path, r#"{"keyboard": {}}"#).unwrap();
+        assert!(load_corrections(&path).is_err());
+    }
+}
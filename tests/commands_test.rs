@@ -0,0 +1,108 @@
+use razer_rgb_mac::commands::*;
+use razer_rgb_mac::decode::decode;
+use razer_rgb_mac::emulator::Emulator;
+use razer_rgb_mac::razer_report::*;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn golden_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/commands.txt")
+    }
+
+    /// Distinct, recognizable values for a command's parameters.
+    fn sample_params(def: &CommandDef) -> Vec<u8> {
+        (1..=def.params.len() as u8).map(|i| i * 0x11).collect()
+    }
+
+    /// Every table entry built with `sample_params` must match the bytes in
+    /// `tests/golden/commands.txt`. Run with `UPDATE_GOLDEN=1` to write the
+    /// file after adding a command, then review the diff.
+    #[test]
+    fn test_commands_match_golden() {
+        let built: BTreeMap<&str, String> = COMMANDS
+            .iter()
+            .map(|def| (def.name, def.build(&sample_params(def)).to_hex()))
+            .collect();
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            let lines: Vec<String> = built
+                .iter()
+                .map(|(name, hex)| format!("{name} {hex}"))
+                .collect();
+            std::fs::write(golden_path(), lines.join("\n") + "\n").unwrap();
+        }
+
+        let golden_file = std::fs::read_to_string(golden_path()).unwrap();
+        let golden: BTreeMap<&str, &str> = golden_file
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .collect();
+        for (name, hex) in &built {
+            match golden.get(name) {
+                Some(expected) => assert_eq!(hex, expected, "{name} differs from golden"),
+                None => panic!("{name} has no golden entry; run with UPDATE_GOLDEN=1"),
+            }
+        }
+        assert_eq!(golden.len(), built.len(), "golden has stale entries");
+    }
+
+    #[test]
+    fn test_generated_builders_match_table() {
+        let cases = [
+            ("none", RazerReport::none(), vec![]),
+            (
+                "static_rgb",
+                RazerReport::static_rgb(1, 2, 3),
+                vec![1, 2, 3],
+            ),
+            ("breathing", RazerReport::breathing(4, 5, 6), vec![4, 5, 6]),
+            ("spectrum", RazerReport::spectrum(), vec![]),
+            ("wave", RazerReport::wave(1, 0x28), vec![1, 0x28]),
+            ("custom_frame", RazerReport::custom_frame(), vec![]),
+            ("brightness", RazerReport::brightness(0x80), vec![0x80]),
+            ("get_brightness", RazerReport::get_brightness(), vec![]),
+            ("get_firmware", RazerReport::get_firmware(), vec![]),
+            ("get_serial", RazerReport::get_serial(), vec![]),
+            (
+                "set_device_mode",
+                RazerReport::set_device_mode(3, 0),
+                vec![3, 0],
+            ),
+            ("get_device_mode", RazerReport::get_device_mode(), vec![]),
+        ];
+        for (name, report, params) in cases {
+            assert_eq!(find(name).unwrap().build(&params), report, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_lookup_is_unambiguous() {
+        for def in COMMANDS {
+            let report = def.build(&sample_params(def));
+            assert_eq!(lookup(&report).unwrap().name, def.name);
+            assert_eq!(decode(&report).command, Some(def.description));
+        }
+    }
+
+    #[test]
+    fn test_response_fields() {
+        let emulator = Emulator::new();
+        let def = find("get_firmware").unwrap();
+        let response = emulator.respond(&RazerReport::get_firmware());
+        assert_eq!(
+            def.read_returns(&response),
+            vec![("major", Value::Byte(1)), ("minor", Value::Byte(2))]
+        );
+
+        emulator.respond(&RazerReport::set_device_mode(0x03, 0x00));
+        let response = emulator.respond(&RazerReport::get_device_mode());
+        assert_eq!(
+            decode(&response).details.as_deref(),
+            Some("mode 0x03, param 0x00")
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use razer_rgb_mac::console::*;
+use razer_rgb_mac::razer_report::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_builds_matching_report() {
+        let command =
+            RawCommand::parse("0F", "0x02", "09", "", "01 05 01 00 00 01 FF 00 00", 0x1F).unwrap();
+        assert_eq!(command.report(), RazerReport::static_rgb(0xFF, 0, 0));
+    }
+
+    #[test]
+    fn test_parse_defaults_and_errors() {
+        let command = RawCommand::parse("00", "82", "", "3f", "0x01,0x02", 0x1F).unwrap();
+        assert_eq!(command.data_size, 2);
+        assert_eq!(command.transaction_id, 0x3F);
+        assert!(command.report().crc_valid());
+        // An empty id is the one the keyboard expects.
+        let command = RawCommand::parse("00", "82", "", "", "", 0x3F).unwrap();
+        assert_eq!(command.transaction_id, 0x3F);
+
+        assert!(RawCommand::parse("0F", "zz", "", "", "", 0x1F).is_err());
+        assert!(RawCommand::parse("0F", "02", "", "", "100", 0x1F).is_err());
+        let too_long = vec!["00"; 81].join(" ");
+        assert!(RawCommand::parse("0F", "02", "", "", &too_long, 0x1F).is_err());
+    }
+
+    #[test]
+    fn test_history_moves_resent_to_top() {
+        let first = RawCommand::parse("00", "81", "", "", "", 0x1F).unwrap();
+        let second = RawCommand::parse("0F", "84", "", "", "01 05", 0x1F).unwrap();
+        let mut history = History::default();
+        history.push(first.clone());
+        history.push(second.clone());
+        history.push(first.clone());
+
+        let commands: Vec<&RawCommand> = history.iter().collect();
+        assert_eq!(commands, vec![&first, &second]);
+        for _ in 0..HISTORY_LEN + 5 {
+            history.push(
+                RawCommand::parse("00", "00", "", "", &format!("{:02X}", history.len()), 0x1F)
+                    .unwrap(),
+            );
+        }
+        assert_eq!(history.len(), HISTORY_LEN);
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(&RazerReport::get_firmware().to_bytes());
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("0000  00 1F 00 00 00 02 00 81"));
+        assert!(lines[5].starts_with("0050  "));
+    }
+}
@@ -0,0 +1,241 @@
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::config::load_or_create_token;
+use razer_rgb_mac::control::ControlServer;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::http;
+use razer_rgb_mac::profile::{Profile, ProfileStore};
+use razer_rgb_mac::razer_report::*;
+use razer_rgb_mac::transport::MockTransport;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use tungstenite::Message;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "secret";
+
+    fn start_server(profiles: ProfileStore) -> (SocketAddr, MockTransport) {
+        let mock = MockTransport::new();
+        let device = Arc::new(Mutex::new(Device::new(mock.clone())));
+        let server =
+            ControlServer::bind("127.0.0.1:0", device, TOKEN.to_string(), profiles).unwrap();
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+        (addr, mock)
+    }
+
+    fn call(addr: SocketAddr, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+        let auth = format!("Bearer {TOKEN}");
+        let (status, body) = http::request_with_headers(
+            addr,
+            method,
+            path,
+            &[("Authorization", &auth)],
+            body.as_ref(),
+        )
+        .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn test_requests_need_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, _) = start_server(ProfileStore::new(dir.path()));
+
+        let (status, _) = http::request(addr, "GET", "/state", None).unwrap();
+        assert_eq!(status, 401);
+        let (status, _) = http::request(addr, "GET", "/state?token=secret", None).unwrap();
+        assert_eq!(status, 200);
+    }
+
+    #[test]
+    fn test_token_file_is_private_and_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api-token");
+        let token = load_or_create_token(&path).unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert_eq!(load_or_create_token(&path).unwrap(), token);
+
+        std::fs::write(&path, "").unwrap();
+        let fresh = load_or_create_token(&path).unwrap();
+        assert_ne!(fresh, token);
+        assert_eq!(fresh.len(), 32);
+    }
+
+    #[test]
+    fn test_post_effect_and_get_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, mock) = start_server(ProfileStore::new(dir.path()));
+
+        let (status, state) = call(
+            addr,
+            "POST",
+            "/effect",
+            Some(json!({ "effect": "breathing", "color": [0, 0, 255] })),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(
+            state["effect"],
+            json!({ "effect": "breathing", "color": [0, 0, 255] })
+        );
+        assert_eq!(
+            mock.requests()[0].to_bytes(),
+            RazerReport::breathing(0, 0, 255).to_bytes()
+        );
+
+        let (_, state) = call(addr, "GET", "/state", None);
+        assert_eq!(state["effect"]["effect"], "breathing");
+        assert_eq!(state["brightness"], 255);
+
+        let (status, _) = call(addr, "POST", "/effect", Some(json!({ "effect": "static" })));
+        assert_eq!(status, 400);
+    }
+
+    #[test]
+    fn test_frames_must_fit_the_matrix() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, mock) = start_server(ProfileStore::new(dir.path()));
+
+        let oversized = vec![vec![[255, 0, 0]; 30]; 6];
+        let (status, _) = call(addr, "POST", "/frame", Some(json!({ "frame": oversized })));
+        assert_eq!(status, 400);
+        let undersized = vec![vec![[255, 0, 0]; 3]; 2];
+        let (status, _) = call(addr, "POST", "/frame", Some(json!({ "frame": undersized })));
+        assert_eq!(status, 400);
+        assert!(mock.requests().is_empty());
+
+        // The device is still usable afterwards.
+        let (status, state) = call(
+            addr,
+            "POST",
+            "/frame",
+            Some(json!({ "frame": vec![vec![[0, 0, 255]; 22]; 6] })),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(state["frame"][5][21], json!([0, 0, 255]));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ProfileStore::new(dir.path());
+        let mut evening = Profile::new("evening", Effect::Static(Rgb::new(255, 120, 40)));
+        evening.brightness = Some(80);
+        store.save(&evening).unwrap();
+        assert_eq!(store.load("evening").unwrap(), evening);
+        assert!(store.load("../evening").is_err());
+
+        let (addr, mock) = start_server(store);
+        let (_, list) = call(addr, "GET", "/profiles", None);
+        assert_eq!(list["profiles"], json!(["evening"]));
+
+        let (status, state) = call(
+            addr,
+            "POST",
+            "/profile",
+            Some(json!({ "profile": "evening" })),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(state["brightness"], 80);
+        let requests = mock.requests();
+        assert_eq!(
+            requests[0].to_bytes(),
+            RazerReport::brightness(80).to_bytes()
+        );
+        assert_eq!(
+            requests[1].to_bytes(),
+            RazerReport::static_rgb(255, 120, 40).to_bytes()
+        );
+
+        let (status, _) = call(addr, "POST", "/profile", Some(json!({ "profile": "nope" })));
+        assert_eq!(status, 404);
+    }
+
+    #[test]
+    fn test_websocket_frames_in_state_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, mock) = start_server(ProfileStore::new(dir.path()));
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let (mut socket, _) =
+            tungstenite::client(format!("ws://{addr}/ws?token={TOKEN}"), stream).unwrap();
+
+        let initial: Value =
+            serde_json::from_str(socket.read().unwrap().into_text().unwrap().as_str()).unwrap();
+        assert_eq!(initial["effect"]["effect"], "spectrum");
+
+        let mut frame = vec![0u8; 6 * 22 * 3];
+        frame[..3].copy_from_slice(&[9, 8, 7]);
+        socket.send(Message::binary(frame)).unwrap();
+
+        let update: Value =
+            serde_json::from_str(socket.read().unwrap().into_text().unwrap().as_str()).unwrap();
+        assert_eq!(update["effect"]["effect"], "custom");
+        assert_eq!(update["frame"][0][0], json!([9, 8, 7]));
+        let requests = mock.requests();
+        assert_eq!(&requests[0].arguments[5..8], &[9, 8, 7]);
+        assert_eq!(requests[6].arguments[2], EXT_EFFECT_CUSTOM_FRAME);
+
+        socket
+            .send(Message::text(json!({ "brightness": 10 }).to_string()))
+            .unwrap();
+        let update: Value =
+            serde_json::from_str(socket.read().unwrap().into_text().unwrap().as_str()).unwrap();
+        assert_eq!(update["brightness"], 10);
+    }
+
+    #[test]
+    fn test_websocket_upgrade_checks_and_pipelined_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, _) = start_server(ProfileStore::new(dir.path()));
+        let auth = format!("Bearer {TOKEN}");
+        let upgrade = [
+            ("Authorization", auth.as_str()),
+            ("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="),
+        ];
+        let (status, _) = http::request_with_headers(addr, "GET", "/ws", &upgrade, None).unwrap();
+        assert_eq!(status, 400);
+        let mut headers = upgrade.to_vec();
+        headers.extend([("Upgrade", "websocket"), ("Connection", "Upgrade")]);
+        let (status, _) = http::request_with_headers(addr, "POST", "/ws", &headers, None).unwrap();
+        assert_eq!(status, 400);
+
+        // The handshake and a first message in one write.
+        let text = json!({ "brightness": 10 }).to_string();
+        let mut bytes = format!(
+            "GET /ws?token={TOKEN} HTTP/1.1\r\nHost: {addr}\r\nUpgrade: websocket\r\n\
+             Connection: keep-alive, Upgrade\r\nSec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+        )
+        .into_bytes();
+        // A masked text frame; an all-zero mask leaves the payload as is.
+        bytes.extend([0x81, 0x80 | text.len() as u8, 0, 0, 0, 0]);
+        bytes.extend(text.as_bytes());
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(&bytes).unwrap();
+
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 4096];
+        while !String::from_utf8_lossy(&received).contains("\"brightness\":10") {
+            let read = stream.read(&mut buffer).unwrap();
+            assert!(read > 0, "connection closed before the message was applied");
+            received.extend(&buffer[..read]);
+        }
+        assert!(received.starts_with(b"HTTP/1.1 101"));
+    }
+}
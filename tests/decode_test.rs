@@ -0,0 +1,76 @@
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::decode::*;
+use razer_rgb_mac::emulator::Emulator;
+use razer_rgb_mac::razer_report::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breathing_description() {
+        assert_eq!(
+            RazerReport::breathing(0xFF, 0x00, 0x00).to_string(),
+            "set extended effect: breathing single #FF0000 on backlight, varstore \
+             (status new, transaction 0x1F, data size 9, CRC ok)"
+        );
+    }
+
+    #[test]
+    fn test_decoded_fields() {
+        let decoded = decode(&RazerReport::brightness(0x80));
+        assert_eq!(decoded.command, Some("set brightness"));
+        assert_eq!(
+            decoded.details.as_deref(),
+            Some("128 (50%) on backlight, varstore")
+        );
+        assert_eq!(decoded.status, "new");
+        assert!(decoded.crc_valid);
+    }
+
+    #[test]
+    fn test_bad_crc_and_unknown_command() {
+        let mut report = RazerReport::new();
+        report.command_class = 0x3F;
+        report.command_id = 0x01;
+        report.status = RAZER_CMD_NOT_SUPPORTED;
+        report.crc = 0x55;
+
+        let decoded = decode(&report);
+        assert_eq!(decoded.command, None);
+        assert!(!decoded.crc_valid);
+        assert_eq!(
+            report.to_string(),
+            "unknown command 0x3F/0x01 (status not supported, transaction 0x1F, data size 0, CRC BAD)"
+        );
+    }
+
+    #[test]
+    fn test_responses_show_returned_data() {
+        let emulator = Emulator::new();
+        let request = RazerReport::get_firmware();
+        assert_eq!(decode(&request).details, None);
+
+        let firmware = decode(&emulator.respond(&request));
+        assert_eq!(firmware.details.as_deref(), Some("v1.2"));
+        let serial = decode(&emulator.respond(&RazerReport::get_serial()));
+        assert_eq!(serial.details.as_deref(), Some("\"PM2143H00000001\""));
+    }
+
+    #[test]
+    fn test_from_hex_accepts_dumps() {
+        let report = RazerReport::custom_frame_row(1, 0, &[Rgb::new(1, 2, 3), Rgb::new(4, 5, 6)]);
+        let spaced: Vec<String> = report
+            .to_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        let parsed = RazerReport::from_hex(&spaced.join(" ")).unwrap();
+        assert_eq!(parsed.to_bytes(), report.to_bytes());
+        assert_eq!(
+            decode(&parsed).details.as_deref(),
+            Some("row 1, columns 0-1: #010203 #040506")
+        );
+        assert!(RazerReport::from_hex("00ff").is_none());
+    }
+}
@@ -0,0 +1,62 @@
+use razer_rgb_mac::async_client::AsyncClient;
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::descriptor::*;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::frame::Frame;
+use razer_rgb_mac::razer_report::*;
+use razer_rgb_mac::transport::MockTransport;
+use std::collections::HashSet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ornata_v2() -> DeviceDescriptor {
+        *by_product_id(0x025D).unwrap()
+    }
+
+    #[test]
+    fn test_product_ids_are_unique() {
+        let ids: HashSet<u16> = DEVICES.iter().map(|d| d.product_id).collect();
+        assert_eq!(ids.len(), DEVICES.len());
+        assert_eq!(by_product_id(0x02A1), Some(&ORNATA_V3));
+        assert_eq!(by_product_id(0xFFFF), None);
+    }
+
+    #[test]
+    fn test_device_stamps_transaction_id() {
+        let mock = MockTransport::new();
+        let mut device = Device::with_descriptor(mock.clone(), ornata_v2());
+        device
+            .set_effect(Effect::Static(Rgb::new(1, 2, 3)))
+            .unwrap();
+        device.set_frame(&Frame::default()).unwrap();
+        device.set_brightness(0x10).unwrap();
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1 + 6 + 1 + 1);
+        for request in requests {
+            assert_eq!(request.transaction_id, 0x3F);
+            assert!(request.crc_valid());
+        }
+    }
+
+    #[test]
+    fn test_raw_exchange_keeps_transaction_id() {
+        let mock = MockTransport::new();
+        let mut device = Device::with_descriptor(mock.clone(), ornata_v2());
+        let mut report = RazerReport::get_firmware();
+        report.transaction_id = 0xFF;
+        device.exchange(&report).unwrap();
+        assert_eq!(mock.requests()[0].transaction_id, 0xFF);
+    }
+
+    #[tokio::test]
+    async fn test_async_client_uses_descriptor() {
+        let mock = MockTransport::new();
+        let client = AsyncClient::new(mock.clone()).with_descriptor(ornata_v2());
+        client.apply(RazerReport::spectrum()).await.unwrap();
+        assert_eq!(mock.requests()[0].transaction_id, 0x3F);
+    }
+}
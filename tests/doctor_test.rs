@@ -0,0 +1,144 @@
+use razer_rgb_mac::descriptor::DeviceDescriptor;
+use razer_rgb_mac::doctor::*;
+use razer_rgb_mac::emulator::Emulator;
+use razer_rgb_mac::transport::{MockTransport, Transport, TransportError};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bus with scripted devices and a scripted answer to `open`.
+    struct FakeProbe {
+        devices: Result<Vec<UsbDeviceInfo>, rusb::Error>,
+        open: Result<Box<dyn Transport>, rusb::Error>,
+    }
+
+    impl FakeProbe {
+        fn new(devices: Vec<UsbDeviceInfo>, open: Result<Box<dyn Transport>, rusb::Error>) -> Self {
+            Self {
+                devices: Ok(devices),
+                open,
+            }
+        }
+    }
+
+    impl Probe for FakeProbe {
+        fn devices(&mut self) -> Result<Vec<UsbDeviceInfo>, rusb::Error> {
+            self.devices.clone()
+        }
+
+        fn open(
+            &mut self,
+            _device: &UsbDeviceInfo,
+            _descriptor: &'static DeviceDescriptor,
+        ) -> Result<Box<dyn Transport>, rusb::Error> {
+            std::mem::replace(&mut self.open, Err(rusb::Error::NoDevice))
+        }
+    }
+
+    fn usb(vendor_id: u16, product_id: u16, interfaces: &[u8]) -> UsbDeviceInfo {
+        UsbDeviceInfo {
+            bus: 1,
+            address: 4,
+            vendor_id,
+            product_id,
+            interfaces: interfaces.to_vec(),
+        }
+    }
+
+    fn ornata() -> UsbDeviceInfo {
+        usb(0x1532, 0x02A1, &[0, 1, 2])
+    }
+
+    fn statuses(report: &Report) -> Vec<Status> {
+        report.checks.iter().map(|check| check.status).collect()
+    }
+
+    #[test]
+    fn test_working_keyboard_reports_firmware_and_serial() {
+        let mut probe = FakeProbe::new(
+            vec![usb(0x046D, 0xC52B, &[0]), ornata()],
+            Ok(Box::new(Emulator::new())),
+        );
+        let report = run(&mut probe);
+
+        assert!(report.healthy());
+        assert_eq!(statuses(&report), vec![Status::Pass; 5]);
+        let labels: Vec<&str> = report.checks.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels[0], "libusb works (2 USB devices)");
+        assert_eq!(
+            labels[1],
+            "Razer Ornata V3 (1532:02A1 on bus 1 address 4), interfaces 0, 1, 2"
+        );
+        assert_eq!(labels[3], "Firmware v1.2");
+        assert_eq!(labels[4], "Serial PM2143H00000001");
+    }
+
+    #[test]
+    fn test_missing_libusb_and_empty_bus() {
+        let mut probe = FakeProbe {
+            devices: Err(rusb::Error::Other),
+            open: Err(rusb::Error::NoDevice),
+        };
+        let report = run(&mut probe);
+        assert!(!report.healthy());
+        assert_eq!(statuses(&report), vec![Status::Fail]);
+        assert!(report.checks[0].fix.as_deref().unwrap().contains("libusb"));
+
+        let mut probe = FakeProbe::new(vec![usb(0x046D, 0xC52B, &[0])], Err(rusb::Error::NoDevice));
+        let report = run(&mut probe);
+        assert_eq!(statuses(&report), vec![Status::Pass, Status::Fail]);
+        assert_eq!(report.checks[1].label, "No Razer devices on the USB bus");
+    }
+
+    #[test]
+    fn test_open_failures_come_with_fixes() {
+        let mut probe = FakeProbe::new(vec![ornata()], Err(rusb::Error::Access));
+        let report = run(&mut probe);
+        let last = report.checks.last().unwrap();
+        assert_eq!(last.status, Status::Fail);
+        assert!(last.label.starts_with("Can't open: permission denied"));
+        assert!(last.fix.is_some());
+
+        let mut probe = FakeProbe::new(vec![ornata()], Err(rusb::Error::Busy));
+        let report = run(&mut probe);
+        let last = report.checks.last().unwrap();
+        assert!(last.label.contains("busy"));
+        assert!(last.fix.as_deref().unwrap().contains("Synapse"));
+    }
+
+    #[test]
+    fn test_wrong_interface_and_silent_keyboard() {
+        // Only the keyboard interface: no interface 2 to send reports to.
+        let mut probe = FakeProbe::new(
+            vec![usb(0x1532, 0x02A1, &[0])],
+            Ok(Box::new(Emulator::new())),
+        );
+        let report = run(&mut probe);
+        assert_eq!(
+            statuses(&report),
+            vec![Status::Pass, Status::Pass, Status::Fail]
+        );
+        assert!(report.checks[2].label.contains("no interface 2"));
+
+        let mock = MockTransport::new();
+        mock.push_error(TransportError::Usb(rusb::Error::Pipe));
+        let mut probe = FakeProbe::new(vec![ornata()], Ok(Box::new(mock)));
+        let report = run(&mut probe);
+        let last = report.checks.last().unwrap();
+        assert_eq!(last.status, Status::Fail);
+        assert!(last.label.starts_with("Keyboard doesn't answer queries"));
+    }
+
+    #[test]
+    fn test_unsupported_razer_device_is_a_warning() {
+        let mut probe = FakeProbe::new(
+            vec![usb(0x1532, 0x0084, &[0, 1])],
+            Err(rusb::Error::NoDevice),
+        );
+        let report = run(&mut probe);
+        assert!(report.healthy());
+        assert_eq!(statuses(&report), vec![Status::Pass, Status::Warn]);
+        assert!(report.to_string().contains("1532:0084"));
+    }
+}
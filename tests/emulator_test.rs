@@ -0,0 +1,94 @@
+use razer_rgb_mac::async_client::AsyncClient;
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::emulator::*;
+use razer_rgb_mac::frame::Frame;
+use razer_rgb_mac::razer_report::*;
+use razer_rgb_mac::transport::{Transport, TransportError};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_echoes_transaction_and_crc() {
+        let emulator = Emulator::new();
+        let request = RazerReport::static_rgb(1, 2, 3);
+        let response = emulator.respond(&request);
+
+        assert_eq!(response.status, RAZER_CMD_SUCCESSFUL);
+        assert_eq!(response.transaction_id, request.transaction_id);
+        assert_eq!(response.command_class, request.command_class);
+        assert_eq!(response.command_id, request.command_id);
+        assert!(response.crc_valid());
+        assert_eq!(emulator.state().effect, Effect::Static(Rgb::new(1, 2, 3)));
+    }
+
+    #[test]
+    fn test_bad_crc_is_rejected() {
+        let emulator = Emulator::new();
+        let mut request = RazerReport::static_rgb(9, 9, 9);
+        request.crc ^= 0xFF;
+
+        let response = emulator.respond(&request);
+        assert_eq!(response.status, RAZER_CMD_FAILURE);
+        assert_eq!(emulator.state().effect, Effect::Spectrum);
+        assert_eq!(emulator.state().reports, 1);
+    }
+
+    #[test]
+    fn test_unknown_command_not_supported() {
+        let mut emulator = Emulator::new();
+        let mut request = RazerReport::new();
+        request.command_class = 0x3F;
+        request.command_id = 0x01;
+        request.calculate_crc();
+
+        assert_eq!(
+            emulator.send(&request).unwrap_err(),
+            TransportError::Status(RAZER_CMD_NOT_SUPPORTED)
+        );
+    }
+
+    #[test]
+    fn test_device_state_matches_emulator() {
+        let emulator = Emulator::new();
+        let mut device = Device::new(emulator.clone());
+
+        let mut frame = Frame::new(6, 22);
+        frame.set(2, 5, Rgb::new(10, 20, 30));
+        frame.set(5, 21, Rgb::new(255, 0, 128));
+        device.set_frame(&frame).unwrap();
+        device.set_effect(Effect::Custom).unwrap();
+        device.set_brightness(0x40).unwrap();
+
+        let state = emulator.state();
+        assert_eq!(state.effect, Effect::Custom);
+        assert_eq!(state.frame, frame);
+        assert_eq!(state.brightness, 0x40);
+
+        device
+            .set_effect(Effect::Wave {
+                direction: 0x02,
+                speed: 0x10,
+            })
+            .unwrap();
+        assert_eq!(emulator.state().effect, device.effect());
+    }
+
+    #[tokio::test]
+    async fn test_async_queries() {
+        let emulator = Emulator::with_state(EmulatorState {
+            serial: "PM0000TEST".to_string(),
+            firmware: (2, 7),
+            brightness: 0x33,
+            ..EmulatorState::default()
+        });
+        let client = AsyncClient::new(emulator);
+
+        assert_eq!(client.firmware_version().await.unwrap(), (2, 7));
+        assert_eq!(client.serial().await.unwrap(), "PM0000TEST");
+        assert_eq!(client.brightness().await.unwrap(), 0x33);
+    }
+}
@@ -0,0 +1,151 @@
+use razer_rgb_mac::animation::Animation;
+use razer_rgb_mac::color::{Gradient, Rgb};
+use razer_rgb_mac::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use razer_rgb_mac::heatmap::*;
+use razer_rgb_mac::layout::{Key, Layout};
+use std::io::Cursor;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn key(row: usize, col: usize, name: Option<&str>, code: Option<u16>) -> Key {
+        Key {
+            row,
+            col,
+            x: col as f32,
+            y: row as f32,
+            width: 1.0,
+            height: 1.0,
+            name: name.map(str::to_string),
+            code,
+        }
+    }
+
+    /// "a" found by its name (code 30), and a key found by code 57 alone.
+    fn layout() -> Layout {
+        Layout {
+            name: "test".to_string(),
+            keys: vec![key(2, 1, Some("A"), None), key(4, 7, None, Some(57))],
+        }
+    }
+
+    fn settings(half_life: Option<Duration>) -> HeatmapSettings {
+        HeatmapSettings {
+            half_life,
+            saturation: 4.0,
+            gradient: Gradient::new(vec![Rgb::BLACK, Rgb::WHITE]).unwrap(),
+        }
+    }
+
+    fn press(at: u64, code: u16) -> KeyPress {
+        KeyPress { at: ms(at), code }
+    }
+
+    #[test]
+    fn test_recording_replays_presses_on_time() {
+        let text = "{\"at_ms\": 200, \"code\": 57}\n\n{\"at_ms\": 0, \"code\": 30}\n";
+        let mut recording = Recording::parse(Cursor::new(text)).unwrap();
+        assert_eq!(recording.presses(), [press(0, 30), press(200, 57)]);
+
+        let mut codes = Vec::new();
+        assert!(recording.read(ms(100), &mut codes).unwrap());
+        assert_eq!(codes, [30]);
+        assert!(!recording.read(ms(200), &mut codes).unwrap());
+        assert_eq!(codes, [30, 57]);
+
+        let err = Recording::parse(Cursor::new("{\"at_ms\": 0, \"code\": 1}\nnope")).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+    }
+
+    #[test]
+    fn test_presses_heat_keys_and_cool_down() {
+        let recording = Recording::new(Vec::new());
+        let mut effect = HeatmapEffect::new(recording, layout(), settings(Some(ms(1000))));
+        effect.press(30);
+        effect.press(30);
+        effect.press(57);
+        // Counted, but there is no key for it.
+        effect.press(1000);
+        assert_eq!(effect.heat(2, 1), 2.0);
+        assert_eq!(effect.heat(4, 7), 1.0);
+        assert_eq!(effect.counts().get(1000), 1);
+        assert_eq!(effect.counts().total(), 4);
+
+        effect.cool(ms(1000));
+        assert_eq!(effect.heat(2, 1), 1.0);
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        effect.draw(&mut frame);
+        assert_eq!(frame.get(2, 1), Rgb::BLACK.lerp(Rgb::WHITE, 0.25));
+        assert_eq!(frame.get(4, 7), Rgb::BLACK.lerp(Rgb::WHITE, 0.125));
+        assert_eq!(frame.get(0, 0), Rgb::BLACK);
+    }
+
+    #[test]
+    fn test_effect_plays_a_recording_to_its_end() {
+        let recording = Recording::new(vec![press(0, 30), press(120, 57)]);
+        let mut effect = HeatmapEffect::new(recording, layout(), settings(Some(ms(1000))));
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+
+        assert_eq!(effect.render(ms(0), &mut frame), Some(FRAME_INTERVAL));
+        assert_eq!(frame.get(2, 1), Rgb::BLACK.lerp(Rgb::WHITE, 0.25));
+        assert_eq!(frame.get(4, 7), Rgb::BLACK);
+        assert_eq!(effect.render(ms(150), &mut frame), None);
+        assert!(frame.get(4, 7) != Rgb::BLACK);
+        assert!(effect.heat(2, 1) < 1.0);
+    }
+
+    #[test]
+    fn test_all_time_counts_are_kept_across_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(COUNTS_FILE);
+        std::fs::write(&path, r#"{"30": 3}"#).unwrap();
+
+        let recording = Recording::new(vec![press(0, 57), press(5000, 30)]);
+        let mut effect = HeatmapEffect::new(recording, layout(), settings(None));
+        effect.keep_counts(&path).unwrap();
+        // Saved presses show at once, scaled to the busiest key.
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        assert!(effect.render(ms(0), &mut frame).is_some());
+        assert_eq!(frame.get(2, 1), Rgb::WHITE);
+        assert_eq!(frame.get(4, 7), Rgb::BLACK.lerp(Rgb::WHITE, 1.0 / 3.0));
+        // Nothing cools down without a half-life.
+        assert!(effect.render(ms(5000), &mut frame).is_none());
+        assert_eq!(effect.heat(2, 1), 4.0);
+
+        let saved = KeyCounts::load(&path).unwrap();
+        assert_eq!((saved.get(30), saved.get(57)), (4, 1));
+        assert_eq!(saved.ranked(), [(30, 4), (57, 1)]);
+        assert_eq!(
+            KeyCounts::load(&dir.path().join("missing.json")).unwrap(),
+            KeyCounts::default()
+        );
+    }
+
+    #[test]
+    fn test_recorder_writes_a_replayable_recording() {
+        let mut out = Vec::new();
+        let recording = Recording::new(vec![press(0, 30), press(40, 57)]);
+        let mut recorder = Recorder::new(recording, &mut out);
+        let mut codes = Vec::new();
+        recorder.read(ms(10), &mut codes).unwrap();
+        recorder.read(ms(50), &mut codes).unwrap();
+        drop(recorder);
+        let replay = Recording::parse(Cursor::new(out)).unwrap();
+        assert_eq!(replay.presses(), [press(10, 30), press(50, 57)]);
+
+        let settings: HeatmapSettings =
+            serde_json::from_str(r#"{"half_life_ms": null, "gradient": ["black", "red"]}"#)
+                .unwrap();
+        assert_eq!(settings.half_life, None);
+        assert_eq!(settings.saturation, HeatmapSettings::default().saturation);
+        let settings: HeatmapSettings = serde_json::from_str(r#"{"saturation": 0}"#).unwrap();
+        assert_eq!(settings.half_life, Some(Duration::from_secs(10)));
+        assert!(settings.validate().is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::emulator::Emulator;
+use razer_rgb_mac::idle::*;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Idle time set by the test.
+    #[derive(Clone, Default)]
+    struct FakeIdle(Arc<Mutex<Duration>>);
+
+    impl FakeIdle {
+        fn set(&self, secs: u64) {
+            *self.0.lock().unwrap() = Duration::from_secs(secs);
+        }
+    }
+
+    impl IdleSource for FakeIdle {
+        fn idle_for(&mut self) -> io::Result<Duration> {
+            Ok(*self.0.lock().unwrap())
+        }
+    }
+
+    fn settings() -> IdleSettings {
+        IdleSettings {
+            dim_after: Some(Duration::from_secs(60)),
+            dim_brightness: 0x40,
+            off_after: Duration::from_secs(600),
+            fade: Duration::from_millis(70),
+        }
+    }
+
+    fn setup(effect: Effect) -> (Emulator, Mutex<Device>, FakeIdle, IdleDimmer) {
+        let emulator = Emulator::new();
+        let mut device = Device::new(emulator.clone());
+        device.set_effect(effect).unwrap();
+        let idle = FakeIdle::default();
+        let dimmer = IdleDimmer::new(idle.clone(), settings());
+        (emulator, Mutex::new(device), idle, dimmer)
+    }
+
+    #[test]
+    fn test_dims_turns_off_and_wakes() {
+        let (emulator, device, idle, mut dimmer) = setup(Effect::Spectrum);
+
+        idle.set(30);
+        assert_eq!(dimmer.poll(&device).unwrap(), None);
+        idle.set(61);
+        assert_eq!(dimmer.poll(&device).unwrap(), Some(Phase::Dimmed));
+        // Dimming leaves the effect running.
+        assert_eq!(emulator.state().effect, Effect::Spectrum);
+        assert_eq!(emulator.state().brightness, 0x40);
+        assert_eq!(dimmer.poll(&device).unwrap(), None);
+
+        idle.set(600);
+        assert_eq!(dimmer.poll(&device).unwrap(), Some(Phase::Off));
+        assert_eq!(emulator.state().effect, Effect::Off);
+        assert_eq!(emulator.state().brightness, 0);
+
+        idle.set(0);
+        assert_eq!(dimmer.poll(&device).unwrap(), Some(Phase::Active));
+        assert_eq!(emulator.state().effect, Effect::Spectrum);
+        assert_eq!(emulator.state().brightness, 0xFF);
+    }
+
+    #[test]
+    fn test_wake_keeps_changes_made_while_dimmed() {
+        let (emulator, device, idle, mut dimmer) = setup(Effect::Static(Rgb::RED));
+
+        idle.set(120);
+        dimmer.poll(&device).unwrap();
+        device
+            .lock()
+            .unwrap()
+            .set_effect(Effect::Static(Rgb::BLUE))
+            .unwrap();
+
+        idle.set(0);
+        assert_eq!(dimmer.poll(&device).unwrap(), Some(Phase::Active));
+        assert_eq!(emulator.state().effect, Effect::Static(Rgb::BLUE));
+        assert_eq!(emulator.state().brightness, 0xFF);
+    }
+
+    #[test]
+    fn test_dimming_never_brightens() {
+        let (emulator, device, idle, mut dimmer) = setup(Effect::Static(Rgb::RED));
+        device.lock().unwrap().set_brightness(0x10).unwrap();
+
+        idle.set(61);
+        dimmer.poll(&device).unwrap();
+        assert_eq!(emulator.state().brightness, 0x10);
+        idle.set(1);
+        dimmer.poll(&device).unwrap();
+        assert_eq!(emulator.state().brightness, 0x10);
+    }
+
+    #[test]
+    fn test_activity_between_polls_wakes_before_dimming_again() {
+        let (emulator, device, idle, mut dimmer) = setup(Effect::Static(Rgb::GREEN));
+
+        idle.set(700);
+        assert_eq!(dimmer.poll(&device).unwrap(), Some(Phase::Off));
+        // Someone typed and then stopped again past the dim timeout.
+        idle.set(61);
+        assert_eq!(dimmer.poll(&device).unwrap(), Some(Phase::Active));
+        assert_eq!(emulator.state().effect, Effect::Static(Rgb::GREEN));
+        assert_eq!(dimmer.poll(&device).unwrap(), Some(Phase::Dimmed));
+        assert_eq!(emulator.state().brightness, 0x40);
+    }
+
+    #[test]
+    fn test_device_stays_free_while_fading() {
+        let emulator = Emulator::new();
+        let device = Arc::new(Mutex::new(Device::new(emulator.clone())));
+        let idle = FakeIdle::default();
+        let mut dimmer = IdleDimmer::new(
+            idle.clone(),
+            IdleSettings {
+                fade: Duration::from_secs(3),
+                ..settings()
+            },
+        );
+        idle.set(61);
+        let shared = Arc::clone(&device);
+        let fading = std::thread::spawn(move || dimmer.poll(&shared).unwrap());
+        while emulator.state().brightness == 0xFF {
+            std::thread::yield_now();
+        }
+
+        // Others get the device between steps instead of after the fade.
+        let guard = device.lock().unwrap();
+        assert!(!fading.is_finished());
+        drop(guard);
+        assert_eq!(fading.join().unwrap(), Some(Phase::Dimmed));
+        assert_eq!(emulator.state().brightness, 0x40);
+    }
+}
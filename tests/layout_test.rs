@@ -0,0 +1,86 @@
+use razer_rgb_mac::descriptor::ORNATA_V3;
+use razer_rgb_mac::frame::{MATRIX_COLS, MATRIX_ROWS};
+use razer_rgb_mac::layout::*;
+use std::fs;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_covers_the_matrix() {
+        let grid = Layout::grid(MATRIX_ROWS, MATRIX_COLS);
+        assert_eq!(grid.keys.len(), MATRIX_ROWS * MATRIX_COLS);
+        assert_eq!(grid.size(), (MATRIX_COLS as f32, MATRIX_ROWS as f32));
+        let key = &grid.keys[MATRIX_COLS + 2];
+        assert_eq!((key.row, key.col), (1, 2));
+        assert_eq!(key.center(), (2.5, 1.5));
+        assert!(grid.validate().is_ok());
+    }
+
+    #[test]
+    fn test_load_fills_defaults_and_finds_keys_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("02A1.json");
+        fs::write(
+            &path,
+            r#"{"name": "test", "keys": [
+                {"row": 3, "col": 1, "x": 0, "y": 3.25, "width": 1.75, "name": "Caps Lock"},
+                {"row": 3, "col": 2, "x": 1.75, "y": 3.25}]}"#,
+        )
+        .unwrap();
+        let layout = Layout::load(&path).unwrap();
+
+        let caps = layout.key("caps lock").unwrap();
+        assert_eq!((caps.row, caps.col), (3, 1));
+        assert_eq!(caps.height, 1.0);
+        assert_eq!(layout.keys[1].width, 1.0);
+        assert_eq!(layout.key("a"), None);
+        assert_eq!(layout.size(), (2.75, 4.25));
+        assert_eq!(
+            Layout::path_for(&ORNATA_V3).file_name().unwrap(),
+            "02A1.json"
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_keys_off_the_matrix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("layout.json");
+        fs::write(
+            &path,
+            r#"{"name": "bad", "keys": [{"row": 6, "col": 0, "x": 0, "y": 0}]}"#,
+        )
+        .unwrap();
+        assert!(Layout::load(&path).is_err());
+
+        fs::write(
+            &path,
+            r#"{"name": "bad", "keys": [{"row": 0, "col": 0, "x": 0, "y": 0, "width": 0}]}"#,
+        )
+        .unwrap();
+        assert!(Layout::load(&path).is_err());
+        assert!(Layout::load(&dir.path().join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn test_key_codes_match_by_code_or_usual_name() {
+        let layout: Layout = serde_json::from_str(
+            r#"{"name": "test", "keys": [
+                {"row": 3, "col": 1, "x": 0, "y": 3.25, "name": "Caps Lock"},
+                {"row": 5, "col": 7, "x": 3.75, "y": 5.25, "width": 6.25, "code": 57},
+                {"row": 5, "col": 1, "x": 0, "y": 5.25, "name": "fn", "code": 464}]}"#,
+        )
+        .unwrap();
+        assert_eq!(code_name(58), Some("caps lock"));
+        assert_eq!(code_name(464), None);
+        assert_eq!(layout.key_for_code(58).map(|key| key.col), Some(1));
+        assert_eq!(layout.key_for_code(57).map(|key| key.col), Some(7));
+        assert_eq!(layout.key_for_code(464).map(|key| key.col), Some(1));
+        assert_eq!(layout.key_for_code(30), None);
+        assert_eq!(
+            Layout::grid(MATRIX_ROWS, MATRIX_COLS).key_for_code(30),
+            None
+        );
+    }
+}
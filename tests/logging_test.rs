@@ -0,0 +1,114 @@
+use razer_rgb_mac::descriptor::ORNATA_V3;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::emojis::*;
+use razer_rgb_mac::logging::*;
+use razer_rgb_mac::razer_report::RazerReport;
+use razer_rgb_mac::transport::{MockTransport, TransportError};
+use tracing::Level;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `f` with a subscriber that only fills a buffer, and returns what it got.
+    fn capture(level: LevelFilter, f: impl FnOnce()) -> Vec<LogRecord> {
+        let buffer = LogBuffer::default();
+        let subscriber = subscriber(level, LogStyle::Emoji, false, Some(buffer.clone()));
+        tracing::subscriber::with_default(subscriber, f);
+        buffer.records()
+    }
+
+    #[test]
+    fn test_records_keep_icon_and_fields() {
+        let records = capture(LevelFilter::INFO, || {
+            tracing::info!(
+                icon = EMOJI_PAINT,
+                device = "Razer Ornata V3",
+                "Setting Wave"
+            );
+            tracing::warn!("no icon given");
+            tracing::debug!("filtered out");
+        });
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].level, Level::INFO);
+        assert_eq!(records[0].icon, EMOJI_PAINT);
+        assert_eq!(records[0].message, "Setting Wave");
+        assert_eq!(records[0].field("device"), Some("Razer Ornata V3"));
+        assert_eq!(records[1].icon, EMOJI_WARNING);
+    }
+
+    #[test]
+    fn test_plain_style_has_no_emoji() {
+        let records = capture(LevelFilter::INFO, || {
+            tracing::info!(
+                icon = EMOJI_CHECK,
+                command = "static_rgb",
+                latency_us = 812u64,
+                "Applied"
+            );
+        });
+        let record = &records[0];
+
+        assert_eq!(
+            record.format(LogStyle::Emoji),
+            format!("{EMOJI_CHECK} Applied command=static_rgb latency_us=812")
+        );
+        let plain = record.format(LogStyle::Plain);
+        assert_eq!(
+            plain,
+            "INFO  [ok] Applied command=static_rgb latency_us=812"
+        );
+        assert!(plain.is_ascii());
+        for (emoji, tag) in ASCII_TAGS {
+            assert_eq!(ascii_tag(emoji), *tag);
+            assert!(tag.is_ascii());
+        }
+    }
+
+    #[test]
+    fn test_device_logs_every_exchange() {
+        let mock = MockTransport::new();
+        mock.push_error(TransportError::Status(0x05));
+        let mut device = Device::new(Box::new(mock.clone()));
+
+        let records = capture(LevelFilter::DEBUG, || {
+            let _ = device.send(&RazerReport::brightness(128));
+            let _ = device.send(&RazerReport::get_firmware());
+        });
+
+        assert_eq!(records.len(), 2);
+        let failed = &records[0];
+        assert_eq!(failed.level, Level::WARN);
+        assert_eq!(failed.field("device"), Some(ORNATA_V3.name));
+        assert_eq!(failed.field("command"), Some("brightness"));
+        assert_eq!(failed.field("status"), Some("command failed (status 0x05)"));
+        assert!(failed.field("latency_us").is_some());
+
+        let ok = &records[1];
+        assert_eq!(ok.level, Level::DEBUG);
+        assert_eq!(ok.field("command"), Some("get_firmware"));
+        assert_eq!(ok.field("status"), Some("successful"));
+    }
+
+    #[test]
+    fn test_buffer_drops_oldest() {
+        let buffer = LogBuffer::new(2);
+        let subscriber = subscriber(
+            LevelFilter::INFO,
+            LogStyle::Plain,
+            false,
+            Some(buffer.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..3 {
+                tracing::info!("event {i}");
+            }
+        });
+
+        let messages: Vec<String> = buffer.records().into_iter().map(|r| r.message).collect();
+        assert_eq!(messages, vec!["event 1", "event 2"]);
+        buffer.clear();
+        assert!(buffer.records().is_empty());
+    }
+}
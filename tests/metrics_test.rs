@@ -0,0 +1,196 @@
+use razer_rgb_mac::animation::{self, Animation};
+use razer_rgb_mac::color::{Gradient, Rgb};
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::emulator::Emulator;
+use razer_rgb_mac::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use razer_rgb_mac::metrics::*;
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed readings; memory fails when `None`.
+    #[derive(Default)]
+    struct FakeSource {
+        cpu: Vec<f32>,
+        memory: Option<f32>,
+        temperatures: Vec<f32>,
+    }
+
+    impl MetricsSource for FakeSource {
+        fn cpu(&mut self) -> io::Result<Vec<f32>> {
+            Ok(self.cpu.clone())
+        }
+
+        fn memory(&mut self) -> io::Result<f32> {
+            self.memory
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no meminfo"))
+        }
+
+        fn temperatures(&mut self) -> io::Result<Vec<f32>> {
+            Ok(self.temperatures.clone())
+        }
+    }
+
+    fn black_and_white() -> Gradient {
+        Gradient::new(vec![Rgb::BLACK, Rgb::WHITE]).unwrap()
+    }
+
+    fn render(source: FakeSource, config: MetricsConfig) -> Frame {
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        let mut effect = MetricsEffect::new(source, config);
+        assert_eq!(
+            effect.render(Duration::ZERO, &mut frame),
+            Some(Duration::from_secs(1))
+        );
+        frame
+    }
+
+    #[test]
+    fn test_proc_source_reads_fixture_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("proc")).unwrap();
+        let zone = root.join("sys/class/thermal/thermal_zone0");
+        fs::create_dir_all(&zone).unwrap();
+        fs::create_dir_all(root.join("sys/class/thermal/cooling_device0")).unwrap();
+        fs::write(zone.join("temp"), "51500\n").unwrap();
+        fs::write(
+            root.join("proc/meminfo"),
+            "MemTotal:       16000000 kB\nMemFree:         1000000 kB\nMemAvailable:    4000000 kB\n",
+        )
+        .unwrap();
+        let stat = |cpu0: &str, cpu1: &str| {
+            let text = format!("cpu  0 0 0 0 0 0 0 0 0 0\ncpu0 {cpu0}\ncpu1 {cpu1}\nintr 1 2 3\n");
+            fs::write(root.join("proc/stat"), text).unwrap();
+        };
+
+        let mut source = ProcSource::with_root(root);
+        stat("100 0 100 800 0 0 0 0 0 0", "0 0 0 1000 0 0 0 0 0 0");
+        assert_eq!(source.cpu().unwrap(), [0.2, 0.0]);
+        // user, nice, system, idle, iowait, irq, softirq, steal.
+        stat("150 0 150 900 0 0 0 0 0 0", "0 0 0 1150 50 0 0 0 0 0");
+        assert_eq!(source.cpu().unwrap(), [0.5, 0.0]);
+
+        assert_eq!(source.memory().unwrap(), 0.75);
+        assert_eq!(source.temperatures().unwrap(), [51.5]);
+
+        fs::write(root.join("proc/meminfo"), "MemTotal: 16000000 kB\n").unwrap();
+        assert!(source.memory().is_err());
+    }
+
+    #[test]
+    fn test_cpu_keys_and_memory_bar() {
+        let source = FakeSource {
+            cpu: vec![0.0, 1.0, 0.5],
+            memory: Some(0.5),
+            ..Default::default()
+        };
+        let config = MetricsConfig {
+            gradient: black_and_white(),
+            ..Default::default()
+        };
+        let frame = render(source, config);
+
+        assert_eq!(frame.get(0, 0), Rgb::BLACK);
+        assert_eq!(frame.get(0, 1), Rgb::WHITE);
+        assert_eq!(frame.get(0, 2), Rgb::new(128, 128, 128));
+        assert_eq!(frame.get(0, 3), Rgb::BLACK);
+
+        // Half of the number row, shaded by position.
+        assert_eq!(frame.get(1, 10), Rgb::new(121, 121, 121));
+        assert_eq!(frame.get(1, 11), Rgb::BLACK);
+        assert!(frame.row(2).iter().all(|&color| color == Rgb::BLACK));
+    }
+
+    #[test]
+    fn test_fill_shows_hottest_sensor_in_range() {
+        let source = FakeSource {
+            temperatures: vec![30.0, 65.0, 50.0],
+            ..Default::default()
+        };
+        let config = MetricsConfig {
+            gradient: black_and_white(),
+            temperature_range: [40.0, 90.0],
+            bindings: vec![Binding::new(
+                Metric::Temperature,
+                KeyGroup {
+                    row: 5,
+                    from: 2,
+                    to: Some(4),
+                },
+            )],
+            ..Default::default()
+        };
+        let frame = render(source, config);
+
+        assert_eq!(frame.get(5, 1), Rgb::BLACK);
+        assert_eq!(frame.get(5, 2), Rgb::new(128, 128, 128));
+        assert_eq!(frame.get(5, 3), Rgb::new(128, 128, 128));
+        assert_eq!(frame.get(5, 4), Rgb::BLACK);
+    }
+
+    #[test]
+    fn test_failing_metric_leaves_its_keys_dark() {
+        let source = FakeSource {
+            cpu: vec![1.0; 30],
+            memory: None,
+            ..Default::default()
+        };
+        let frame = render(source, MetricsConfig::default());
+
+        // More cores than keys: the rest are dropped.
+        assert!(frame.row(0).iter().all(|&color| color == Rgb::RED));
+        assert!(frame.row(1).iter().all(|&color| color == Rgb::BLACK));
+    }
+
+    #[test]
+    fn test_config_parses_and_plays_on_device() {
+        let config: MetricsConfig = serde_json::from_str(
+            r#"{"interval_ms": 250, "gradient": ["blue", "red"],
+                "bindings": [{"metric": "memory", "keys": {"row": 3}, "style": "fill"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.interval, Duration::from_millis(250));
+        assert_eq!(config.bindings[0].style(), Style::Fill);
+        assert_eq!(config.temperature_range, [40.0, 90.0]);
+        assert!(serde_json::from_str::<MetricsConfig>(r#"{"gradient": []}"#).is_err());
+        let zero = MetricsConfig {
+            interval: Duration::ZERO,
+            ..config.clone()
+        };
+        assert!(zero.validate().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(METRICS_FILE);
+        assert_eq!(
+            MetricsConfig::load(&path).unwrap(),
+            MetricsConfig::default()
+        );
+
+        let emulator = Emulator::new();
+        let device = Mutex::new(Device::new(emulator.clone()));
+        let source = FakeSource {
+            memory: Some(1.0),
+            ..Default::default()
+        };
+        let mut effect = MetricsEffect::new(source, config);
+        let mut frames = 0;
+        animation::play(&device, &mut effect, |next| {
+            assert_eq!(next, Duration::from_millis(250));
+            frames += 1;
+            frames < 2
+        })
+        .unwrap();
+        assert_eq!(frames, 2);
+        let state = emulator.state();
+        assert_eq!(state.effect, Effect::Custom);
+        assert!(state.frame.row(3).iter().all(|&color| color == Rgb::RED));
+        assert_eq!(state.frame.get(0, 0), Rgb::BLACK);
+    }
+}
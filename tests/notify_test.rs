@@ -0,0 +1,149 @@
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::control::ControlServer;
+use razer_rgb_mac::device::{Device, LightingState};
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::emulator::{Emulator, EmulatorState};
+use razer_rgb_mac::frame::Frame;
+use razer_rgb_mac::http;
+use razer_rgb_mac::notify::*;
+use razer_rgb_mac::profile::ProfileStore;
+use razer_rgb_mac::transition::Step;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn short(pattern: Pattern, repeat: u32) -> Notification {
+        Notification {
+            repeat,
+            duration: Duration::from_millis(40),
+            ..Notification::new(pattern, Rgb::RED)
+        }
+    }
+
+    #[test]
+    fn test_flash_alternates_color_and_off() {
+        let snapshot = LightingState::default();
+        let steps = short(Pattern::Flash, 2).steps(&snapshot);
+
+        let timeline: Vec<_> = steps
+            .iter()
+            .map(|(at, step)| match step {
+                Step::Settle(state) => (at.as_millis(), state.effect, state.brightness),
+                other => panic!("unexpected step {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            timeline,
+            vec![
+                (0, Effect::Static(Rgb::RED), 255),
+                (20, Effect::Off, 255),
+                (40, Effect::Static(Rgb::RED), 255),
+                (60, Effect::Off, 255),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pulse_fades_in_and_out_each_repeat() {
+        let notification = Notification {
+            duration: Duration::from_millis(132),
+            ..short(Pattern::Pulse, 2)
+        };
+        let steps = notification.steps(&LightingState::default());
+
+        assert!(matches!(&steps[0].1, Step::Settle(state) if state.brightness == 0));
+        let brightness: Vec<u8> = steps[1..]
+            .iter()
+            .map(|(_, step)| match step {
+                Step::Brightness(brightness) => *brightness,
+                other => panic!("unexpected step {other:?}"),
+            })
+            .collect();
+        assert_eq!(brightness, vec![180, 255, 180, 0, 180, 255, 180, 0]);
+        assert_eq!(steps.last().unwrap().0, notification.length());
+    }
+
+    #[test]
+    fn test_notify_restores_snapshot_exactly() {
+        // The keyboard was dimmed by something other than this device.
+        let emulator = Emulator::with_state(EmulatorState {
+            brightness: 40,
+            ..EmulatorState::default()
+        });
+        let mut device = Device::new(emulator.clone());
+        let mut frame = Frame::default();
+        frame.set(1, 3, Rgb::new(9, 8, 7));
+        device.set_frame(&frame).unwrap();
+
+        for pattern in Pattern::ALL {
+            notify(&mut device, &short(pattern, 2)).unwrap();
+            let keyboard = emulator.state();
+            assert_eq!(keyboard.effect, Effect::Custom, "{pattern}");
+            assert_eq!(keyboard.brightness, 40, "{pattern}");
+            assert_eq!(keyboard.frame, frame, "{pattern}");
+            assert_eq!(device.brightness(), 40);
+        }
+    }
+
+    #[test]
+    fn test_json_defaults_and_limits() {
+        let notification: Notification = serde_json::from_value(json!({
+            "notify": "pulse",
+            "color": "red",
+        }))
+        .unwrap();
+        assert_eq!(notification, Notification::new(Pattern::Pulse, Rgb::RED));
+        assert!(notification.validate().is_ok());
+
+        let notification: Notification =
+            serde_json::from_value(json!({ "color": [0, 0, 255], "repeat": 1 })).unwrap();
+        assert_eq!(notification.pattern, Pattern::Flash);
+        assert_eq!(notification.length(), Duration::from_millis(500));
+
+        assert!(short(Pattern::Flash, 0).validate().is_err());
+        let too_long = Notification {
+            repeat: 100,
+            duration: Duration::from_secs(1),
+            ..Notification::new(Pattern::Flash, Rgb::RED)
+        };
+        assert!(too_long.validate().is_err());
+        assert!("strobe".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn test_control_api_notifies_and_answers_restored_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let emulator = Emulator::new();
+        let mut device = Device::new(emulator.clone());
+        device.set_effect(Effect::Static(Rgb::BLUE)).unwrap();
+        let device = Arc::new(Mutex::new(device));
+        let server = ControlServer::bind(
+            "127.0.0.1:0",
+            device,
+            "secret".to_string(),
+            ProfileStore::new(dir.path()),
+        )
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+
+        let body = json!({ "notify": "flash", "color": "red", "repeat": 2, "duration_ms": 40 });
+        let (status, state) =
+            http::request(addr, "POST", "/notify?token=secret", Some(&body)).unwrap();
+        assert_eq!(status, 200);
+        let state: serde_json::Value = serde_json::from_slice(&state).unwrap();
+        assert_eq!(
+            state["effect"],
+            json!({ "effect": "static", "color": [0, 0, 255] })
+        );
+        assert_eq!(emulator.state().effect, Effect::Static(Rgb::BLUE));
+
+        let body = json!({ "notify": "flash", "color": "red", "repeat": 0 });
+        let (status, _) = http::request(addr, "POST", "/notify?token=secret", Some(&body)).unwrap();
+        assert_eq!(status, 400);
+    }
+}
@@ -0,0 +1,153 @@
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::descriptor::by_product_id;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::openrgb::*;
+use razer_rgb_mac::razer_report::*;
+use razer_rgb_mac::transport::MockTransport;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_server() -> (TcpStream, MockTransport) {
+        let mock = MockTransport::new();
+        let device = Arc::new(Mutex::new(Device::new(mock.clone())));
+        let server = OpenRgbServer::bind("127.0.0.1:0", device).unwrap();
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+        (TcpStream::connect(addr).unwrap(), mock)
+    }
+
+    fn request(stream: &mut TcpStream, packet_id: u32, payload: &[u8]) -> Vec<u8> {
+        stream.write_all(&packet(0, packet_id, payload)).unwrap();
+        let (device_index, reply_id, reply) = read_packet(stream).unwrap();
+        assert_eq!(device_index, 0);
+        assert_eq!(reply_id, packet_id);
+        reply
+    }
+
+    /// Packets without a reply are handled in order, so a round trip after
+    /// them means they have been applied.
+    fn sync(stream: &mut TcpStream) {
+        request(stream, REQUEST_CONTROLLER_COUNT, &[]);
+    }
+
+    #[test]
+    fn test_protocol_version_and_controller_count() {
+        let (mut stream, _) = start_server();
+        let version = request(&mut stream, REQUEST_PROTOCOL_VERSION, &5u32.to_le_bytes());
+        assert_eq!(version, PROTOCOL_VERSION.to_le_bytes());
+
+        let count = request(&mut stream, REQUEST_CONTROLLER_COUNT, &[]);
+        assert_eq!(count, 1u32.to_le_bytes());
+
+        // A header claiming a huge payload is turned down before allocating.
+        let mut header = packet(0, RGBCONTROLLER_UPDATELEDS, &[]);
+        header[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = read_packet(&mut header.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_controller_data_describes_keyboard() {
+        let (mut stream, _) = start_server();
+        let data = request(&mut stream, REQUEST_CONTROLLER_DATA, &3u32.to_le_bytes());
+
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.u32().unwrap() as usize, data.len());
+        assert_eq!(reader.i32().unwrap(), DEVICE_TYPE_KEYBOARD);
+        assert_eq!(reader.str().unwrap(), "Razer Ornata V3");
+        assert_eq!(reader.str().unwrap(), "Razer");
+        for _ in 0..3 {
+            reader.str().unwrap();
+        }
+        assert_eq!(reader.str().unwrap(), "USB 1532:02A1");
+
+        let mode_count = reader.u16().unwrap();
+        assert_eq!(mode_count, 6);
+        assert_eq!(reader.i32().unwrap(), MODE_SPECTRUM);
+        let modes = Mode::all(razer_rgb_mac::effect::Effect::Spectrum);
+        for mode in &modes {
+            assert_eq!(reader.str().unwrap(), mode.name);
+            assert_eq!(reader.i32().unwrap(), mode.value);
+            for _ in 0..11 {
+                reader.u32().unwrap();
+            }
+            reader.colors().unwrap();
+        }
+
+        assert_eq!(reader.u16().unwrap(), 1);
+        assert_eq!(reader.str().unwrap(), "Keyboard");
+        assert_eq!(reader.i32().unwrap(), ZONE_TYPE_MATRIX);
+        assert_eq!(reader.u32().unwrap(), 132);
+
+        // Other keyboards describe themselves.
+        let ornata_v2 = *by_product_id(0x025D).unwrap();
+        let device = Device::with_descriptor(MockTransport::new(), ornata_v2);
+        let data = controller_data(&device, 3);
+        let mut reader = Reader::new(&data);
+        reader.u32().unwrap();
+        reader.i32().unwrap();
+        assert_eq!(reader.str().unwrap(), "Razer Ornata V2");
+        for _ in 0..4 {
+            reader.str().unwrap();
+        }
+        assert_eq!(reader.str().unwrap(), "USB 1532:025D");
+    }
+
+    #[test]
+    fn test_update_leds_uploads_custom_frame() {
+        let (mut stream, mock) = start_server();
+
+        let mut payload = Vec::new();
+        put_u32(&mut payload, 0);
+        put_colors(&mut payload, &vec![Rgb::new(0x10, 0x20, 0x30); 132]);
+        stream
+            .write_all(&packet(0, RGBCONTROLLER_UPDATELEDS, &payload))
+            .unwrap();
+        sync(&mut stream);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 7);
+        for (row, report) in requests[..6].iter().enumerate() {
+            assert_eq!(report.command_class, 0x0F);
+            assert_eq!(report.command_id, 0x03);
+            assert_eq!(report.arguments[2], row as u8);
+            assert_eq!(report.arguments[4], 21);
+            assert_eq!(&report.arguments[5..8], &[0x10, 0x20, 0x30]);
+        }
+        assert_eq!(requests[6].arguments[2], EXT_EFFECT_CUSTOM_FRAME);
+    }
+
+    #[test]
+    fn test_update_mode_sets_static_color() {
+        let (mut stream, mock) = start_server();
+        request(&mut stream, REQUEST_PROTOCOL_VERSION, &3u32.to_le_bytes());
+
+        let mut mode = Mode::all(razer_rgb_mac::effect::Effect::Off)[MODE_STATIC as usize].clone();
+        mode.colors = vec![Rgb::RED];
+        let mut payload = Vec::new();
+        put_u32(&mut payload, 0);
+        put_i32(&mut payload, MODE_STATIC);
+        put_str(&mut payload, &mode.name);
+        put_i32(&mut payload, mode.value);
+        for value in [0u32; 11] {
+            put_u32(&mut payload, value);
+        }
+        put_colors(&mut payload, &mode.colors);
+        stream
+            .write_all(&packet(0, RGBCONTROLLER_UPDATEMODE, &payload))
+            .unwrap();
+        sync(&mut stream);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].to_bytes(),
+            RazerReport::static_rgb(0xFF, 0x00, 0x00).to_bytes()
+        );
+    }
+}
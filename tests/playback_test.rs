@@ -0,0 +1,209 @@
+use image::codecs::gif::GifEncoder;
+use image::{Delay, ImageFormat, Rgba, RgbaImage};
+use razer_rgb_mac::animation::Animation;
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use razer_rgb_mac::layout::{Key, Layout};
+use razer_rgb_mac::playback::*;
+use razer_rgb_mac::profile::{Layer, Profile};
+use std::io::Cursor;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(image: &RgbaImage) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    /// A keyboard-sized GIF per color, shown for `delays` milliseconds each.
+    fn gif(colors: &[[u8; 3]], delays: &[u32]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            for (&[r, g, b], &delay) in colors.iter().zip(delays) {
+                let image = RgbaImage::from_pixel(22, 6, Rgba([r, g, b, 255]));
+                let frame =
+                    image::Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(delay, 1));
+                encoder.encode_frame(frame).unwrap();
+            }
+        }
+        bytes
+    }
+
+    fn key(col: usize, x: f32, width: f32) -> Key {
+        Key {
+            row: 0,
+            col,
+            x,
+            y: 0.0,
+            width,
+            height: 1.0,
+            name: None,
+            code: None,
+        }
+    }
+
+    #[test]
+    fn test_grid_maps_pixels_to_cells() {
+        let mut image = RgbaImage::new(MATRIX_COLS as u32, MATRIX_ROWS as u32);
+        image.put_pixel(3, 2, Rgba([255, 0, 0, 255]));
+        image.put_pixel(21, 5, Rgba([0, 0, 255, 255]));
+        let layout = Layout::grid(MATRIX_ROWS, MATRIX_COLS);
+        let frame = resample(&image, &layout, MATRIX_ROWS, MATRIX_COLS);
+
+        assert_eq!(frame.get(2, 3), Rgb::RED);
+        assert_eq!(frame.get(5, 21), Rgb::BLUE);
+        // Fully transparent pixels are black.
+        assert_eq!(frame.get(0, 0), Rgb::BLACK);
+    }
+
+    #[test]
+    fn test_wide_keys_average_what_they_cover() {
+        let mut image = RgbaImage::new(8, 2);
+        for (x, color) in [[255, 0, 0], [0, 0, 255], [0, 255, 0], [255, 255, 255]]
+            .into_iter()
+            .enumerate()
+        {
+            for y in 0..2 {
+                image.put_pixel(2 * x as u32, y, Rgba([color[0], color[1], color[2], 255]));
+                image.put_pixel(
+                    2 * x as u32 + 1,
+                    y,
+                    Rgba([color[0], color[1], color[2], 128]),
+                );
+            }
+        }
+        // A 2u key, then two 1u keys lit from the matrix out of order.
+        let layout = Layout {
+            name: "test".to_string(),
+            keys: vec![key(0, 0.0, 2.0), key(5, 2.0, 1.0), key(1, 3.0, 1.0)],
+        };
+        let frame = resample(&image, &layout, 1, MATRIX_COLS);
+
+        // Half-transparent pixels count half.
+        assert_eq!(frame.get(0, 0), Rgb::new(96, 0, 96));
+        assert_eq!(frame.get(0, 5), Rgb::new(0, 192, 0));
+        assert_eq!(frame.get(0, 1), Rgb::new(192, 192, 192));
+        assert_eq!(frame.get(0, 2), Rgb::BLACK);
+
+        // One key smaller than a pixel takes the pixel under its center.
+        let tiny = Layout {
+            name: "tiny".to_string(),
+            keys: vec![key(0, 0.0, 1.0), key(1, 1.0, 7.0)],
+        };
+        let image = RgbaImage::from_pixel(1, 1, Rgba([10, 20, 30, 255]));
+        let frame = resample(&image, &tiny, 1, 2);
+        assert_eq!(frame.get(0, 0), Rgb::new(10, 20, 30));
+    }
+
+    #[test]
+    fn test_gif_frames_loop_at_speed() {
+        let clip = Clip::decode(&gif(&[[255, 0, 0], [0, 0, 255]], &[50, 0])).unwrap();
+        assert_eq!(clip.len(), 2);
+        // A zero delay shows for 100 ms, as in browsers.
+        assert_eq!(clip.duration(), Duration::from_millis(150));
+
+        let layout = Layout::grid(MATRIX_ROWS, MATRIX_COLS);
+        let mut playback = Playback::new(&clip, &layout, PlaybackSettings::default());
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        let ms = Duration::from_millis;
+        assert_eq!(playback.render(ms(0), &mut frame), Some(ms(50)));
+        assert_eq!(frame.get(3, 10), Rgb::RED);
+        assert_eq!(playback.render(ms(60), &mut frame), Some(ms(90)));
+        assert_eq!(frame.get(3, 10), Rgb::BLUE);
+        assert_eq!(playback.render(ms(160), &mut frame), Some(ms(40)));
+        assert_eq!(frame.get(3, 10), Rgb::RED);
+
+        let settings = PlaybackSettings {
+            speed: 2.0,
+            looping: true,
+        };
+        let mut fast = Playback::new(&clip, &layout, settings);
+        assert_eq!(fast.render(ms(30), &mut frame), Some(ms(45)));
+        assert_eq!(frame.get(0, 0), Rgb::BLUE);
+    }
+
+    #[test]
+    fn test_png_plays_once() {
+        let image = RgbaImage::from_pixel(44, 12, Rgba([0, 255, 0, 255]));
+        let clip = Clip::decode(&png(&image)).unwrap();
+        assert_eq!(clip.len(), 1);
+        assert!(Clip::decode(b"GIF89a garbage").is_err());
+        assert!(Clip::decode(b"plain text").is_err());
+
+        let settings = PlaybackSettings {
+            speed: 1.0,
+            looping: false,
+        };
+        let layout = Layout::grid(MATRIX_ROWS, MATRIX_COLS);
+        let mut playback = Playback::new(&clip, &layout, settings);
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        assert!(playback.render(Duration::ZERO, &mut frame).is_some());
+        assert_eq!(frame.get(5, 21), Rgb::GREEN);
+        assert_eq!(
+            playback.render(Duration::from_millis(100), &mut frame),
+            None
+        );
+
+        let stopped = PlaybackSettings {
+            speed: 0.0,
+            looping: true,
+        };
+        assert!(stopped.validate().is_err());
+        let runaway = PlaybackSettings {
+            speed: 1e30,
+            looping: true,
+        };
+        assert!(runaway.validate().is_err());
+    }
+
+    #[test]
+    fn test_profile_image_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("red.gif");
+        std::fs::write(&path, gif(&[[255, 0, 0]], &[200])).unwrap();
+
+        let text = format!(
+            r#"{{"name": "party", "effect": {{"effect": "off"}},
+                "layer": {{"kind": "image", "path": {:?}, "speed": 2, "loop": false}}}}"#,
+            path.to_str().unwrap()
+        );
+        let profile: Profile = serde_json::from_str(&text).unwrap();
+        let layer = profile.layer.clone().unwrap();
+        assert_eq!(
+            layer,
+            Layer::Image {
+                path: path.clone(),
+                playback: PlaybackSettings {
+                    speed: 2.0,
+                    looping: false,
+                },
+            }
+        );
+        let saved: Profile =
+            serde_json::from_str(&serde_json::to_string(&profile).unwrap()).unwrap();
+        assert_eq!(saved, profile);
+
+        let mut animation = layer
+            .animation(&Layout::grid(MATRIX_ROWS, MATRIX_COLS))
+            .unwrap();
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        assert_eq!(
+            animation.render(Duration::ZERO, &mut frame),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(frame.get(0, 0), Rgb::RED);
+
+        let missing = Layer::Image {
+            path: dir.path().join("missing.gif"),
+            playback: PlaybackSettings::default(),
+        };
+        assert!(missing
+            .animation(&Layout::grid(MATRIX_ROWS, MATRIX_COLS))
+            .is_err());
+    }
+}
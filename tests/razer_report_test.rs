@@ -1,3 +1,4 @@
+use razer_rgb_mac::color::Rgb;
 use razer_rgb_mac::razer_report::*;
 
 #[cfg(test)]
@@ -44,4 +45,42 @@ mod tests {
         assert_eq!(spectrum_cmd.data_size, 0x06);
         assert_eq!(spectrum_cmd.arguments[2], EXT_EFFECT_SPECTRUM);
     }
+
+    #[test]
+    fn test_spectrum_and_wave_effect_ids() {
+        assert_eq!(RazerReport::spectrum().arguments[2], 0x03);
+        let wave_cmd = RazerReport::wave(0x01, 0x28);
+        // Expected: 0x00^0x00^0x00^0x06^0x0F^0x02^0x01^0x05^0x04^0x01^0x28 = 0x22
+        assert_eq!(wave_cmd.crc, 0x22, "CRC for wave command is incorrect");
+        assert_eq!(wave_cmd.arguments[2], 0x04);
+        assert_eq!(&wave_cmd.arguments[3..5], &[0x01, 0x28]);
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let cmd = RazerReport::breathing(0x12, 0x34, 0x56);
+        let bytes = cmd.to_bytes();
+        assert_eq!(bytes.len(), REPORT_LEN);
+        assert_eq!(bytes[1], 0x1F);
+        assert_eq!(bytes[6], 0x0F);
+        assert_eq!(bytes[88], cmd.crc);
+        assert_eq!(RazerReport::from_bytes(&bytes).to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_custom_frame_row() {
+        let cmd = RazerReport::custom_frame_row(2, 0, &[Rgb::RED, Rgb::GREEN]);
+        assert_eq!(cmd.command_id, 0x03);
+        assert_eq!(cmd.data_size, 0x47);
+        assert_eq!(cmd.arguments[2], 2);
+        assert_eq!(cmd.arguments[3], 0);
+        assert_eq!(cmd.arguments[4], 1);
+        assert_eq!(&cmd.arguments[5..11], &[0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00]);
+    }
+
+    #[test]
+    #[should_panic(expected = "column 255 at the latest")]
+    fn test_custom_frame_row_past_last_column() {
+        RazerReport::custom_frame_row(0, 250, &[Rgb::BLACK; 25]);
+    }
 }
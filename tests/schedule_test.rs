@@ -0,0 +1,179 @@
+use chrono::{DateTime, Duration as TimeDelta, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+use razer_rgb_mac::schedule::*;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct FakeClock(Arc<Mutex<DateTime<FixedOffset>>>);
+
+    impl FakeClock {
+        fn at(text: &str) -> Self {
+            Self(Arc::new(Mutex::new(local(text))))
+        }
+
+        fn set(&self, text: &str) {
+            *self.0.lock().unwrap() = local(text);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> DateTime<FixedOffset> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    /// Parses `2024-06-21 19:00` as a local time in UTC+2.
+    fn local(text: &str) -> DateTime<FixedOffset> {
+        let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+        let naive = chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        offset.from_local_datetime(&naive).unwrap()
+    }
+
+    fn at(hour: u32, minute: u32) -> When {
+        When::At(NaiveTime::from_hms_opt(hour, minute, 0).unwrap())
+    }
+
+    const BERLIN: Location = Location {
+        latitude: 52.52,
+        longitude: 13.40,
+    };
+
+    #[test]
+    fn test_rules_parse_and_validate() {
+        let schedule: Schedule = serde_json::from_str(
+            r#"{"location": {"latitude": 52.52, "longitude": 13.4},
+                "rules": [{"at": "19:00", "profile": "evening"},
+                          {"sunset": -30, "profile": "warm", "priority": 2}]}"#,
+        )
+        .unwrap();
+        assert_eq!(schedule.rules[0], Rule::new(at(19, 0), "evening"));
+        assert_eq!(schedule.rules[1].when, When::Sunset(-30));
+        assert_eq!(schedule.rules[1].priority, 2);
+        assert!(schedule.validate().is_ok());
+        assert_eq!(schedule.rules[1].when.to_string(), "30 min before sunset");
+
+        let text = serde_json::to_string(&schedule.rules[0]).unwrap();
+        assert_eq!(text, r#"{"at":"19:00","profile":"evening"}"#);
+
+        let no_location = Schedule {
+            location: None,
+            ..schedule
+        };
+        assert!(no_location.validate().is_err());
+        assert!(serde_json::from_str::<Rule>(r#"{"at": "7pm", "profile": "x"}"#).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SCHEDULE_FILE);
+        assert_eq!(Schedule::load(&path).unwrap(), Schedule::default());
+        assert!(no_location.save(&path).is_err());
+    }
+
+    #[test]
+    fn test_sun_times_match_almanac() {
+        // London at midsummer 2024: sunrise 03:43 and sunset 20:21 UTC.
+        let london = Location {
+            latitude: 51.5072,
+            longitude: -0.1276,
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let (rise, set) = sun_times(date, london).unwrap();
+        let close = |actual: DateTime<Utc>, hour, minute| {
+            let expected = Utc.with_ymd_and_hms(2024, 6, 21, hour, minute, 0).unwrap();
+            (actual - expected).num_minutes().abs() <= 2
+        };
+        assert!(close(rise, 3, 43), "sunrise {rise}");
+        assert!(close(set, 20, 21), "sunset {set}");
+
+        // The midnight sun in Tromsø.
+        let tromso = Location {
+            latitude: 69.65,
+            longitude: 18.96,
+        };
+        assert_eq!(sun_times(date, tromso), None);
+    }
+
+    #[test]
+    fn test_latest_rule_wins_and_ties_go_to_priority() {
+        let mut schedule = Schedule {
+            location: Some(BERLIN),
+            rules: vec![
+                Rule::new(at(7, 0), "day"),
+                Rule::new(at(19, 0), "evening"),
+                Rule::new(at(23, 0), "off"),
+                Rule {
+                    priority: 1,
+                    ..Rule::new(at(19, 0), "movie")
+                },
+                Rule::new(at(19, 0), "ignored"),
+            ],
+        };
+        let profile = |schedule: &Schedule, time| schedule.active(local(time)).unwrap().profile;
+        assert_eq!(profile(&schedule, "2024-06-21 06:59"), "off");
+        assert_eq!(profile(&schedule, "2024-06-21 12:00"), "day");
+        assert_eq!(profile(&schedule, "2024-06-21 19:00"), "movie");
+        assert_eq!(profile(&schedule, "2024-06-21 23:30"), "off");
+
+        // Sunset in Berlin is about 21:33 local time at midsummer, 16:00
+        // in December, so it beats 19:00 in June only.
+        schedule.rules = vec![
+            Rule::new(at(19, 0), "evening"),
+            Rule::new(When::Sunset(0), "night"),
+        ];
+        assert_eq!(profile(&schedule, "2024-06-21 20:00"), "evening");
+        assert_eq!(profile(&schedule, "2024-06-21 22:00"), "night");
+        assert_eq!(profile(&schedule, "2024-12-21 18:00"), "night");
+        assert_eq!(profile(&schedule, "2024-12-21 20:00"), "evening");
+    }
+
+    #[test]
+    fn test_scheduler_reports_each_change_once() {
+        let schedule = Schedule {
+            location: None,
+            rules: vec![Rule::new(at(7, 0), "day"), Rule::new(at(19, 0), "evening")],
+        };
+        let clock = FakeClock::at("2024-06-21 12:00");
+        let mut scheduler = Scheduler::new(schedule.clone(), clock.clone());
+
+        assert_eq!(scheduler.poll().as_deref(), Some("day"));
+        assert_eq!(scheduler.poll(), None);
+        clock.set("2024-06-21 18:59");
+        assert_eq!(scheduler.poll(), None);
+        clock.set("2024-06-21 19:00");
+        assert_eq!(scheduler.poll().as_deref(), Some("evening"));
+        clock.set("2024-06-22 06:00");
+        assert_eq!(scheduler.poll(), None);
+        clock.set("2024-06-22 07:30");
+        assert_eq!(scheduler.poll().as_deref(), Some("day"));
+
+        // New rules are applied right away.
+        scheduler.set_schedule(schedule);
+        assert_eq!(scheduler.poll().as_deref(), Some("day"));
+    }
+
+    #[test]
+    fn test_upcoming_runs_past_midnight() {
+        let schedule = Schedule {
+            location: Some(BERLIN),
+            rules: vec![
+                Rule::new(at(7, 0), "day"),
+                Rule::new(When::Sunset(30), "night"),
+            ],
+        };
+        let scheduler = Scheduler::new(schedule, FakeClock::at("2024-06-21 12:00"));
+        let upcoming = scheduler.upcoming(3);
+
+        let profiles: Vec<_> = upcoming
+            .iter()
+            .map(|change| change.profile.as_str())
+            .collect();
+        assert_eq!(profiles, ["night", "day", "night"]);
+        let sunset = upcoming[0].at;
+        assert_eq!(sunset.date(), NaiveDate::from_ymd_opt(2024, 6, 21).unwrap());
+        assert!(sunset.time() > NaiveTime::from_hms_opt(21, 50, 0).unwrap());
+        assert!(sunset.time() < NaiveTime::from_hms_opt(22, 10, 0).unwrap());
+        assert!(upcoming[1].at - upcoming[0].at < TimeDelta::hours(10));
+    }
+}
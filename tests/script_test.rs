@@ -0,0 +1,169 @@
+use razer_rgb_mac::animation::render_frames;
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::frame::{Frame, MATRIX_COLS, MATRIX_ROWS};
+use razer_rgb_mac::layout::{Key, Layout};
+use razer_rgb_mac::script::*;
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> Layout {
+        Layout::grid(MATRIX_ROWS, MATRIX_COLS)
+    }
+
+    fn render(source: &str, layout: &Layout, t: f32) -> Result<Frame, String> {
+        let mut script = Script::compile(source, layout, Limits::default())?;
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        script.render(Duration::from_secs_f32(t), &mut frame)?;
+        Ok(frame)
+    }
+
+    /// Writes `source` and moves the file's time on, so a reload notices
+    /// even on file systems with coarse timestamps.
+    fn write(path: &Path, source: &str, age: u64) {
+        fs::write(path, source).unwrap();
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + age);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_script_draws_with_time_and_colors() {
+        let source = r##"
+            fill(rgb(10, 20, 300));
+            set(0, 0, color("red"));
+            if t > 1.0 {
+                set(5, 21, mix(color("black"), color("#ffffff"), 0.5));
+            }
+            set(2, 2, hsv(240.0, 1.0, 1.0));
+        "##;
+        let frame = render(source, &grid(), 0.0).unwrap();
+        assert_eq!(frame.get(0, 0), Rgb::RED);
+        assert_eq!(frame.get(1, 1), Rgb::new(10, 20, 255));
+        assert_eq!(frame.get(2, 2), Rgb::BLUE);
+        assert_eq!(frame.get(5, 21), Rgb::new(10, 20, 255));
+
+        let frame = render(source, &grid(), 1.5).unwrap();
+        assert_eq!(frame.get(5, 21), Rgb::new(128, 128, 128));
+
+        let err = render(r#"set(0, 0, color("nope"))"#, &grid(), 0.0).unwrap_err();
+        assert!(err.contains("nope"), "{err}");
+        assert!(render("set(6, 0, rgb(1, 1, 1))", &grid(), 0.0).is_err());
+        assert!(Script::compile("fill(", &grid(), Limits::default()).is_err());
+    }
+
+    #[test]
+    fn test_keys_carry_layout_positions() {
+        let layout = Layout {
+            name: "test".to_string(),
+            keys: vec![
+                Key {
+                    row: 0,
+                    col: 1,
+                    x: 0.0,
+                    y: 0.0,
+                    width: 1.0,
+                    height: 1.0,
+                    name: Some("esc".to_string()),
+                    code: None,
+                },
+                Key {
+                    row: 4,
+                    col: 7,
+                    x: 3.75,
+                    y: 5.0,
+                    width: 6.25,
+                    height: 1.0,
+                    name: Some("space".to_string()),
+                    code: None,
+                },
+            ],
+        };
+        let source = r#"
+            for key in keys {
+                if key.name == "esc" { set(key, rgb(0, 0, 255)); }
+                if key.x > width / 2.0 && key.w > 6.0 { set(key.row, key.col, rgb(0, 255, 0)); }
+            }
+        "#;
+        let frame = render(source, &layout, 0.0).unwrap();
+        assert_eq!(frame.get(0, 1), Rgb::BLUE);
+        assert_eq!(frame.get(4, 7), Rgb::GREEN);
+        assert_eq!(frame.get(0, 0), Rgb::BLACK);
+    }
+
+    #[test]
+    fn test_limits_stop_runaway_scripts() {
+        // Plenty of time, so a slow machine still hits the operation limit.
+        let counted = Limits {
+            time: Duration::from_secs(30),
+            ..Limits::default()
+        };
+        let mut script = Script::compile("loop { }", &grid(), counted).unwrap();
+        let mut frame = Frame::new(MATRIX_ROWS, MATRIX_COLS);
+        let err = script.render(Duration::ZERO, &mut frame).unwrap_err();
+        assert!(err.contains("operations"), "{err}");
+
+        let slow = Limits {
+            operations: 0,
+            time: Duration::from_millis(10),
+        };
+        let mut script = Script::compile("loop { }", &grid(), slow).unwrap();
+        let mut frame = Frame::filled(MATRIX_ROWS, MATRIX_COLS, Rgb::RED);
+        let err = script.render(Duration::ZERO, &mut frame).unwrap_err();
+        assert!(err.contains("longer than"), "{err}");
+        // A failed frame leaves the buffer alone.
+        assert_eq!(frame.get(0, 0), Rgb::RED);
+
+        // No modules and no eval.
+        assert!(render(r#"import "fs" as fs;"#, &grid(), 0.0).is_err());
+        assert!(render(r#"eval("fill(rgb(1, 1, 1))")"#, &grid(), 0.0).is_err());
+    }
+
+    #[test]
+    fn test_effect_reloads_when_the_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ScriptStore::new(dir.path());
+        let path = store.path("pulse").unwrap();
+        write(&path, "fill(rgb(255, 0, 0));", 0);
+        let mut effect = store.open("pulse", grid()).unwrap();
+
+        let frames = render_frames(&mut effect, 2);
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|frame| frame.get(3, 3) == Rgb::RED));
+
+        write(&path, "fill(rgb(0, 0, 255));", 1);
+        assert_eq!(render_frames(&mut effect, 1)[0].get(3, 3), Rgb::BLUE);
+
+        // A broken edit keeps the last good script running.
+        write(&path, "fill(", 2);
+        assert!(!effect.reload_if_changed());
+        assert_eq!(render_frames(&mut effect, 1)[0].get(3, 3), Rgb::BLUE);
+
+        // A script failing at run time leaves the keys dark.
+        write(&path, "set(99, 0, rgb(1, 1, 1));", 3);
+        assert_eq!(render_frames(&mut effect, 1)[0].get(3, 3), Rgb::BLACK);
+    }
+
+    #[test]
+    fn test_store_lists_scripts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ScriptStore::new(dir.path().join("scripts"));
+        assert_eq!(store.list().unwrap(), Vec::<String>::new());
+        assert!(store.path("../escape").is_err());
+        assert!(store.open("missing", grid()).is_err());
+
+        fs::create_dir_all(dir.path().join("scripts")).unwrap();
+        for name in ["wave.rhai", "fire.rhai", "notes.txt"] {
+            fs::write(dir.path().join("scripts").join(name), "").unwrap();
+        }
+        assert_eq!(store.list().unwrap(), ["fire", "wave"]);
+    }
+}
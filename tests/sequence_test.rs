@@ -0,0 +1,226 @@
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::device::{Device, LightingState};
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::sequence::*;
+use razer_rgb_mac::transition::{Easing, Step, STEP_INTERVAL};
+use razer_rgb_mac::transport::MockTransport;
+use razer_rgb_mac::worker::{Command, Event, Worker};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn keyframe(at: u64, effect: Effect, easing: Option<Easing>) -> Keyframe {
+        Keyframe {
+            easing,
+            ..Keyframe::new(ms(at), effect)
+        }
+    }
+
+    fn trigger(at: u64, name: &str) -> Trigger {
+        Trigger {
+            at: ms(at),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_state_at_eases_still_pictures_and_holds_animations() {
+        let mut red = keyframe(1000, Effect::Static(Rgb::RED), Some(Easing::Linear));
+        red.brightness = Some(100);
+        let sequence = Sequence::new(
+            "test",
+            vec![
+                keyframe(0, Effect::Off, None),
+                red,
+                keyframe(2000, Effect::Static(Rgb::BLUE), None),
+            ],
+        );
+        let base = LightingState::default();
+        assert_eq!(sequence.length(), ms(2000));
+
+        let halfway = sequence.state_at(ms(500), &base);
+        assert_eq!(halfway.effect, Effect::Custom);
+        assert_eq!(halfway.frame.get(3, 4), Rgb::BLACK.lerp(Rgb::RED, 0.5));
+        assert_eq!(halfway.brightness, 178);
+
+        let red = sequence.state_at(ms(1999), &base);
+        assert_eq!(
+            (red.effect, red.brightness),
+            (Effect::Static(Rgb::RED), 100)
+        );
+        // Later keyframes keep the brightness they don't set.
+        let blue = sequence.state_at(ms(5000), &base);
+        assert_eq!(
+            (blue.effect, blue.brightness),
+            (Effect::Static(Rgb::BLUE), 100)
+        );
+
+        // An animated effect can't be blended, so only the brightness eases.
+        let mut dim = keyframe(1000, Effect::Static(Rgb::RED), Some(Easing::Linear));
+        dim.brightness = Some(0);
+        let animated = Sequence::new("test", vec![keyframe(0, Effect::Spectrum, None), dim]);
+        let state = animated.state_at(ms(250), &base);
+        assert_eq!((state.effect, state.brightness), (Effect::Spectrum, 191));
+    }
+
+    #[test]
+    fn test_player_sends_only_changes() {
+        let mut dim = keyframe(1000, Effect::Static(Rgb::RED), None);
+        dim.brightness = Some(50);
+        let sequence = Sequence::new(
+            "test",
+            vec![
+                keyframe(0, Effect::Static(Rgb::RED), None),
+                dim,
+                keyframe(3000, Effect::Static(Rgb::BLUE), Some(Easing::Linear)),
+            ],
+        );
+        let base = LightingState::default();
+        let mut player = SequencePlayer::new(&sequence, base.clone());
+
+        let tick = player.advance(ms(0));
+        let red = LightingState {
+            effect: Effect::Static(Rgb::RED),
+            ..base.clone()
+        };
+        assert_eq!(tick.steps, vec![Step::Settle(red)]);
+        assert_eq!(tick.next, Some(ms(1000)));
+
+        let tick = player.advance(ms(1000));
+        assert_eq!(tick.steps, vec![Step::Brightness(50)]);
+        // Easing toward the last keyframe wants frames at the step rate.
+        assert_eq!(tick.next, Some(STEP_INTERVAL));
+
+        let tick = player.advance(ms(2000));
+        assert!(matches!(tick.steps[..], [Step::Frame(_)]));
+        let tick = player.advance(ms(3000));
+        assert_eq!(tick.steps.len(), 1);
+        assert!(matches!(&tick.steps[0], Step::Settle(state)
+            if state.effect == Effect::Static(Rgb::BLUE) && state.brightness == 50));
+        assert_eq!(tick.next, None);
+    }
+
+    #[test]
+    fn test_looping_wraps_and_fires_triggers_every_pass() {
+        let mut sequence = Sequence::new(
+            "test",
+            vec![
+                keyframe(0, Effect::Static(Rgb::RED), None),
+                keyframe(400, Effect::Static(Rgb::GREEN), None),
+            ],
+        );
+        sequence.looping = true;
+        sequence.length = Some(ms(1000));
+        sequence.triggers = vec![trigger(0, "start"), trigger(500, "half")];
+        assert!(sequence.validate().is_ok());
+        let base = LightingState::default();
+        assert_eq!(
+            sequence.state_at(ms(2300), &base).effect,
+            Effect::Static(Rgb::RED)
+        );
+
+        let mut player = SequencePlayer::new(&sequence, base);
+        assert_eq!(player.advance(ms(0)).triggers, ["start"]);
+        let tick = player.advance(ms(600));
+        assert_eq!(tick.triggers, ["half"]);
+        // Wakes up again for the wrap.
+        assert_eq!(tick.next, Some(ms(400)));
+        assert_eq!(player.advance(ms(1100)).triggers, ["start"]);
+        assert_eq!(
+            player.advance(ms(3600)).triggers,
+            ["half", "start", "half", "start", "half"]
+        );
+        assert_eq!(player.advance(ms(5000)).next, Some(ms(400)));
+    }
+
+    #[test]
+    fn test_json_validation_and_store() {
+        let sequence: Sequence = serde_json::from_str(
+            r#"{"name": "alert", "loop": true, "length_ms": 800,
+                "keyframes": [
+                    {"at_ms": 0, "effect": {"effect": "static", "color": "red"}, "brightness": 255},
+                    {"at_ms": 400, "effect": {"effect": "off"}, "easing": "ease-out"}],
+                "triggers": [{"at_ms": 400, "name": "dark"}]}"#,
+        )
+        .unwrap();
+        assert!(sequence.looping);
+        assert_eq!(sequence.length(), ms(800));
+        assert_eq!(sequence.keyframes[1].easing, Some(Easing::EaseOut));
+        assert!(sequence.validate().is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = SequenceStore::new(dir.path().join("sequences"));
+        assert_eq!(store.list().unwrap(), Vec::<String>::new());
+        store.save(&sequence).unwrap();
+        assert_eq!(store.list().unwrap(), ["alert"]);
+        assert_eq!(store.load("alert").unwrap(), sequence);
+
+        let mut unsorted = sequence.clone();
+        unsorted.keyframes.reverse();
+        assert!(unsorted.validate().unwrap_err().contains("time order"));
+        let mut short = sequence.clone();
+        short.length = Some(ms(300));
+        assert!(short.validate().unwrap_err().contains("past the end"));
+        let mut empty = sequence.clone();
+        empty.keyframes.clear();
+        assert!(store.save(&empty).is_err());
+        let mut invalid = sequence;
+        invalid.name = "../escape".to_string();
+        assert!(store.save(&invalid).is_err());
+        store.delete("alert").unwrap();
+        assert!(store.load("alert").is_err());
+    }
+
+    #[test]
+    fn test_worker_plays_sequences_and_reports_triggers() {
+        let device = Arc::new(Mutex::new(Device::new(MockTransport::new())));
+        let worker = Worker::spawn(Arc::clone(&device), || {});
+        let wait = Duration::from_secs(5);
+
+        let mut sequence = Sequence::new(
+            "short",
+            vec![
+                keyframe(0, Effect::Static(Rgb::RED), None),
+                keyframe(60, Effect::Static(Rgb::BLUE), None),
+            ],
+        );
+        sequence.triggers = vec![trigger(30, "middle")];
+        worker.submit(Command::Play(sequence.clone()));
+        assert_eq!(
+            worker.wait_event(wait),
+            Some(Event::Triggered {
+                sequence: "short".to_string(),
+                trigger: "middle".to_string(),
+            })
+        );
+        let Some(Event::Applied { command, state, .. }) = worker.wait_event(wait) else {
+            panic!("expected the sequence to finish");
+        };
+        assert_eq!(command, "sequence");
+        assert_eq!(state.effect, Effect::Static(Rgb::BLUE));
+
+        // A looping sequence plays until something else comes along.
+        sequence.looping = true;
+        sequence.triggers.clear();
+        worker.submit(Command::Play(sequence));
+        std::thread::sleep(Duration::from_millis(100));
+        worker.submit(Command::SetEffect(Effect::Spectrum));
+        assert_eq!(
+            worker.wait_event(wait),
+            Some(Event::Superseded {
+                command: "sequence"
+            })
+        );
+        let Some(Event::Applied { state, .. }) = worker.wait_event(wait) else {
+            panic!("expected the new effect to apply");
+        };
+        assert_eq!(state.effect, Effect::Spectrum);
+    }
+}
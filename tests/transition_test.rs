@@ -0,0 +1,197 @@
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::device::{Device, LightingState};
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::frame::Frame;
+use razer_rgb_mac::profile::Profile;
+use razer_rgb_mac::transition::*;
+use razer_rgb_mac::transport::MockTransport;
+use razer_rgb_mac::worker::{Command, Event, Worker};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(effect: Effect, brightness: u8) -> LightingState {
+        LightingState {
+            effect,
+            brightness,
+            ..LightingState::default()
+        }
+    }
+
+    fn brightness_steps(fade: &Fade) -> Vec<u8> {
+        fade.steps
+            .iter()
+            .filter_map(|(_, step)| match step {
+                Step::Brightness(brightness) => Some(*brightness),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_still_pictures_crossfade_and_settle() {
+        let from = state(Effect::Static(Rgb::RED), 255);
+        let to = state(Effect::Static(Rgb::BLUE), 255);
+        let transition = Transition::new(Duration::from_millis(99), Easing::Linear);
+        let fade = Fade::new(&from, &to, &transition);
+
+        assert_eq!(fade.steps.len(), 3);
+        let (at, Step::Frame(frame)) = &fade.steps[0] else {
+            panic!("expected a frame, got {:?}", fade.steps[0]);
+        };
+        assert_eq!(*at, Duration::from_millis(33));
+        assert_eq!(frame.get(2, 7), Rgb::new(170, 0, 85));
+        assert!(matches!(fade.steps[1].1, Step::Frame(_)));
+        assert_eq!(
+            fade.steps[2],
+            (Duration::from_millis(99), Step::Settle(to.clone()))
+        );
+        assert!(brightness_steps(&fade).is_empty());
+    }
+
+    #[test]
+    fn test_animated_effects_dip_through_black() {
+        let from = state(Effect::Spectrum, 200);
+        let to = state(Effect::Breathing(Rgb::GREEN), 100);
+        let transition = Transition::new(Duration::from_millis(198), Easing::Linear);
+        let fade = Fade::new(&from, &to, &transition);
+
+        assert_eq!(brightness_steps(&fade), vec![133, 67, 0, 33, 67, 100]);
+        let settles: Vec<_> = fade
+            .steps
+            .iter()
+            .filter(|(_, step)| matches!(step, Step::Settle(_)))
+            .collect();
+        // One switch while dark; a second would restart the breathing.
+        assert_eq!(settles.len(), 1);
+        assert_eq!(
+            settles[0].1,
+            Step::Settle(LightingState {
+                brightness: 0,
+                ..to.clone()
+            })
+        );
+        assert_eq!(
+            fade.steps.last().unwrap().0,
+            transition.duration,
+            "the fade ends on time"
+        );
+    }
+
+    #[test]
+    fn test_zero_duration_switches_at_once() {
+        let mut frame = Frame::default();
+        frame.set(0, 0, Rgb::WHITE);
+        let from = state(Effect::Spectrum, 255);
+        let target = Target {
+            effect: Effect::Custom,
+            frame: Some(frame.clone()),
+            brightness: Some(80),
+        };
+        let to = target.resolve(&from);
+        assert_eq!(to.frame, frame);
+        assert_eq!(to.brightness, 80);
+
+        let fade = Fade::new(&from, &to, &Transition::default());
+        assert_eq!(fade.steps, vec![(Duration::ZERO, Step::Settle(to))]);
+
+        let mock = MockTransport::new();
+        let mut device = Device::new(mock.clone());
+        fade.run(&mut device).unwrap();
+        assert_eq!(device.effect(), Effect::Custom);
+        assert_eq!(device.brightness(), 80);
+        assert_eq!(device.frame().get(0, 0), Rgb::WHITE);
+    }
+
+    #[test]
+    fn test_interrupted_dip_restores_brightness() {
+        let from = state(
+            Effect::Wave {
+                direction: 0,
+                speed: 1,
+            },
+            255,
+        );
+        let to = state(Effect::Spectrum, 120);
+        let transition = Transition::new(Duration::from_millis(330), Easing::EaseInOut);
+        let fade = Fade::new(&from, &to, &transition);
+
+        let mut applied = Vec::new();
+        let mut waits = 0;
+        let finished = fade
+            .play_with(
+                |step| {
+                    applied.push(step.clone());
+                    Ok(())
+                },
+                |_| {
+                    waits += 1;
+                    waits < 3
+                },
+            )
+            .unwrap();
+
+        assert!(!finished);
+        assert_eq!(applied.len(), 3);
+        assert_eq!(applied.last(), Some(&Step::Brightness(120)));
+    }
+
+    #[test]
+    fn test_parse_options_and_profile_json() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("250"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("1e20s").is_err());
+        assert!(parse_duration("inf").is_err());
+        assert_eq!("ease-out".parse(), Ok(Easing::EaseOut));
+        assert!("bouncy".parse::<Easing>().is_err());
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+
+        let profile: Profile = serde_json::from_str(
+            r#"{"name": "calm", "effect": {"effect": "spectrum"},
+                "transition": {"duration_ms": 800}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile.transition,
+            Some(Transition::new(
+                Duration::from_millis(800),
+                Easing::EaseInOut
+            ))
+        );
+        let json = serde_json::to_string(&Profile::new("plain", Effect::Off)).unwrap();
+        assert!(!json.contains("transition"));
+    }
+
+    #[test]
+    fn test_worker_fade_gives_way_to_new_commands() {
+        let device = Arc::new(Mutex::new(Device::new(MockTransport::new())));
+        let worker = Worker::spawn(Arc::clone(&device), || {});
+        let slow = Transition::new(Duration::from_secs(30), Easing::Linear);
+        worker.submit(Command::Fade(
+            Target::effect(Effect::Static(Rgb::RED)),
+            slow,
+        ));
+        std::thread::sleep(Duration::from_millis(100));
+        worker.submit(Command::SetEffect(Effect::Static(Rgb::BLUE)));
+
+        let wait = Duration::from_secs(5);
+        assert_eq!(
+            worker.wait_event(wait),
+            Some(Event::Superseded { command: "static" })
+        );
+        let Some(Event::Applied { state, .. }) = worker.wait_event(wait) else {
+            panic!("expected the new effect to apply");
+        };
+        assert_eq!(state.effect, Effect::Static(Rgb::BLUE));
+        // The dip was cut short, but the keys aren't left dark.
+        assert_eq!(state.brightness, LightingState::default().brightness);
+    }
+}
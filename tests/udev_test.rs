@@ -0,0 +1,57 @@
+use razer_rgb_mac::descriptor::{DEVICES, ORNATA_V3};
+use razer_rgb_mac::device::OpenError;
+use razer_rgb_mac::udev::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_covers_every_device() {
+        let rule = rule();
+        for descriptor in DEVICES {
+            assert!(rule.contains(&format!(
+                "ATTRS{{idVendor}}==\"1532\", ATTRS{{idProduct}}==\"{:04x}\"",
+                descriptor.product_id
+            )));
+        }
+        assert!(rule
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .all(|line| line.ends_with("TAG+=\"uaccess\"")));
+    }
+
+    #[test]
+    fn test_install_writes_rule() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("70-razer-rgb.rules");
+        install(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), rule());
+    }
+
+    #[test]
+    fn test_open_errors_are_classified() {
+        assert_eq!(
+            OpenError::classify(&ORNATA_V3, rusb::Error::Access),
+            OpenError::Permission(&ORNATA_V3)
+        );
+        assert_eq!(
+            OpenError::classify(&ORNATA_V3, rusb::Error::Busy),
+            OpenError::Busy(&ORNATA_V3)
+        );
+        assert_eq!(
+            OpenError::classify(&ORNATA_V3, rusb::Error::NoDevice),
+            OpenError::NotFound
+        );
+        assert_eq!(
+            OpenError::classify(&ORNATA_V3, rusb::Error::Pipe),
+            OpenError::Usb(&ORNATA_V3, rusb::Error::Pipe)
+        );
+        assert!(OpenError::Busy(&ORNATA_V3)
+            .to_string()
+            .contains("Razer Ornata V3 is busy"));
+        assert!(OpenError::Permission(&ORNATA_V3)
+            .to_string()
+            .starts_with("permission denied opening the Razer Ornata V3"));
+    }
+}
@@ -0,0 +1,158 @@
+use razer_rgb_mac::color::Rgb;
+use razer_rgb_mac::device::Device;
+use razer_rgb_mac::effect::Effect;
+use razer_rgb_mac::frame::Frame;
+use razer_rgb_mac::razer_report::*;
+use razer_rgb_mac::transition::{Target, Transition};
+use razer_rgb_mac::transport::{MockTransport, TransportError};
+use razer_rgb_mac::worker::*;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_coalesce_keeps_latest_per_slot() {
+        let batch = vec![
+            Command::SetEffect(Effect::Static(Rgb::new(255, 0, 0))),
+            Command::SetBrightness(10),
+            Command::SetEffect(Effect::Spectrum),
+            Command::SetBrightness(20),
+        ];
+        let (keep, dropped) = coalesce(batch);
+        assert_eq!(
+            keep,
+            vec![
+                Command::SetEffect(Effect::Spectrum),
+                Command::SetBrightness(20)
+            ]
+        );
+        assert_eq!(dropped.len(), 2);
+    }
+
+    #[test]
+    fn test_coalesce_keeps_frame_before_custom() {
+        let frame = Frame::default();
+        let batch = vec![
+            Command::SetFrame(frame.clone()),
+            Command::SetEffect(Effect::Custom),
+        ];
+        let (keep, dropped) = coalesce(batch);
+        assert_eq!(keep.len(), 2);
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn test_coalesce_keeps_frame_before_fade_to_custom() {
+        let frame = Frame::default();
+        let fade = |target| Command::Fade(target, Transition::default());
+        let batch = vec![
+            Command::SetFrame(frame.clone()),
+            fade(Target::effect(Effect::Custom)),
+        ];
+        let (keep, dropped) = coalesce(batch);
+        assert_eq!(keep.len(), 2);
+        assert!(dropped.is_empty());
+
+        // A fade that brings its own frame leaves nothing of the upload.
+        let batch = vec![
+            Command::SetFrame(frame.clone()),
+            fade(Target {
+                frame: Some(frame),
+                ..Target::effect(Effect::Custom)
+            }),
+        ];
+        let (keep, dropped) = coalesce(batch);
+        assert_eq!(keep.len(), 1);
+        assert_eq!(dropped.len(), 1);
+    }
+
+    #[test]
+    fn test_worker_reports_applied_and_failed() {
+        let mock = MockTransport::new();
+        let device = Arc::new(Mutex::new(Device::new(mock.clone())));
+        let worker = Worker::spawn(device, || {});
+
+        worker.submit(Command::SetEffect(Effect::Static(Rgb::new(0, 255, 0))));
+        match worker.wait_event(WAIT).unwrap() {
+            Event::Applied { command, state, .. } => {
+                assert_eq!(command, "static");
+                assert_eq!(state.effect, Effect::Static(Rgb::new(0, 255, 0)));
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        mock.push_error(TransportError::Usb(rusb::Error::Timeout));
+        worker.submit(Command::SetBrightness(5));
+        assert_eq!(
+            worker.wait_event(WAIT).unwrap(),
+            Event::Failed {
+                command: "brightness",
+                error: TransportError::Usb(rusb::Error::Timeout),
+            }
+        );
+    }
+
+    #[test]
+    fn test_queued_commands_are_coalesced() {
+        let mock = MockTransport::new();
+        let device = Arc::new(Mutex::new(Device::new(mock.clone())));
+        let worker = Worker::spawn(Arc::clone(&device), || {});
+
+        // Keep the worker off the device while the queue fills up.
+        let guard = device.lock().unwrap();
+        worker.submit(Command::SetEffect(Effect::Static(Rgb::new(255, 0, 0))));
+        worker.submit(Command::SetEffect(Effect::Static(Rgb::new(0, 255, 0))));
+        worker.submit(Command::SetEffect(Effect::Static(Rgb::new(0, 0, 255))));
+        drop(guard);
+
+        let events: Vec<Event> = (0..3).map(|_| worker.wait_event(WAIT).unwrap()).collect();
+        let superseded = events
+            .iter()
+            .filter(|event| matches!(event, Event::Superseded { .. }))
+            .count();
+        assert_eq!(superseded, 2);
+        assert!(matches!(events[2], Event::Applied { .. }));
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].to_bytes(),
+            RazerReport::static_rgb(0, 0, 255).to_bytes()
+        );
+    }
+
+    #[test]
+    fn test_raw_commands_return_response() {
+        let mock = MockTransport::new();
+        let mut failure = RazerReport::get_serial();
+        failure.status = RAZER_CMD_NOT_SUPPORTED;
+        mock.push_response(failure);
+        let device = Arc::new(Mutex::new(Device::new(mock.clone())));
+        let worker = Worker::spawn(Arc::clone(&device), || {});
+
+        let guard = device.lock().unwrap();
+        worker.submit(Command::Raw(RazerReport::get_serial()));
+        worker.submit(Command::Restore(Default::default()));
+        drop(guard);
+
+        match worker.wait_event(WAIT).unwrap() {
+            Event::Exchanged {
+                request, response, ..
+            } => {
+                assert_eq!(request, RazerReport::get_serial());
+                assert_eq!(response.unwrap().status, RAZER_CMD_NOT_SUPPORTED);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        // The restore queued behind it must not have swallowed the raw report.
+        assert!(matches!(
+            worker.wait_event(WAIT).unwrap(),
+            Event::Applied { .. }
+        ));
+    }
+}